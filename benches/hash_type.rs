@@ -0,0 +1,136 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Benchmark of the PA-Star hash types
+ *
+ * Times `Coord::get_id` for each hash type and prints, for every thread
+ * count and shift, how unevenly a search wavefront is spread over threads
+ * (largest thread load over the mean, 1.00 is perfect), and for the
+ * choices of `choose_default_hash` also the fraction of successors sent to
+ * another thread:
+ *   cargo bench --bench hash_type
+ */
+
+use astar_msa_rust::coord::Coord;
+use astar_msa_rust::coord_hash::{CoordHash, HashType, SUM_HASH_SPREAD};
+use astar_msa_rust::HASH_SHIFT;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+
+const HASH_TYPES: [HashType; 4] = [HashType::FZorder, HashType::PZorder, HashType::FSum, HashType::PSum];
+const SEQ_LEN: u16 = 300;
+
+/// Deterministic coordinates around the lattice diagonal, grouped by
+/// wavefront (coordinate sum), like the nodes A-Star keeps open together
+fn wavefronts<const N: usize>() -> Vec<Vec<Coord<N>>> {
+    let mut state = 0x9E3779B97F4A7C15u64;
+    let mut next = move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) as i32
+    };
+
+    (1..12)
+        .map(|step| {
+            let center = SEQ_LEN as i32 * step / 12;
+            (0..2000)
+                .map(|_| {
+                    let mut coords = [0u16; N];
+                    for c in coords.iter_mut() {
+                        *c = (center + next() % 31 - 15).clamp(0, SEQ_LEN as i32) as u16;
+                    }
+                    Coord::from_array(coords)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Mean over wavefronts of the largest thread load divided by the mean load
+fn imbalance<const N: usize>(fronts: &[Vec<Coord<N>>], hash_type: HashType, threads: usize, shift: usize) -> f64 {
+    let total: f64 = fronts
+        .iter()
+        .map(|front| {
+            let mut load = vec![0usize; threads];
+            for coord in front {
+                load[coord.get_id(threads, hash_type, shift, &[])] += 1;
+            }
+            *load.iter().max().unwrap() as f64 * threads as f64 / front.len() as f64
+        })
+        .sum();
+    total / fronts.len() as f64
+}
+
+/// Fraction of the successors of the wavefront nodes (every move advancing
+/// at least one sequence) that another thread owns, each one a message
+fn remote_fraction<const N: usize>(fronts: &[Vec<Coord<N>>], hash_type: HashType, threads: usize, shift: usize) -> f64 {
+    let (mut remote, mut moves) = (0usize, 0usize);
+    for coord in fronts.iter().flatten() {
+        let owner = coord.get_id(threads, hash_type, shift, &[]);
+        for neigh_num in 1..1u32 << N {
+            let mut next = *coord;
+            for dim in (0..N).filter(|dim| neigh_num & (1 << dim) != 0) {
+                next.set(dim, coord.get(dim) + 1);
+            }
+            remote += (next.get_id(threads, hash_type, shift, &[]) != owner) as usize;
+            moves += 1;
+        }
+    }
+    remote as f64 / moves as f64
+}
+
+fn bench_hash<const N: usize>(c: &mut Criterion) {
+    let fronts = wavefronts::<N>();
+
+    for threads in [4, 16] {
+        for shift in [0, 4, 12] {
+            let report: Vec<String> = HASH_TYPES
+                .iter()
+                .map(|&h| format!("{} {:.2}", CoordHash::get_hash_name(h), imbalance(&fronts, h, threads, shift)))
+                .collect();
+            println!("N={} threads={} shift={}: {}", N, threads, shift, report.join(", "));
+        }
+    }
+
+    // The rule of `choose_default_hash`: the sum hash at the largest shift
+    // within `SUM_HASH_SPREAD` and one past it, against Z-order at the same
+    // shift and at the default `HASH_SHIFT`
+    for threads in [2, 4, 8, 16] {
+        let within = (0..64).take_while(|&s| threads << s <= SUM_HASH_SPREAD).last().unwrap_or(0);
+        let trials = [
+            (HashType::FSum, within),
+            (HashType::FSum, within + 1),
+            (HashType::FZorder, within),
+            (HashType::FZorder, HASH_SHIFT),
+        ];
+        let report: Vec<String> = trials
+            .iter()
+            .map(|&(h, shift)| format!(
+                "{} shift {} {:.2}/{:.2}",
+                CoordHash::get_hash_name(h), shift, imbalance(&fronts, h, threads, shift), remote_fraction(&fronts, h, threads, shift)
+            ))
+            .collect();
+        println!("N={} threads={} (imbalance/remote): {}", N, threads, report.join(", "));
+    }
+
+    let coords: Vec<Coord<N>> = fronts.concat();
+    let mut group = c.benchmark_group(format!("hash N={}", N));
+    for hash_type in HASH_TYPES {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(CoordHash::get_hash_name(hash_type)),
+            &hash_type,
+            |b, &hash_type| {
+                b.iter(|| {
+                    coords
+                        .iter()
+                        .map(|coord| coord.get_id(16, black_box(hash_type), 4, &[]))
+                        .sum::<usize>()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_hash::<3>, bench_hash::<4>, bench_hash::<5>, bench_hash::<8>);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Benchmark of the pairwise DP fill used by the heuristic phase
+ *
+ * Compare the scalar and anti-diagonal fills with:
+ *   cargo bench --bench pair_align
+ *   cargo bench --bench pair_align --features simd
+ */
+
+use astar_msa_rust::cost::Cost;
+use astar_msa_rust::pair_align::PairAlign;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+const RESIDUES: &[u8] = b"ACDEFGHIKLMNPQRSTVWY";
+
+/// Deterministic pseudo-random protein sequence
+fn protein(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            RESIDUES[(state >> 33) as usize % RESIDUES.len()]
+        })
+        .collect()
+}
+
+fn bench_pair_align(c: &mut Criterion) {
+    Cost::set_cost_pam250();
+
+    let s1 = protein(1000, 1);
+    let s2 = protein(1000, 2);
+    let s3 = protein(1200, 3);
+
+    c.bench_function("pair_align 1000x1000", |b| {
+        b.iter(|| PairAlign::new((0, 1), black_box(&s1), black_box(&s2)))
+    });
+    c.bench_function("pair_align 1000x1200", |b| {
+        b.iter(|| PairAlign::new((0, 2), black_box(&s1), black_box(&s3)))
+    });
+}
+
+criterion_group!(benches, bench_pair_align);
+criterion_main!(benches);
@@ -0,0 +1,280 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Library entry point: align in-memory sequences in one call
+ */
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::alignment::Alignment;
+use crate::cost::{Cost, ScoringScheme, Matrix};
+use crate::heuristic_hpair::HeuristicHPair;
+use crate::logger;
+use crate::msa_options::{PAStarOpt, PAStarOptBuilder};
+use crate::output_sink::{AppendFastaSink, OutputSink};
+use crate::pair_align::PairAlign;
+use crate::pastar;
+use crate::read_fasta::read_inline_sequences;
+use crate::sequences::{SequenceSet, Sequences, StopHandling};
+
+/// Align `sequences` with the active cost matrix (see `Cost::set_matrix`),
+/// failing if none was selected. Gap characters and trailing stop codons
+/// are dropped. Replaces the loaded sequences and runs the serial A-Star
+/// when `options.threads_num` is 1, PA-Star otherwise.
+///
+/// ```
+/// use astar_msa_rust::{align, Cost};
+/// use astar_msa_rust::msa_options::PAStarOpt;
+///
+/// Cost::set_cost_nuc();
+/// let seqs = vec!["ACGT".to_string(), "AGT".to_string()];
+/// let options = PAStarOpt::builder().threads(1).build().unwrap();
+/// let alignment = align(&seqs, &options).unwrap();
+/// assert_eq!(alignment.seqs()[1], "A-GT");
+/// ```
+pub fn align(sequences: &[String], options: &PAStarOpt) -> Result<Alignment, String> {
+    load(sequences, options.common.to_reference)?;
+    pastar::run_pastar_for_sequences(options.clone())
+}
+
+/// Align each set of sequences in `inputs` with `params`, handing every
+/// alignment to `sink` as soon as it's done. Stops at the first failure.
+/// An `append_output` file is opened once for the whole batch.
+pub fn align_batch(inputs: &[Vec<String>], params: &AlignParams, sink: &mut dyn OutputSink) -> Result<(), String> {
+    let mut options = params.options.clone().build()?;
+    let mut appended = match options.common.append_output.take() {
+        Some(filename) if !options.common.summary_only => Some(AppendFastaSink::open(&filename, &options.common)?),
+        _ => None,
+    };
+    for sequences in inputs {
+        params.activate_costs();
+        let alignment = align(sequences, &options)?;
+        if let Some(appended) = &mut appended {
+            appended.emit(&alignment)?;
+        }
+        sink.emit(&alignment)?;
+    }
+    Ok(())
+}
+
+/// Replace the loaded sequences and build the heuristic for them, or only
+/// the pairs against sequence 0 when aligning `to_reference`
+fn load(sequences: &[String], to_reference: bool) -> Result<(), String> {
+    Sequences::clear();
+    read_inline_sequences(sequences, &[])?;
+    Sequences::normalize(StopHandling::default())?;
+    if to_reference {
+        HeuristicHPair::init_reference_with(true)?;
+    } else {
+        Sequences::check_search_count()?;
+        HeuristicHPair::init()?;
+    }
+    Ok(())
+}
+
+/// Sequences, cost matrix and heuristic set up once by `prepare`. Each
+/// `run` reinstalls them, so searches with different options skip the
+/// loading and the pairwise alignments. Clones share the same inputs.
+#[derive(Clone)]
+pub struct PreparedProblem {
+    sequences: Arc<SequenceSet>,
+    costs: Arc<ScoringScheme>,
+    aligns: Arc<Vec<PairAlign>>,
+}
+
+impl PreparedProblem {
+    /// The sequences, cost matrix and heuristic loaded now, once
+    /// `HeuristicHPair::init` has run
+    pub fn capture() -> Self {
+        PreparedProblem {
+            sequences: Arc::new(Sequences::snapshot()),
+            costs: Arc::new(Cost::matrix_snapshot()),
+            aligns: HeuristicHPair::shared_aligns(),
+        }
+    }
+
+    /// Make this problem the loaded one again
+    pub fn install(&self) {
+        Sequences::restore(&self.sequences);
+        Cost::restore(&self.costs);
+        HeuristicHPair::install(self.aligns.clone());
+    }
+
+    /// Search the prepared problem with `options`
+    pub fn run(&self, options: &PAStarOpt) -> Result<Alignment, String> {
+        self.install();
+        pastar::run_pastar_for_sequences(options.clone())
+    }
+
+    /// Run `search` `times` times on this problem, reinstalled before each
+    /// run, and print the wall time of each with the min, median and max.
+    /// `search` is told whether it's the first run; later ones run quiet
+    /// (the log file still gets their output) and should write no files. Fails if a later run finds a
+    /// different alignment. Returns the first alignment and the times.
+    pub fn repeat(
+        &self,
+        times: usize,
+        mut search: impl FnMut(bool) -> Result<Alignment, String>,
+    ) -> Result<(Alignment, Vec<Duration>), String> {
+        let was_quiet = logger::is_quiet();
+        let mut first: Option<Alignment> = None;
+        let mut durations = Vec::with_capacity(times);
+        for run in 0..times {
+            self.install();
+            logger::set_quiet(was_quiet || run > 0);
+            let start = Instant::now();
+            let result = search(run == 0);
+            durations.push(start.elapsed());
+            logger::set_quiet(was_quiet);
+
+            let alignment = result?;
+            match &first {
+                Some(expected) if expected.seqs() != alignment.seqs() => {
+                    return Err(format!("Run {} found a different alignment than run 1", run + 1));
+                }
+                Some(_) => {}
+                None => first = Some(alignment),
+            }
+        }
+        let first = first.ok_or("Nothing to repeat: 0 runs")?;
+
+        crate::info!("\nRepeated the search {} times:", times);
+        for (run, duration) in durations.iter().enumerate() {
+            crate::info!("Run {}: {:.3}s", run + 1, duration.as_secs_f64());
+        }
+        let (min, median, max) = timing_summary(&durations);
+        crate::info!("Wall time: min {:.3}s, median {:.3}s, max {:.3}s",
+                     min.as_secs_f64(), median.as_secs_f64(), max.as_secs_f64());
+        Ok((first, durations))
+    }
+}
+
+/// Min, median (mean of the middle two for an even count) and max of
+/// `durations`, which must not be empty
+fn timing_summary(durations: &[Duration]) -> (Duration, Duration, Duration) {
+    let mut sorted = durations.to_vec();
+    sorted.sort_unstable();
+    let mid = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2 } else { sorted[mid] };
+    (sorted[0], median, sorted[sorted.len() - 1])
+}
+
+/// Load `sequences` with the matrix and gap cost of `params` and build the
+/// heuristic once, for several searches:
+///
+/// ```
+/// use astar_msa_rust::align::{prepare, AlignParams};
+/// use astar_msa_rust::msa_options::PAStarOpt;
+///
+/// let seqs = vec!["ACGTTGCA".to_string(), "AGTTGA".to_string(), "ACGTGCAA".to_string()];
+/// let problem = prepare(&seqs, &AlignParams::nucleotide()).unwrap();
+/// let serial = problem.run(&PAStarOpt::builder().threads(1).build().unwrap()).unwrap();
+/// let parallel = problem.run(&PAStarOpt::builder().threads(2).build().unwrap()).unwrap();
+/// assert_eq!(serial.sp_score(), parallel.sp_score());
+/// ```
+pub fn prepare(sequences: &[String], params: &AlignParams) -> Result<PreparedProblem, String> {
+    params.activate_costs();
+    load(sequences, false)?;
+    Ok(PreparedProblem::capture())
+}
+
+/// Cost matrix, gap cost and search options for one `align` call. Start
+/// from a preset so the matrix is never left unset:
+///
+/// ```
+/// use astar_msa_rust::align::AlignParams;
+///
+/// let params = AlignParams::blosum62().threads(1);
+/// let alignment = params.align(&["HEAGAWGHEE".to_string(), "PAWHEAE".to_string()]).unwrap();
+/// assert_eq!(alignment.seqs()[1].replace('-', ""), "PAWHEAE");
+/// ```
+#[derive(Clone, Debug)]
+pub struct AlignParams {
+    matrix: Matrix,
+    gap_cost: Option<i32>,
+    options: PAStarOptBuilder,
+}
+
+impl AlignParams {
+    pub fn new(matrix: Matrix) -> Self {
+        AlignParams {
+            matrix,
+            gap_cost: None,
+            options: PAStarOpt::builder(),
+        }
+    }
+
+    /// Nucleotide costs: mismatch 1, gap 2
+    ///
+    /// ```
+    /// use astar_msa_rust::align::AlignParams;
+    ///
+    /// let alignment = AlignParams::nucleotide()
+    ///     .align(&["ACGT".to_string(), "AGT".to_string()])
+    ///     .unwrap();
+    /// assert_eq!(alignment.seqs()[1], "A-GT");
+    /// ```
+    pub fn nucleotide() -> Self {
+        AlignParams::new(Matrix::Nucleotide)
+    }
+
+    /// PAM250 protein costs, gap 30
+    ///
+    /// ```
+    /// use astar_msa_rust::align::AlignParams;
+    ///
+    /// let alignment = AlignParams::pam250()
+    ///     .align(&["VLSPADKTNV".to_string(), "VHLTPEEKSAV".to_string()])
+    ///     .unwrap();
+    /// assert_eq!(alignment.length(), alignment.seqs()[1].len());
+    /// ```
+    pub fn pam250() -> Self {
+        AlignParams::new(Matrix::Pam250)
+    }
+
+    /// BLOSUM62 protein costs, gap 19
+    ///
+    /// ```
+    /// use astar_msa_rust::align::AlignParams;
+    ///
+    /// let alignment = AlignParams::blosum62()
+    ///     .align(&["WWCW".to_string(), "WCW".to_string()])
+    ///     .unwrap();
+    /// assert_eq!(alignment.seqs()[1], "W-CW");
+    /// ```
+    pub fn blosum62() -> Self {
+        AlignParams::new(Matrix::Blosum62)
+    }
+
+    /// Override the matrix's residue/gap cost
+    pub fn gap_cost(mut self, gap_cost: i32) -> Self {
+        self.gap_cost = Some(gap_cost);
+        self
+    }
+
+    pub fn threads(mut self, threads_num: usize) -> Self {
+        self.options = self.options.threads(threads_num);
+        self
+    }
+
+    /// Replace the search options, keeping the matrix and gap cost
+    pub fn options(mut self, options: PAStarOptBuilder) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn activate_costs(&self) {
+        Cost::set_matrix(self.matrix);
+        if let Some(gap_cost) = self.gap_cost {
+            Cost::set_gap_cost(gap_cost);
+        }
+    }
+
+    /// Activate the cost matrix and align `sequences` (see `align`)
+    pub fn align(&self, sequences: &[String]) -> Result<Alignment, String> {
+        let options = self.options.clone().build()?;
+        self.activate_costs();
+        align(sequences, &options)
+    }
+}
@@ -0,0 +1,665 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Result of a multiple sequence alignment and its statistics
+ */
+
+use std::fmt;
+use std::str::FromStr;
+use crate::cost::{Cost, ScoringScheme, GAP};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Alignment {
+    seqs: Vec<String>,
+    pairwise_scores: Vec<((usize, usize), i32)>,
+    search_scores: Option<SearchScores>,
+    f_levels: Option<FLevelStats>,
+    nodes_expanded: Option<usize>,
+    list_stats: Option<ListStats>,
+    /// Scores of an alignment streamed without its rows
+    columns: Option<ColumnStats>,
+}
+
+/// Cost breakdown of the goal node an exact search finished on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchScores {
+    pub f: i32,
+    pub g: i32,
+    pub h: i32,
+    /// Heuristic estimate at the origin, the search's initial lower bound
+    pub initial_h: i32,
+}
+
+impl SearchScores {
+    /// How much the heuristic underestimated the optimal cost at the origin
+    pub fn underestimate(&self) -> i32 {
+        self.g - self.initial_h
+    }
+}
+
+/// How the f values of the nodes a search expanded were spread, a measure
+/// of how well the heuristic tells nodes apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FLevelStats {
+    /// Number of distinct f values expanded
+    pub distinct: usize,
+    /// Highest f expanded, the optimal cost once the goal is reached
+    pub max_f: i32,
+    /// Nodes expanded at `max_f`, the ties still open at termination
+    pub at_max_f: usize,
+}
+
+/// Open and closed list sizes of the search that built an alignment. PA-Star
+/// sums them over its threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListStats {
+    /// Closed nodes reached again by a cheaper path
+    pub reopened: usize,
+    /// Closed list size when the search finished
+    pub closed: usize,
+    /// Largest the open list grew
+    pub peak_open: usize,
+}
+
+/// Scores of an alignment added up column by column as it's written out
+/// (see `column_stream`), kept in place of the rows it never built
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColumnStats {
+    pub seq_num: usize,
+    /// Number of columns
+    pub length: usize,
+    /// Sum-of-pairs cost under the costs the columns were added with
+    pub sp_score: i32,
+    /// Residue pairs of the same residue
+    pub identical: usize,
+    /// Residue against residue pairs
+    pub residue_pairs: usize,
+    /// Gap against residue pairs
+    pub gap_pairs: usize,
+    /// Longest and total input length, as `Alignment::input_length_bounds`
+    pub input_bounds: (usize, usize),
+}
+
+impl ColumnStats {
+    /// No columns yet for inputs of lengths `input_lens`
+    pub fn new(input_lens: &[usize]) -> Self {
+        ColumnStats {
+            seq_num: input_lens.len(),
+            input_bounds: (input_lens.iter().copied().max().unwrap_or(0), input_lens.iter().sum()),
+            ..Default::default()
+        }
+    }
+
+    /// Add one column, a residue or gap per sequence, scored like
+    /// `Alignment::sp_score_with` and `Alignment::similarity`
+    pub fn add_column(&mut self, costs: &ScoringScheme, column: &[u8]) {
+        self.length += 1;
+        for (i, &c1) in column.iter().enumerate() {
+            for &c2 in &column[i + 1..] {
+                self.sp_score += costs.cost(c1, c2);
+                match (c1 == GAP, c2 == GAP) {
+                    (true, true) => {}
+                    (false, false) => {
+                        self.residue_pairs += 1;
+                        self.identical += (c1 == c2) as usize;
+                    }
+                    _ => self.gap_pairs += 1,
+                }
+            }
+        }
+    }
+
+    /// `Alignment::similarity` of the added columns
+    pub fn similarity(&self, mode: IdentityMode) -> f64 {
+        let total = match mode {
+            IdentityMode::Strict => self.residue_pairs + self.gap_pairs,
+            IdentityMode::Lenient => self.residue_pairs,
+        };
+        if total > 0 {
+            (self.identical as f64 * 100.0) / total as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Gap summary of an alignment
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GapStats {
+    /// Number of columns with at least one gap
+    pub gap_columns: usize,
+    /// Longest run of consecutive gaps in each sequence
+    pub longest_gap_run: Vec<usize>,
+    /// Fraction of all alignment cells that are gaps
+    pub gap_fraction: f64,
+}
+
+/// How gaps count towards the similarity percentage. Gap/gap pairs are
+/// always skipped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IdentityMode {
+    /// Gap against residue counts as a mismatch
+    #[default]
+    Strict,
+    /// Only residue/residue pairs are compared
+    Lenient,
+}
+
+impl FromStr for IdentityMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "strict" => Ok(IdentityMode::Strict),
+            "lenient" => Ok(IdentityMode::Lenient),
+            _ => Err(format!("Unknown identity mode: {}", s)),
+        }
+    }
+}
+
+/// File format of the written alignment
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputFormat {
+    #[default]
+    Fasta,
+    /// Match columns uppercase with `-` gaps, insert columns lowercase
+    /// with `.` gaps
+    A2m,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fasta" => Ok(OutputFormat::Fasta),
+            "a2m" => Ok(OutputFormat::A2m),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+/// Whether the alignment spans the whole sequences or only their best
+/// scoring parts
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlignMode {
+    #[default]
+    Global,
+    /// Smith-Waterman style: start and end anywhere, two sequences only
+    Local,
+}
+
+impl FromStr for AlignMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "global" => Ok(AlignMode::Global),
+            "local" => Ok(AlignMode::Local),
+            _ => Err(format!("Unknown alignment mode: {}", s)),
+        }
+    }
+}
+
+impl Alignment {
+    pub fn new(seqs: Vec<String>) -> Self {
+        Alignment {
+            seqs,
+            pairwise_scores: Vec::new(),
+            search_scores: None,
+            f_levels: None,
+            nodes_expanded: None,
+            list_stats: None,
+            columns: None,
+        }
+    }
+
+    /// Alignment written out by `column_stream` without building its rows.
+    /// It has no `seqs`; the scores and length come from `stats`.
+    pub fn streamed(stats: ColumnStats) -> Self {
+        Alignment { columns: Some(stats), ..Alignment::new(Vec::new()) }
+    }
+
+    /// Scores of a streamed alignment, `None` if it has rows
+    pub fn column_stats(&self) -> Option<&ColumnStats> {
+        self.columns.as_ref()
+    }
+
+    /// Attach the pairwise optimal costs the heuristic was built from
+    pub fn with_pairwise_scores(mut self, scores: Vec<((usize, usize), i32)>) -> Self {
+        self.pairwise_scores = scores;
+        self
+    }
+
+    /// Attach the final node breakdown of the search that built it
+    pub fn with_search_scores(mut self, scores: SearchScores) -> Self {
+        self.search_scores = Some(scores);
+        self
+    }
+
+    /// Attach the f value spread of the search that built it
+    pub fn with_f_levels(mut self, f_levels: FLevelStats) -> Self {
+        self.f_levels = Some(f_levels);
+        self
+    }
+
+    /// f value spread of the expanded nodes, `None` when the alignment
+    /// didn't come from the serial A-Star
+    pub fn f_levels(&self) -> Option<&FLevelStats> {
+        self.f_levels.as_ref()
+    }
+
+    /// Attach the number of nodes the search that built it expanded
+    pub fn with_nodes_expanded(mut self, nodes: usize) -> Self {
+        self.nodes_expanded = Some(nodes);
+        self
+    }
+
+    /// Nodes expanded by the search, `None` when the alignment didn't come
+    /// from an exact search
+    pub fn nodes_expanded(&self) -> Option<usize> {
+        self.nodes_expanded
+    }
+
+    /// Attach the list sizes of the search that built it
+    pub fn with_list_stats(mut self, stats: ListStats) -> Self {
+        self.list_stats = Some(stats);
+        self
+    }
+
+    /// List sizes of the search, `None` when the alignment didn't come from
+    /// A-Star or PA-Star
+    pub fn list_stats(&self) -> Option<&ListStats> {
+        self.list_stats.as_ref()
+    }
+
+    /// Nodes expanded per alignment column. Every node of the path but the
+    /// goal is expanded, so it's at least 1, and 1 when the heuristic led
+    /// straight to the goal; a high ratio means a weak heuristic.
+    pub fn expansion_ratio(&self) -> Option<f64> {
+        let nodes = self.nodes_expanded?;
+        (self.length() > 0).then(|| nodes as f64 / self.length() as f64)
+    }
+
+    /// Final f/g/h and initial heuristic, `None` when the alignment didn't
+    /// come from an exact search
+    pub fn search_scores(&self) -> Option<&SearchScores> {
+        self.search_scores.as_ref()
+    }
+
+    /// Aligned sequences, one per input sequence
+    pub fn seqs(&self) -> &[String] {
+        &self.seqs
+    }
+
+    pub fn seq_num(&self) -> usize {
+        self.columns.map_or(self.seqs.len(), |stats| stats.seq_num)
+    }
+
+    /// Optimal pairwise cost of each sequence pair used by the heuristic,
+    /// empty when the alignment didn't come from a search
+    pub fn pairwise_scores(&self) -> &[((usize, usize), i32)] {
+        &self.pairwise_scores
+    }
+
+    /// Check that every row has the same length. The statistics read past
+    /// the end of a short row as gaps, so a broken reconstruction would
+    /// otherwise only show up as wrong numbers.
+    pub fn check_rows(&self) -> Result<(), String> {
+        let len = self.length();
+        match self.seqs.iter().position(|s| s.len() != len) {
+            Some(i) => Err(format!(
+                "Aligned rows differ in length: row {} has {} columns, expected {}",
+                i, self.seqs[i].len(), len
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of alignment columns
+    pub fn length(&self) -> usize {
+        if let Some(stats) = &self.columns {
+            return stats.length;
+        }
+        self.seqs.first().map(|s| s.len()).unwrap_or(0)
+    }
+
+    /// Longest and total input (degapped) length. Any valid alignment has
+    /// `length()` within these bounds.
+    pub fn input_length_bounds(&self) -> (usize, usize) {
+        if let Some(stats) = &self.columns {
+            return stats.input_bounds;
+        }
+        let lens = self.seqs.iter().map(|s| s.bytes().filter(|&c| c != b'-').count());
+        lens.fold((0, 0), |(max, sum), len| (max.max(len), sum + len))
+    }
+
+    /// Sum-of-pairs cost under the active cost matrix, scored the same way
+    /// as the search: gap against residue costs a gap, gap against gap
+    /// costs gap-gap
+    pub fn sp_score(&self) -> i32 {
+        self.sp_score_with(&Cost::matrix_snapshot())
+    }
+
+    /// `sp_score` under `costs` instead of the active matrix. A streamed
+    /// alignment keeps the score it was written with.
+    pub fn sp_score_with(&self, costs: &ScoringScheme) -> i32 {
+        if let Some(stats) = &self.columns {
+            return stats.sp_score;
+        }
+        let align_len = self.seqs.first().map(|s| s.len()).unwrap_or(0);
+        let mut score = 0;
+
+        for pos in 0..align_len {
+            for i in 0..self.seqs.len() {
+                for j in (i + 1)..self.seqs.len() {
+                    let c1 = self.seqs[i].as_bytes().get(pos).copied().unwrap_or(GAP);
+                    let c2 = self.seqs[j].as_bytes().get(pos).copied().unwrap_or(GAP);
+                    score += costs.cost(c1, c2);
+                }
+            }
+        }
+
+        score
+    }
+
+    /// Percentage of identical pairs over all compared column pairs
+    pub fn similarity(&self, mode: IdentityMode) -> f64 {
+        if let Some(stats) = &self.columns {
+            return stats.similarity(mode);
+        }
+        let align_len = self.seqs.first().map(|s| s.len()).unwrap_or(0);
+        let mut total = 0;
+        let mut equal = 0;
+
+        for pos in 0..align_len {
+            for i in 0..self.seqs.len() {
+                for j in (i + 1)..self.seqs.len() {
+                    let c1 = self.seqs[i].as_bytes().get(pos).copied().unwrap_or(b'-');
+                    let c2 = self.seqs[j].as_bytes().get(pos).copied().unwrap_or(b'-');
+                    match (c1, c2) {
+                        (b'-', b'-') => continue,
+                        (b'-', _) | (_, b'-') if mode == IdentityMode::Lenient => continue,
+                        _ => {}
+                    }
+                    if c1 == c2 {
+                        equal += 1;
+                    }
+                    total += 1;
+                }
+            }
+        }
+
+        if total > 0 {
+            (equal as f64 * 100.0) / total as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Classify columns as match states (`true`) when their gap fraction is
+    /// below `max_gap_fraction`, insert states otherwise
+    pub fn match_columns(&self, max_gap_fraction: f64) -> Vec<bool> {
+        let seq_num = self.seqs.len();
+        (0..self.length())
+            .map(|pos| {
+                let gaps = self.seqs.iter()
+                    .filter(|s| s.as_bytes().get(pos).is_none_or(|&c| c == b'-'))
+                    .count();
+                (gaps as f64) < max_gap_fraction * seq_num as f64
+            })
+            .collect()
+    }
+
+    /// Majority-rule consensus, one character per column: `-` where more
+    /// than half the rows have a gap, otherwise the column's most frequent
+    /// residue if its share of the column's residues is at least
+    /// `threshold`, `X` if not. Ties go to the residue that sorts first.
+    pub fn consensus(&self, threshold: f64) -> String {
+        (0..self.length())
+            .map(|pos| {
+                let mut counts = [0usize; 256];
+                let mut gaps = 0;
+                for seq in &self.seqs {
+                    match seq.as_bytes().get(pos) {
+                        Some(&b'-') | None => gaps += 1,
+                        Some(&c) => counts[c as usize] += 1,
+                    }
+                }
+                if 2 * gaps > self.seqs.len() {
+                    return '-';
+                }
+                let residues = self.seqs.len() - gaps;
+                let (c, &count) = counts.iter().enumerate().rev().max_by_key(|&(_, count)| count).unwrap();
+                if count as f64 >= threshold * residues as f64 {
+                    c as u8 as char
+                } else {
+                    'X'
+                }
+            })
+            .collect()
+    }
+
+    /// Aligned column of each residue of row `seq_index`, by its position
+    /// in the ungapped sequence
+    pub fn coord_map(&self, seq_index: usize) -> Vec<usize> {
+        self.seqs[seq_index].bytes()
+            .enumerate()
+            .filter(|&(_, c)| c != b'-')
+            .map(|(column, _)| column)
+            .collect()
+    }
+
+    /// Residue of row `seq_index` in each column, `None` for gaps; the
+    /// inverse of `coord_map`
+    pub fn column_map(&self, seq_index: usize) -> Vec<Option<usize>> {
+        let mut residue = 0;
+        self.seqs[seq_index].bytes()
+            .map(|c| {
+                (c != b'-').then(|| {
+                    residue += 1;
+                    residue - 1
+                })
+            })
+            .collect()
+    }
+
+    /// Rows in A2M form, using `match_columns(max_gap_fraction)` as the
+    /// match states
+    pub fn to_a2m(&self, max_gap_fraction: f64) -> Vec<String> {
+        let match_columns = self.match_columns(max_gap_fraction);
+        self.seqs.iter()
+            .map(|seq| {
+                seq.bytes()
+                    .zip(&match_columns)
+                    .map(|(c, &is_match)| match (is_match, c) {
+                        (true, _) => c.to_ascii_uppercase() as char,
+                        (false, b'-') => '.',
+                        (false, _) => c.to_ascii_lowercase() as char,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn gap_stats(&self) -> GapStats {
+        let align_len = self.seqs.first().map(|s| s.len()).unwrap_or(0);
+
+        let mut gap_columns = 0;
+        for pos in 0..align_len {
+            if self.seqs.iter().any(|s| s.as_bytes().get(pos) == Some(&b'-')) {
+                gap_columns += 1;
+            }
+        }
+
+        let mut gaps = 0;
+        let mut cells = 0;
+        let mut longest_gap_run = Vec::with_capacity(self.seqs.len());
+        for seq in &self.seqs {
+            let mut run = 0;
+            let mut longest = 0;
+            for &c in seq.as_bytes() {
+                if c == b'-' {
+                    gaps += 1;
+                    run += 1;
+                    longest = longest.max(run);
+                } else {
+                    run = 0;
+                }
+            }
+            cells += seq.len();
+            longest_gap_run.push(longest);
+        }
+
+        let gap_fraction = if cells > 0 {
+            gaps as f64 / cells as f64
+        } else {
+            0.0
+        };
+
+        GapStats {
+            gap_columns,
+            longest_gap_run,
+            gap_fraction,
+        }
+    }
+}
+
+impl fmt::Display for GapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Gap columns: {}, longest gap runs: {:?}, gap fraction: {:.2}%",
+               self.gap_columns, self.longest_gap_run, self.gap_fraction * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consensus() {
+        let same = Alignment::new(vec!["AC-GT".to_string(); 3]);
+        assert_eq!(same.consensus(1.0), "AC-GT");
+
+        let mixed = Alignment::new(vec![
+            "ACGT-A".to_string(),
+            "ACCT--".to_string(),
+            "AGGA-C".to_string(),
+            "AAGC-G".to_string(),
+        ]);
+        // Column 1 is C in half its rows, column 5 has three different
+        // residues
+        assert_eq!(mixed.consensus(0.5), "ACGT-X");
+        assert_eq!(mixed.consensus(0.7), "AXGX-X");
+    }
+
+    #[test]
+    fn test_gap_stats() {
+        let alignment = Alignment::new(vec![
+            "AC--GT".to_string(),
+            "A-C-GT".to_string(),
+            "ACCTG-".to_string(),
+        ]);
+
+        let stats = alignment.gap_stats();
+        assert_eq!(stats.gap_columns, 4);
+        assert_eq!(stats.longest_gap_run, vec![2, 1, 1]);
+        assert!((stats.gap_fraction - 5.0 / 18.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sp_score() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        let alignment = Alignment::new(vec![
+            "AC-T".to_string(),
+            "AG-T".to_string(),
+            "A-GT".to_string(),
+        ]);
+
+        // Columns: 0 + (1 + 2 + 2) + (2 + 2 + 2) + 0
+        assert_eq!(alignment.sp_score(), 11);
+    }
+
+    #[test]
+    fn test_check_rows_rejects_unequal_lengths() {
+        let alignment = Alignment::new(vec!["AC-T".to_string(), "AG-T".to_string()]);
+        assert!(alignment.check_rows().is_ok());
+
+        let alignment = Alignment::new(vec!["AC-T".to_string(), "AGT".to_string(), "A-GT".to_string()]);
+        let err = alignment.check_rows().unwrap_err();
+        assert!(err.contains("row 1 has 3 columns, expected 4"));
+
+        let options = crate::msa_options::AStarOpt::builder().build();
+        assert_eq!(crate::backtrace::report_alignment(&alignment, &options), Err(err));
+    }
+
+    #[test]
+    fn test_similarity_skips_shared_gaps() {
+        let alignment = Alignment::new(vec![
+            "A-----C".to_string(),
+            "A-----G".to_string(),
+            "A-----C".to_string(),
+        ]);
+
+        // Only the first and last columns are compared: 3 + 1 equal of 6
+        assert!((alignment.similarity(IdentityMode::Strict) - 200.0 / 3.0).abs() < 1e-9);
+
+        let gappy = Alignment::new(vec!["AC-T".to_string(), "A-GT".to_string()]);
+        assert_eq!(gappy.similarity(IdentityMode::Strict), 50.0);
+        assert_eq!(gappy.similarity(IdentityMode::Lenient), 100.0);
+        assert_eq!("LENIENT".parse::<IdentityMode>(), Ok(IdentityMode::Lenient));
+    }
+
+    #[test]
+    fn test_length_bounds() {
+        let alignment = Alignment::new(vec!["AC-T".to_string(), "A-GT".to_string()]);
+        assert_eq!(alignment.length(), 4);
+        assert_eq!(alignment.input_length_bounds(), (3, 6));
+        assert_eq!(Alignment::new(Vec::new()).length(), 0);
+    }
+
+    #[test]
+    fn test_a2m_insert_columns() {
+        let alignment = Alignment::new(vec![
+            "AC-GT".to_string(),
+            "A--GT".to_string(),
+            "ACTG-".to_string(),
+        ]);
+
+        // Column 2 is two thirds gaps, column 1 and 4 one third
+        assert_eq!(alignment.match_columns(0.5), vec![true, true, false, true, true]);
+        assert_eq!(alignment.to_a2m(0.5), vec!["AC.GT", "A-.GT", "ACtG-"]);
+        assert_eq!("A2M".parse::<OutputFormat>(), Ok(OutputFormat::A2m));
+    }
+
+    #[test]
+    fn test_coord_map() {
+        let gapless = Alignment::new(vec!["ACGT".to_string(), "AGGT".to_string()]);
+        assert_eq!(gapless.coord_map(1), [0, 1, 2, 3]);
+
+        let gapped = Alignment::new(vec!["A-CG-T".to_string(), "AACGGT".to_string()]);
+        assert_eq!(gapped.coord_map(0), [0, 2, 3, 5]);
+        assert_eq!(gapped.column_map(0), [Some(0), None, Some(1), Some(2), None, Some(3)]);
+    }
+
+    #[test]
+    fn test_gap_stats_no_gaps() {
+        let alignment = Alignment::new(vec!["ACGT".to_string(), "AGGT".to_string()]);
+
+        let stats = alignment.gap_stats();
+        assert_eq!(stats.gap_columns, 0);
+        assert_eq!(stats.longest_gap_run, vec![0, 0]);
+        assert_eq!(stats.gap_fraction, 0.0);
+    }
+}
@@ -0,0 +1,196 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Differences between two alignments of the same sequences, to see
+ * what a change of gap costs or matrix did to the result
+ */
+
+use std::fmt;
+use ahash::AHashSet;
+use crate::alignment::Alignment;
+use crate::cost::GAP;
+use crate::read_fasta::read_aligned_fasta;
+
+/// Residue index and character of every row in one column, `None` for gaps.
+/// Two alignments share a column when they put the same residues together.
+type ColumnKey = Vec<Option<(usize, u8)>>;
+
+/// A residue of one sequence whose preceding gap run changed length
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GapChange {
+    /// Index of the residue in the ungapped sequence, or its length for the
+    /// trailing gaps
+    pub residue: usize,
+    pub gaps_before: usize,
+    pub gaps_after: usize,
+}
+
+/// What changed from alignment `a` to alignment `b`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlignmentDiff {
+    /// Columns of `a` whose residues `b` doesn't put together
+    pub removed_columns: Vec<usize>,
+    /// Columns of `b` whose residues `a` doesn't put together
+    pub added_columns: Vec<usize>,
+    /// Gap run changes of each row
+    pub gap_changes: Vec<Vec<GapChange>>,
+    /// `b`'s SP score minus `a`'s under the active cost matrix
+    pub score_delta: i32,
+}
+
+fn column_keys(alignment: &Alignment) -> Vec<ColumnKey> {
+    let mut next = vec![0; alignment.seq_num()];
+    (0..alignment.length())
+        .map(|pos| {
+            alignment.seqs().iter().zip(next.iter_mut())
+                .map(|(row, next)| match row.as_bytes().get(pos) {
+                    Some(&c) if c != GAP => {
+                        *next += 1;
+                        Some((*next - 1, c))
+                    }
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Length of the gap run before each residue of `row`, plus the trailing run
+fn gap_runs(row: &str) -> Vec<usize> {
+    let mut runs = vec![0];
+    for c in row.bytes() {
+        if c == GAP {
+            *runs.last_mut().unwrap() += 1;
+        } else {
+            runs.push(0);
+        }
+    }
+    runs
+}
+
+/// Indices of the columns in `keys` that aren't in `other`. All-gap columns
+/// align no residues and are never reported.
+fn unmatched_columns(keys: &[ColumnKey], other: &AHashSet<ColumnKey>) -> Vec<usize> {
+    keys.iter().enumerate()
+        .filter(|(_, key)| key.iter().any(Option::is_some) && !other.contains(*key))
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+/// Compare two alignments of the same sequences, rows matched by index.
+/// Columns are compared by the residues they align, so inserting a column
+/// only reports that column.
+pub fn diff_alignments(a: &Alignment, b: &Alignment) -> AlignmentDiff {
+    let (keys_a, keys_b) = (column_keys(a), column_keys(b));
+    let set_a: AHashSet<ColumnKey> = keys_a.iter().cloned().collect();
+    let set_b: AHashSet<ColumnKey> = keys_b.iter().cloned().collect();
+
+    let gap_changes = a.seqs().iter().zip(b.seqs())
+        .map(|(row_a, row_b)| {
+            gap_runs(row_a).into_iter().zip(gap_runs(row_b)).enumerate()
+                .filter(|(_, (before, after))| before != after)
+                .map(|(residue, (gaps_before, gaps_after))| GapChange { residue, gaps_before, gaps_after })
+                .collect()
+        })
+        .collect();
+
+    AlignmentDiff {
+        removed_columns: unmatched_columns(&keys_a, &set_b),
+        added_columns: unmatched_columns(&keys_b, &set_a),
+        gap_changes,
+        score_delta: b.sp_score() - a.sp_score(),
+    }
+}
+
+impl AlignmentDiff {
+    /// Whether both alignments put the same residues together
+    pub fn is_empty(&self) -> bool {
+        self.removed_columns.is_empty()
+            && self.added_columns.is_empty()
+            && self.gap_changes.iter().all(Vec::is_empty)
+            && self.score_delta == 0
+    }
+
+    /// The summary followed, if anything changed, by both alignments, each
+    /// with a `*` under the columns the other one doesn't have
+    pub fn render(&self, a: &Alignment, b: &Alignment) -> String {
+        let mut out = format!("{}\n", self);
+        if self.is_empty() {
+            return out;
+        }
+        for (label, alignment, columns) in [("Before", a, &self.removed_columns), ("After", b, &self.added_columns)] {
+            out.push_str(&format!("{}:\n", label));
+            for row in alignment.seqs() {
+                out.push_str(&format!("  {}\n", row));
+            }
+            let mut marks = vec![b' '; alignment.length()];
+            for &pos in columns {
+                marks[pos] = b'*';
+            }
+            out.push_str(&format!("  {}\n", String::from_utf8(marks).unwrap().trim_end()));
+        }
+        out
+    }
+}
+
+impl fmt::Display for AlignmentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "Alignments are identical");
+        }
+        write!(f, "Changed columns: {} removed, {} added, score delta: {:+}",
+               self.removed_columns.len(), self.added_columns.len(), self.score_delta)?;
+        for (row, changes) in self.gap_changes.iter().enumerate().filter(|(_, c)| !c.is_empty()) {
+            let changes: Vec<String> = changes.iter()
+                .map(|c| format!("{}: {}->{}", c.residue, c.gaps_before, c.gaps_after))
+                .collect();
+            write!(f, "\nSequence {} gaps before residue {}", row, changes.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Print how `alignment` differs from the aligned FASTA file `filename`
+pub fn report_diff(filename: &str, alignment: &Alignment) -> Result<(), String> {
+    let records = read_aligned_fasta(filename)?;
+    if records.len() != alignment.seq_num() {
+        return Err(format!("{} has {} sequences, the alignment has {}", filename, records.len(), alignment.seq_num()));
+    }
+    let before = Alignment::new(records.into_iter().map(|(_, row)| row).collect());
+    crate::info!("\nDifferences from {}:", filename);
+    crate::info!("{}", diff_alignments(&before, alignment).render(&before, alignment).trim_end());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::Cost;
+
+    #[test]
+    fn test_diff_alignments() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        let rows = |rows: &[&str]| Alignment::new(rows.iter().map(|s| s.to_string()).collect());
+        let a = rows(&["ACG-T", "A-GCT", "ACGCT"]);
+        let diff = diff_alignments(&a, &a.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "Alignments are identical");
+
+        // Moving one gap of the second row regroups the residues of columns 1 and 2
+        let b = rows(&["ACG-T", "AG-CT", "ACGCT"]);
+        let diff = diff_alignments(&a, &b);
+        assert_eq!(diff.removed_columns, [1, 2]);
+        assert_eq!(diff.added_columns, [1, 2]);
+        assert_eq!(diff.gap_changes[0], []);
+        assert_eq!(diff.gap_changes[1], [
+            GapChange { residue: 1, gaps_before: 1, gaps_after: 0 },
+            GapChange { residue: 2, gaps_before: 0, gaps_after: 1 },
+        ]);
+        assert_eq!(diff.score_delta, b.sp_score() - a.sp_score());
+        assert!(diff.render(&a, &b).contains("   **\n"));
+    }
+}
@@ -5,124 +5,650 @@
  * \brief Serial A-Star algorithm for multiple sequence alignment
  */
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
+use std::collections::hash_map::Entry;
+use crate::alignment::{AlignMode, Alignment, FLevelStats, ListStats};
 use crate::coord::Coord;
 use crate::node::Node;
 use crate::priority_list::PriorityList;
-use crate::heuristic_hpair::HeuristicHPair;
+use crate::heuristic_hpair::SearchCosts;
+use crate::interrupt;
 use crate::sequences::Sequences;
 use crate::time_counter::TimeCounter;
 use crate::backtrace;
+use crate::center_star;
+use crate::gap_limit;
+use crate::pair_align;
+use crate::result_cache;
 use crate::msa_options::AStarOpt;
 
+/// Partial expansion step: keep the `neighbors` with `lower < f <= upper`
+/// (`lower` being what an earlier pass over the same node already queued)
+/// and return the smallest f left above `upper`, where the node is
+/// requeued to generate the rest
+pub fn partial_successors<const N: usize>(
+    neighbors: Vec<Node<N>>,
+    lower: Option<i32>,
+    upper: i32,
+) -> (Vec<Node<N>>, Option<i32>) {
+    let mut next_f: Option<i32> = None;
+    let queued = neighbors
+        .into_iter()
+        .filter(|neighbor| {
+            let f = neighbor.get_f();
+            if f > upper {
+                next_f = Some(next_f.map_or(f, |next| next.min(f)));
+            }
+            lower.is_none_or(|lower| f > lower) && f <= upper
+        })
+        .collect();
+    (queued, next_f)
+}
+
+/// Collects `FLevelStats` over the expanded nodes
+#[derive(Default)]
+struct FLevelCounter {
+    levels: AHashSet<i32>,
+    max_f: Option<i32>,
+    at_max_f: usize,
+}
+
+impl FLevelCounter {
+    fn record(&mut self, f: i32) {
+        self.levels.insert(f);
+        match self.max_f {
+            Some(max_f) if f < max_f => {}
+            Some(max_f) if f == max_f => self.at_max_f += 1,
+            _ => {
+                self.max_f = Some(f);
+                self.at_max_f = 1;
+            }
+        }
+    }
+
+    fn stats(&self) -> FLevelStats {
+        FLevelStats { distinct: self.levels.len(), max_f: self.max_f.unwrap_or(0), at_max_f: self.at_max_f }
+    }
+}
+
+/// Lowest g queued for each coordinate that is in the open list but not
+/// yet closed. A popped node above it is stale, a successor above it is
+/// dominated; neither needs to go through the closed list.
+#[derive(Default)]
+struct QueuedG<const N: usize>(AHashMap<Coord<N>, i32>);
+
+impl<const N: usize> QueuedG<N> {
+    /// Whether a node at `pos` with cost `g` is worth queuing, recording
+    /// it if so. Equal g is queued, it may bring the preferred parent.
+    fn admit(&mut self, pos: Coord<N>, g: i32) -> bool {
+        match self.0.entry(pos) {
+            Entry::Occupied(mut best) if g <= *best.get() => {
+                best.insert(g);
+                true
+            }
+            Entry::Occupied(_) => false,
+            Entry::Vacant(best) => {
+                best.insert(g);
+                true
+            }
+        }
+    }
+
+    /// Whether a cheaper node at `pos` was queued after this one
+    fn is_stale(&self, pos: &Coord<N>, g: i32) -> bool {
+        self.0.get(pos).is_some_and(|&best| g > best)
+    }
+
+    /// `pos` was closed, the closed list takes over
+    fn settle(&mut self, pos: &Coord<N>) {
+        self.0.remove(pos);
+    }
+}
+
 pub fn a_star<const N: usize>(
     node_zero: Node<N>,
     coord_final: Coord<N>,
     options: &AStarOpt,
-) -> Result<(), String> {
+) -> Result<Alignment, String> {
+    a_star_observed(node_zero, coord_final, options, None::<fn(&Coord<N>, i32)>)
+}
+
+/// `a_star` calling `observer` with the coordinate and f of every node
+/// inserted into the closed list, in expansion order
+pub fn a_star_observed<const N: usize, F: FnMut(&Coord<N>, i32)>(
+    node_zero: Node<N>,
+    coord_final: Coord<N>,
+    options: &AStarOpt,
+    mut observer: Option<F>,
+) -> Result<Alignment, String> {
     let _timer = TimeCounter::new("\nPhase 2: A-Star running time:");
     
-    let mut open_list = PriorityList::new();
+    let mut open_list = PriorityList::new().with_tie_policy(options.tie_policy);
+    let costs = SearchCosts::for_options(options)?;
+    let (scheme, heuristic) = (&costs.scheme, &costs.heuristic);
     let mut closed_list: AHashMap<Coord<N>, Node<N>> = AHashMap::new();
     
+    // Neighbors derive their heuristic from the parent's, so the start
+    // node needs its full estimate
+    let mut node_zero = node_zero;
+    node_zero.set_f(node_zero.get_g() + heuristic.calculate_h(&node_zero.pos));
+    check_max_cost(node_zero.get_f(), options)?;
+    let mut queued_g = QueuedG::default();
+    queued_g.admit(node_zero.pos, node_zero.get_g());
     open_list.push(node_zero);
     
     let mut nodes_expanded = 0usize;
+    let mut stale_skipped = 0usize;
+    let mut dominated_dropped = 0usize;
+    let mut nodes_reopened = 0usize;
     let mut final_node: Option<Node<N>> = None;
+    // Partially expanded nodes and the f up to which their successors
+    // were queued
+    let mut deferred: AHashMap<Coord<N>, i32> = AHashMap::new();
+    let mut nodes_requeued = 0usize;
+    let mut f_levels = FLevelCounter::default();
+    let mut over_max_cost = 0usize;
+    let mut peak_open = open_list.len();
     
     while !open_list.is_empty() {
+        if interrupt::is_cancelled() {
+            break;
+        }
+        
         let current = match open_list.pop() {
             Some(node) => node,
             None => break,
         };
         
-        // Check if better node already found
-        if let Some(existing) = closed_list.get(&current.pos) {
-            if current.get_g() >= existing.get_g() {
-                continue;
-            }
+        // A cheaper path to this coordinate was queued after it
+        if queued_g.is_stale(&current.pos, current.get_g()) {
+            stale_skipped += 1;
+            continue;
         }
         
-        // Check if we reached the goal
-        let is_final = current.pos == coord_final;
-        closed_list.insert(current.pos, current.clone());
-        
-        if is_final {
-            final_node = Some(current.clone());
-            break;
+        // Check if better node already found. An equal cost tie only
+        // moves the parent, the successors' costs don't change. A
+        // partially expanded node comes back above its queued f.
+        let mut queued_up_to = None;
+        if let Some(existing) = closed_list.get_mut(&current.pos)
+            && current.get_g() >= existing.get_g() {
+            match deferred.get(&current.pos) {
+                Some(&up_to) if current.get_g() == existing.get_g() && current.get_f() > up_to => {
+                    queued_up_to = Some((up_to, existing.clone()));
+                }
+                _ => {
+                    if current.better_than(existing) {
+                        existing.set_parenti(current.get_parenti());
+                    }
+                    continue;
+                }
+            }
         }
         
-        nodes_expanded += 1;
+        // The closed entry keeps the node's own f, so its h stays exact
+        let (lower, expanded) = match queued_up_to {
+            Some((up_to, closed)) => {
+                nodes_requeued += 1;
+                (Some(up_to), closed)
+            }
+            None => {
+                // Check if we reached the goal
+                let is_final = current.pos == coord_final;
+                if let Some(observer) = observer.as_mut() {
+                    observer(&current.pos, current.get_f());
+                }
+                closed_list.insert(current.pos, current.clone());
+                queued_g.settle(&current.pos);
+                deferred.remove(&current.pos);
+                f_levels.record(current.get_f());
+                
+                if is_final {
+                    final_node = Some(current.clone());
+                    break;
+                }
+                
+                nodes_expanded += 1;
+                (None, current.clone())
+            }
+        };
         
         // Generate neighbors
-        let neighbors = current.get_neighbors();
-        
-        for mut neighbor in neighbors {
+        let mut neighbors = expanded.get_neighbors(scheme);
+        for neighbor in &mut neighbors {
             // Calculate heuristic
-            let h = HeuristicHPair::calculate_h(&neighbor.pos);
+            let h = expanded.get_h() + heuristic.delta_h(&expanded.pos, &neighbor.pos);
             neighbor.set_f(neighbor.get_g() + h);
+        }
+        
+        if let Some(margin) = options.partial_expansion {
+            let upper = current.get_f() + margin.max(0);
+            let (queued, next_f) = partial_successors(neighbors, lower, upper);
+            neighbors = queued;
+            if let Some(next_f) = next_f.filter(|&f| within_max_cost(f, options)) {
+                deferred.insert(current.pos, upper);
+                let mut requeued = expanded.clone();
+                requeued.set_f(next_f);
+                open_list.push(requeued);
+            } else {
+                deferred.remove(&current.pos);
+            }
+        }
+        
+        for neighbor in neighbors {
+            if !within_max_cost(neighbor.get_f(), options) {
+                over_max_cost += 1;
+                continue;
+            }
             
             // Check if already in closed list with better cost
-            if let Some(existing) = closed_list.get(&neighbor.pos) {
+            if let Some(existing) = closed_list.get_mut(&neighbor.pos) {
                 if neighbor.get_g() >= existing.get_g() {
+                    if neighbor.better_than(existing) {
+                        existing.set_parenti(neighbor.get_parenti());
+                    }
                     continue;
                 }
                 closed_list.remove(&neighbor.pos);
+                nodes_reopened += 1;
             }
             
+            if !queued_g.admit(neighbor.pos, neighbor.get_g()) {
+                dominated_dropped += 1;
+                continue;
+            }
             open_list.push(neighbor);
         }
+        peak_open = peak_open.max(open_list.len());
     }
     
-    println!("Nodes expanded: {}", nodes_expanded);
-    println!("Closed list size: {}", closed_list.len());
+    crate::info!("Nodes expanded: {}", nodes_expanded);
+    crate::info!("Reopened nodes: {}", nodes_reopened);
+    crate::info!("Stale queue entries skipped: {}, dominated successors not queued: {}",
+             stale_skipped, dominated_dropped);
+    if options.partial_expansion.is_some() {
+        crate::info!("Requeued partial expansions: {}", nodes_requeued);
+    }
+    if options.max_cost.is_some() {
+        crate::info!("Successors over the maximum cost: {}", over_max_cost);
+    }
+    crate::info!("Closed list size: {}", closed_list.len());
+    crate::info!("Peak open list size: {}", peak_open);
+    let f_levels = f_levels.stats();
+    crate::info!("Distinct f-levels: {}, nodes at the last one (f = {}): {}",
+             f_levels.distinct, f_levels.max_f, f_levels.at_max_f);
     
     match final_node {
-        Some(node) => {
-            backtrace::backtrace(&node, &closed_list, &options.output_file);
-            Ok(())
-        }
-        None => Err("No solution found".to_string()),
+        Some(node) => backtrace::backtrace(&node, &closed_list, &costs, options).map(|alignment| {
+            let list_stats = ListStats { reopened: nodes_reopened, closed: closed_list.len(), peak_open };
+            let alignment = alignment
+                .with_f_levels(f_levels)
+                .with_nodes_expanded(nodes_expanded)
+                .with_list_stats(list_stats);
+            report_expansion_ratio(&alignment);
+            alignment
+        }),
+        None if interrupt::is_cancelled() => backtrace::save_partial(None, &closed_list, options),
+        None => Err(no_solution(options)),
     }
 }
 
-pub fn run_astar_for_sequences(options: &AStarOpt) -> Result<(), String> {
-    match Sequences::get_seq_num() {
-        2 => a_star::<2>(
-            Sequences::get_initial_node(),
-            Sequences::get_final_coord(),
-            options
-        ),
-        3 => a_star::<3>(
-            Sequences::get_initial_node(),
-            Sequences::get_final_coord(),
-            options
-        ),
-        4 => a_star::<4>(
-            Sequences::get_initial_node(),
-            Sequences::get_final_coord(),
-            options
-        ),
-        5 => a_star::<5>(
+/// Print `Alignment::expansion_ratio`, if the search recorded its nodes
+pub fn report_expansion_ratio(alignment: &Alignment) {
+    if let (Some(ratio), Some(nodes)) = (alignment.expansion_ratio(), alignment.nodes_expanded()) {
+        crate::info!("Expansion ratio: {:.2} ({} nodes expanded for {} columns)", ratio, nodes, alignment.length());
+    }
+}
+
+/// Fail before searching when `lower_bound`, the heuristic estimate at the
+/// origin, is already over `--max-cost`: no alignment can cost less
+pub fn check_max_cost(lower_bound: i32, options: &AStarOpt) -> Result<(), String> {
+    match options.max_cost {
+        Some(max_cost) if lower_bound > max_cost => Err(format!(
+            "Lower bound {} exceeds the maximum cost {}, not searching", lower_bound, max_cost
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether a node of estimate `f` can still lead to an alignment within
+/// `--max-cost`
+pub fn within_max_cost(f: i32, options: &AStarOpt) -> bool {
+    options.max_cost.is_none_or(|max_cost| f <= max_cost)
+}
+
+/// Error for a search that emptied its open list
+pub fn no_solution(options: &AStarOpt) -> String {
+    match options.max_cost {
+        Some(max_cost) => format!("No alignment costs at most {}", max_cost),
+        None => "No solution found".to_string(),
+    }
+}
+
+pub fn run_astar_for_sequences(options: &AStarOpt) -> Result<Alignment, String> {
+    result_cache::with_cache(options, || run_astar_uncached(options))
+}
+
+/// `run_astar_for_sequences` without looking at `--cache-dir`
+pub fn run_astar_uncached(options: &AStarOpt) -> Result<Alignment, String> {
+    if options.mode == AlignMode::Local {
+        return pair_align::run_local_alignment(options);
+    }
+    if options.to_reference {
+        return center_star::run_to_reference(options);
+    }
+    if center_star::use_approximation(options) {
+        return center_star::run_center_star(options);
+    }
+    if let Some(max_gap) = options.max_gap {
+        return gap_limit::run_gap_limited(max_gap, options);
+    }
+
+    dispatch_by_n!(Sequences::get_seq_num(), N => a_star::<N>(
+        Sequences::get_initial_node(),
+        Sequences::get_final_coord(),
+        options
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::Cost;
+    use crate::heuristic_hpair::HeuristicHPair;
+
+    #[test]
+    fn test_repeat_reports_each_run() {
+        use crate::align::PreparedProblem;
+        use crate::logger;
+
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAW", "VHLTPEEKSAVTAW", "VLSAADKTNVAW"]);
+        let path = std::env::temp_dir().join(format!("astar_repeat_{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        logger::open_log_file(path).unwrap();
+        let mut firsts = Vec::new();
+        let (alignment, times) = PreparedProblem::capture()
+            .repeat(3, |first| {
+                firsts.push(first);
+                let result = run_astar_uncached(&AStarOpt::builder().summary_only(!first).build());
+                // The next run gets the problem back however this one left it
+                Sequences::clear();
+                result
+            })
+            .unwrap();
+        logger::close_log_file();
+
+        assert_eq!(firsts, [true, false, false]);
+        assert_eq!(times.len(), 3);
+        assert_eq!(alignment.seqs()[0].replace('-', ""), "VLSPADKTNVKAAW");
+        let text = std::fs::read_to_string(path).unwrap();
+        assert!((1..=3).all(|run| text.contains(&format!("] Run {}: ", run))));
+        assert!(text.contains("] Wall time: min "));
+        // Only the first run printed the alignment body
+        assert_eq!(text.matches("] Gap columns: ").count(), 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_observer_sees_closed_insertions() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKV", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVE"]);
+
+        let mut expanded: Vec<(Coord<3>, i32)> = Vec::new();
+        let options = AStarOpt::builder().build();
+        let observed = a_star_observed(
             Sequences::get_initial_node(),
             Sequences::get_final_coord(),
-            options
-        ),
-        6 => a_star::<6>(
-            Sequences::get_initial_node(),
+            &options,
+            Some(|pos: &Coord<3>, f| expanded.push((*pos, f))),
+        )
+        .unwrap();
+        assert_eq!(observed, run_astar_for_sequences(&options).unwrap());
+
+        // The heuristic is consistent: no node closes twice and f never drops
+        let unique: AHashMap<Coord<3>, i32> = expanded.iter().copied().collect();
+        assert_eq!(unique.len(), expanded.len());
+        assert!(expanded.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(expanded[0].0, Coord::new(0));
+        assert_eq!(expanded.last().unwrap().0, Sequences::get_final_coord::<3>());
+        assert_eq!(expanded.last().unwrap().1, observed.sp_score());
+    }
+
+    #[test]
+    fn test_partial_expansion_matches_full() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD", "VLSEGEWQLVLHVWAKVEAD"]);
+
+        let full = run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+        for margin in [0, 3, 40] {
+            let options = AStarOpt::builder().partial_expansion(margin).build();
+            let partial = run_astar_for_sequences(&options).unwrap();
+            assert_eq!(partial.sp_score(), full.sp_score(), "margin {}", margin);
+            assert_eq!(partial.search_scores().unwrap().g, full.search_scores().unwrap().g);
+        }
+
+        let (queued, next_f) = partial_successors(
+            [5, 9, 7, 12].iter().map(|&f| { let mut n = Node::<2>::new(); n.set_f(f); n }).collect(),
+            Some(5),
+            8,
+        );
+        assert_eq!(queued.iter().map(Node::get_f).collect::<Vec<_>>(), [7]);
+        assert_eq!(next_f, Some(9));
+    }
+
+    #[test]
+    fn test_gap_profile_moves_gaps_out_of_region() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        Sequences::set_seq("ACGTACGTACGT".to_string()).unwrap();
+        Sequences::set_seq("ACGTCGTACGT".to_string()).unwrap();
+        let options = AStarOpt::builder().build();
+        // Gaps in the second row between its residues 2 and 7
+        let region_gaps = |alignment: &Alignment| {
+            let row = alignment.seqs()[1].as_bytes();
+            let residue_col = |n| row.iter().enumerate().filter(|&(_, &c)| c != b'-').nth(n).unwrap().0;
+            row[residue_col(2)..residue_col(7)].iter().filter(|&&c| c == b'-').count()
+        };
+
+        HeuristicHPair::init().unwrap();
+        let plain = run_astar_for_sequences(&options).unwrap();
+        assert_eq!(region_gaps(&plain), 1);
+
+        let mut profile = vec![2; 11];
+        profile[2..8].fill(50);
+        Sequences::set_gap_profile(1, profile).unwrap();
+        HeuristicHPair::init().unwrap();
+        let mut expanded: Vec<i32> = Vec::new();
+        let profiled = a_star_observed(
+            Sequences::get_initial_node::<2>(),
             Sequences::get_final_coord(),
-            options
-        ),
-        7 => a_star::<7>(
-            Sequences::get_initial_node(),
+            &options,
+            Some(|_: &Coord<2>, f| expanded.push(f)),
+        )
+        .unwrap();
+        assert_eq!(region_gaps(&profiled), 0);
+        // The profiled heuristic stays consistent
+        assert!(expanded.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_dominated_paths_not_expanded() {
+        // Three ways into the same coordinate: the costlier one is never
+        // queued, the one overtaken after queuing is skipped when popped
+        let pos = Coord::<2>::from_array([2, 3]);
+        let mut queued_g = QueuedG::default();
+        assert!(queued_g.admit(pos, 10));
+        assert!(!queued_g.admit(pos, 12));
+        assert!(queued_g.admit(pos, 10));
+        assert!(queued_g.admit(pos, 7));
+        assert!(queued_g.is_stale(&pos, 10));
+        assert!(!queued_g.is_stale(&pos, 7));
+        queued_g.settle(&pos);
+        assert!(!queued_g.is_stale(&pos, 10));
+
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKV", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVE"]);
+        let mut expanded = Vec::new();
+        a_star_observed(
+            Sequences::get_initial_node::<3>(),
             Sequences::get_final_coord(),
-            options
-        ),
-        8 => a_star::<8>(
-            Sequences::get_initial_node(),
+            &AStarOpt::builder().build(),
+            Some(|pos: &Coord<3>, _| expanded.push(*pos)),
+        )
+        .unwrap();
+        let distinct: AHashSet<Coord<3>> = expanded.iter().copied().collect();
+        assert_eq!(distinct.len(), expanded.len());
+    }
+
+    #[test]
+    fn test_f_levels() {
+        use std::sync::Arc;
+
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKV", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVE"]);
+        let options = AStarOpt::builder().build();
+
+        let informed = run_astar_for_sequences(&options).unwrap();
+        let informed_levels = *informed.f_levels().unwrap();
+        assert_eq!(informed_levels.max_f, informed.search_scores().unwrap().g);
+        assert!(informed_levels.at_max_f >= 1);
+
+        // Without a heuristic the search is uniform cost (Dijkstra)
+        HeuristicHPair::install(Arc::new(Vec::new()));
+        let uniform = run_astar_for_sequences(&options).unwrap();
+        let uniform_levels = *uniform.f_levels().unwrap();
+        assert_eq!(uniform_levels.max_f, informed_levels.max_f);
+        assert!(uniform_levels.distinct > 5 * informed_levels.distinct,
+                "{:?} vs {:?}", uniform_levels, informed_levels);
+
+        // Every path node is expanded, and the weaker guidance expands more
+        let informed_ratio = informed.expansion_ratio().unwrap();
+        assert!(informed_ratio >= 1.0);
+        assert!(uniform.expansion_ratio().unwrap() > informed_ratio);
+    }
+
+    #[test]
+    fn test_matching_structure_lowers_cost() {
+        use crate::cost::StructureScoring;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        Sequences::clear();
+        for _ in 0..3 {
+            Sequences::set_seq("VLSPADKTNVKAAW".to_string()).unwrap();
+        }
+        let options = AStarOpt::builder().build();
+        let cost = || {
+            HeuristicHPair::init().unwrap();
+            run_astar_for_sequences(&options).unwrap().search_scores().unwrap().g
+        };
+
+        let plain = cost();
+        Cost::set_structure_scoring(StructureScoring { bonus: 5, penalty: 5 });
+        assert_eq!(cost(), plain);
+
+        for i in 0..3 {
+            Sequences::set_ss_labels(i, b"CCHHHHHHCEEEEC".to_vec()).unwrap();
+        }
+        let matching = cost();
+        assert!(matching < plain, "{} >= {}", matching, plain);
+
+        // One sequence labeled differently pays the penalty on its pairs
+        Sequences::set_ss_labels(2, b"EEEEEEEEEEEEEE".to_vec()).unwrap();
+        HeuristicHPair::init().unwrap();
+        let mut expanded: Vec<i32> = Vec::new();
+        let mixed = a_star_observed(
+            Sequences::get_initial_node::<3>(),
             Sequences::get_final_coord(),
-            options
-        ),
-        n => Err(format!("Unsupported number of sequences: {}. Supported: 2-8", n)),
+            &options,
+            Some(|_: &Coord<3>, f| expanded.push(f)),
+        )
+        .unwrap();
+        assert!(mixed.search_scores().unwrap().g > matching);
+        assert!(expanded.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_concurrent_searches_with_different_schemes() {
+        use crate::cost::ScoringScheme;
+
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKV", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVE"]);
+
+        let options = |scheme: ScoringScheme| AStarOpt::builder().scheme(scheme).build();
+        let run = |options: &AStarOpt| {
+            let alignment = run_astar_for_sequences(options).unwrap();
+            (alignment.seqs().to_vec(), alignment.search_scores().unwrap().g)
+        };
+        let (pam, blosum) = (options(ScoringScheme::pam250()), options(ScoringScheme::blosum62()));
+        let sequential = (run(&pam), run(&blosum));
+        assert_ne!(sequential.0.1, sequential.1.1);
+
+        let concurrent = std::thread::scope(|scope| {
+            let pam = scope.spawn(|| run(&pam));
+            let blosum = scope.spawn(|| run(&blosum));
+            (pam.join().unwrap(), blosum.join().unwrap())
+        });
+        assert_eq!(concurrent, sequential);
+    }
+
+    #[test]
+    fn test_tie_policies_stay_optimal() {
+        use crate::priority_types::TiePolicy;
+
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAHAGEYGAEALERM", "VHLTPEEKSAVTALWGKVNVDEVGGEALGRL", "GLSDGEWQLVLNVWGKVEADIPGHGQEVLI"]);
+
+        let run = |policy: TiePolicy| {
+            let mut closed = 0;
+            let options = AStarOpt::builder().tie_policy(policy).build();
+            let alignment = a_star_observed::<3, _>(
+                Sequences::get_initial_node(),
+                Sequences::get_final_coord(),
+                &options,
+                Some(|_: &Coord<3>, _| closed += 1),
+            ).unwrap();
+            (alignment.sp_score(), closed)
+        };
+        let (coord_score, coord_closed) = run(TiePolicy::Coord);
+        let (high_g_score, high_g_closed) = run(TiePolicy::HighG);
+        let (fifo_score, _) = run(TiePolicy::Fifo);
+        assert_eq!(high_g_score, coord_score);
+        assert_eq!(fifo_score, coord_score);
+        assert!(high_g_closed <= coord_closed, "high-g {} vs coord {}", high_g_closed, coord_closed);
+    }
+
+    #[test]
+    fn test_max_cost() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]);
+        let run = |max_cost: i32| {
+            let mut closed = 0;
+            let result = a_star_observed::<3, _>(
+                Sequences::get_initial_node(),
+                Sequences::get_final_coord(),
+                &AStarOpt::builder().max_cost(max_cost).build(),
+                Some(|_: &Coord<3>, _| closed += 1),
+            );
+            (result, closed)
+        };
+
+        let (optimal, _) = run(i32::MAX);
+        let scores = *optimal.unwrap().search_scores().unwrap();
+
+        // A cap under the origin's lower bound is rejected before expanding anything
+        let (rejected, closed) = run(scores.initial_h - 1);
+        assert!(rejected.unwrap_err().contains("Lower bound"));
+        assert_eq!(closed, 0);
+
+        // Between the bound and the optimum the search runs dry
+        assert!(scores.g > scores.initial_h, "{:?}", scores);
+        let (pruned, closed) = run(scores.g - 1);
+        assert_eq!(pruned.unwrap_err(), format!("No alignment costs at most {}", scores.g - 1));
+        assert!(closed > 0);
+        let (capped, _) = run(scores.g);
+        assert_eq!(capped.unwrap().search_scores().unwrap().g, scores.g);
     }
 }
@@ -0,0 +1,108 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Pick the PA-Star hash type and shift for an input by timing
+ * short bounded searches with each configuration
+ */
+
+use std::time::{Duration, Instant};
+use crate::coord_hash::{CoordHash, HashType};
+use crate::msa_options::PAStarOpt;
+use crate::pastar::PAStar;
+use crate::sequences::Sequences;
+use crate::HASH_SHIFT;
+
+/// Node budget of each trial when `--max-nodes` isn't given
+pub const DEFAULT_TRIAL_NODES: usize = 100_000;
+
+/// Hash shifts tried with every hash type
+pub const TRIAL_SHIFTS: [usize; 3] = [0, HASH_SHIFT / 2, HASH_SHIFT];
+
+/// Outcome of one bounded search
+#[derive(Clone, Debug)]
+pub struct HashTrial {
+    pub hash_type: HashType,
+    pub hash_shift: usize,
+    pub elapsed: Duration,
+    pub nodes: usize,
+    /// Busiest thread's expansions over the mean, 1.0 is a perfect split
+    pub imbalance: f64,
+}
+
+fn run_trial<const N: usize>(options: PAStarOpt) -> Result<HashTrial, String> {
+    let (hash_type, hash_shift) = (options.hash_type, options.hash_shift);
+    let pastar = PAStar::<N>::new(Sequences::get_initial_node(), options)?;
+    let start = Instant::now();
+    pastar.search(Sequences::get_final_coord())?;
+    let elapsed = start.elapsed();
+
+    let loads = pastar.thread_loads();
+    let nodes: usize = loads.iter().sum();
+    let max = loads.iter().copied().max().unwrap_or(0);
+    let imbalance = if nodes > 0 {
+        max as f64 * loads.len() as f64 / nodes as f64
+    } else {
+        1.0
+    };
+
+    Ok(HashTrial { hash_type, hash_shift, elapsed, nodes, imbalance })
+}
+
+/// Run a search capped at `max_nodes` expansions for every hash type and
+/// shift in `TRIAL_SHIFTS`, ranked fastest first. Requires
+/// `HeuristicHPair::init` to have run.
+pub fn autotune_hash(options: &PAStarOpt, max_nodes: usize) -> Result<Vec<HashTrial>, String> {
+    let mut trials = Vec::new();
+    for hash_type in HashType::ALL {
+        for hash_shift in TRIAL_SHIFTS {
+            let mut trial_options = options.clone();
+            trial_options.hash_type = hash_type;
+            trial_options.hash_shift = hash_shift;
+            trial_options.max_nodes = Some(max_nodes);
+
+            let trial = dispatch_by_n!(Sequences::get_seq_num(), N => run_trial::<N>(trial_options))?;
+            trials.push(trial);
+        }
+    }
+
+    trials.sort_by(|a, b| a.elapsed.cmp(&b.elapsed).then(a.imbalance.total_cmp(&b.imbalance)));
+    Ok(trials)
+}
+
+/// Print the ranked trials as a table
+pub fn print_ranking(trials: &[HashTrial]) {
+    crate::info!("\nHash autotune ranking:");
+    crate::info!("{:>4}  {:<16} {:>5} {:>10} {:>10} {:>9}", "Rank", "Hash", "Shift", "Time (s)", "Nodes", "Imbalance");
+    for (rank, trial) in trials.iter().enumerate() {
+        crate::info!("{:>4}  {:<16} {:>5} {:>10.3} {:>10} {:>9.2}",
+                 rank + 1,
+                 CoordHash::get_hash_name(trial.hash_type),
+                 trial.hash_shift,
+                 trial.elapsed.as_secs_f64(),
+                 trial.nodes,
+                 trial.imbalance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autotune_tries_every_hash_type() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]);
+
+        let options = PAStarOpt::builder().threads(2).no_affinity(true).build().unwrap();
+        let trials = autotune_hash(&options, 50).unwrap();
+        assert_eq!(trials.len(), HashType::ALL.len() * TRIAL_SHIFTS.len());
+        for hash_type in HashType::ALL {
+            assert!(trials.iter().any(|t| t.hash_type == hash_type));
+        }
+        assert!(trials.windows(2).all(|w| w[0].elapsed <= w[1].elapsed));
+        // Some trials reach the goal first, and each thread may pass the
+        // budget check once before stopping
+        assert!(trials.iter().all(|t| t.nodes > 0 && t.nodes < 50 + 2 && t.imbalance >= 1.0));
+    }
+}
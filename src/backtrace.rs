@@ -5,128 +5,465 @@
  * \brief Backtrace implementation to reconstruct alignment
  */
 
+use crate::alignment::{Alignment, SearchScores};
 use crate::coord::Coord;
+use crate::color::{self, ColorMode};
+use crate::column_stream;
+use crate::cost::{Cost, ScoringScheme, GAP};
+use crate::heuristic_hpair::SearchCosts;
+use crate::logger;
+use crate::msa_options::{AStarOpt, DEFAULT_FASTA_WRAP};
 use crate::node::Node;
+use crate::output_sink::{format_rows, AppendFastaSink, FastaFileSink, OutputSink};
+use crate::read_fasta::read_aligned_fasta;
 use crate::sequences::Sequences;
 use crate::time_counter::TimeCounter;
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
+use std::cmp::Reverse;
 use std::io::Write;
 
-pub fn backtrace<const N: usize>(
+/// Read access to the closed nodes of a finished search, so backtrace can
+/// follow parents without merging per-thread lists first
+pub trait ClosedLookup<const N: usize> {
+    /// Best closed node at `pos`, if any
+    fn closed_node(&self, pos: &Coord<N>) -> Option<Node<N>>;
+
+    /// Call `f` on every closed node, in the lists' hash order, which
+    /// changes from run to run, so callers must not break ties by order
+    fn for_each_closed(&self, f: &mut dyn FnMut(&Node<N>));
+}
+
+impl<const N: usize> ClosedLookup<N> for AHashMap<Coord<N>, Node<N>> {
+    fn closed_node(&self, pos: &Coord<N>) -> Option<Node<N>> {
+        self.get(pos).cloned()
+    }
+
+    fn for_each_closed(&self, f: &mut dyn FnMut(&Node<N>)) {
+        self.values().for_each(f);
+    }
+}
+
+pub fn backtrace<const N: usize, C: ClosedLookup<N> + ?Sized>(
     final_node: &Node<N>,
-    closed_list: &AHashMap<Coord<N>, Node<N>>,
-    output_file: &Option<String>,
-) -> Vec<String> {
+    closed_list: &C,
+    costs: &SearchCosts,
+    options: &AStarOpt,
+) -> Result<Alignment, String> {
     let _timer = TimeCounter::new("Phase 3 - backtrace:");
     
+    crate::info!("Final Score: Node[pos: {}, f: {}, g: {}, h: {}]", 
+             final_node.pos, final_node.get_f(), final_node.get_g(), final_node.get_h());
+    
+    let path = match canonical_path(final_node, closed_list, costs) {
+        Some(path) => path,
+        None if options.deterministic => {
+            return Err("No canonical path found, can't guarantee a deterministic alignment".to_string());
+        }
+        None => {
+            eprintln!("Warning: no canonical path found, following stored parents");
+            stored_parent_path(final_node, closed_list)
+        }
+    };
+    
+    if options.audit_heuristic {
+        let violations = audit_heuristic(&path, final_node.get_g(), |pos| costs.heuristic.calculate_h(pos));
+        for (pos, h, remaining) in &violations {
+            eprintln!("Warning: inadmissible heuristic at {}: h = {} but only {} remains to the goal", pos, h, remaining);
+        }
+        if violations.is_empty() {
+            crate::info!("Heuristic audit: admissible at {} sampled path nodes", audit_samples(path.len()).count());
+        }
+    }
+    
+    // Reconstruct aligned sequences
+    let verify = cfg!(debug_assertions) || options.verify_reconstruction;
+    let pairwise_scores = costs.heuristic.pairwise_scores();
+    let search_scores = SearchScores {
+        f: final_node.get_f(),
+        g: final_node.get_g(),
+        h: final_node.get_h(),
+        initial_h: costs.heuristic.calculate_h(&Coord::<N>::new(0)),
+    };
+    let alignment = path_alignment(&path, &costs.scheme, verify, options)?
+        .with_pairwise_scores(pairwise_scores)
+        .with_search_scores(search_scores);
+    report_alignment(&alignment, options)?;
+    
+    Ok(alignment)
+}
+
+/// Alignment along `path` with its rows in input order, or with
+/// `--stream-output` written straight to the stream file and scored from
+/// its columns, never building the rows
+pub fn path_alignment<const N: usize>(
+    path: &[Node<N>],
+    scheme: &ScoringScheme,
+    verify: bool,
+    options: &AStarOpt,
+) -> Result<Alignment, String> {
+    match &options.stream_output {
+        Some(filename) if !options.summary_only => {
+            if verify {
+                verify_moves(path)?;
+            }
+            let stats = column_stream::stream_to_file(filename, path, options.wrap, scheme)?;
+            // The sequences go back in input order as they would with rows
+            Sequences::restore_input_order(Vec::new());
+            Ok(Alignment::streamed(stats))
+        }
+        _ => Ok(Alignment::new(Sequences::restore_input_order(reconstruct_alignment(path, verify)?))),
+    }
+}
+
+/// Path nodes checked by the heuristic audit
+const AUDIT_SAMPLES: usize = 16;
+
+/// Indices of up to `AUDIT_SAMPLES` evenly spread path nodes, starting at
+/// the origin
+fn audit_samples(path_len: usize) -> impl Iterator<Item = usize> {
+    let step = path_len.div_ceil(AUDIT_SAMPLES).max(1);
+    (0..path_len).step_by(step)
+}
+
+/// Compare `h` at sampled nodes of an optimal path against the cost that
+/// actually remained from there, `final_g - g`. Returns the nodes where the
+/// heuristic overestimated as `(pos, h, remaining)`: the heuristic was not
+/// admissible, so the search may have returned a suboptimal alignment.
+pub fn audit_heuristic<const N: usize>(
+    path: &[Node<N>],
+    final_g: i32,
+    h: impl Fn(&Coord<N>) -> i32,
+) -> Vec<(Coord<N>, i32, i32)> {
+    audit_samples(path.len())
+        .map(|i| &path[i])
+        .filter_map(|node| {
+            let (estimate, remaining) = (h(&node.pos), final_g - node.get_g());
+            (estimate > remaining).then_some((node.pos, estimate, remaining))
+        })
+        .collect()
+}
+
+/// Rebuild the optimal path from the origin to `final_node`, taking the
+/// parent with the smallest move index among equal cost parents.
+///
+/// Every node with f below the final cost is closed with its optimal g in
+/// any run, but which nodes with f equal to it got expanded depends on
+/// expansion order and thread count. Those are checked by cost
+/// (g + h == final cost) instead of closed list membership, so the result
+/// is the same for every run.
+fn canonical_path<const N: usize, C: ClosedLookup<N> + ?Sized>(
+    final_node: &Node<N>,
+    closed_list: &C,
+    costs: &SearchCosts,
+) -> Option<Vec<Node<N>>> {
+    let best = final_node.get_g();
+    let origin = Coord::new(0);
+    let mut dead: AHashSet<Coord<N>> = AHashSet::new();
+    // Path from the goal backwards, with the next move to try at each node
+    let mut stack: Vec<(Node<N>, usize)> = vec![(final_node.clone(), 1)];
+    while let Some((node, next_move)) = stack.last_mut() {
+        if node.pos == origin {
+            break;
+        }
+        
+        let mut parent = None;
+        while *next_move < (1 << N) && parent.is_none() {
+            let neigh_num = *next_move;
+            *next_move += 1;
+            
+            let Some(pos) = predecessor(&node.pos, neigh_num) else {
+                continue;
+            };
+            if dead.contains(&pos) {
+                continue;
+            }
+            let Some(cost) = Node::with_values(0, pos, 0).move_cost(&costs.scheme, neigh_num) else {
+                continue;
+            };
+            let g = node.get_g() - cost;
+            let settled = closed_list.closed_node(&pos).filter(|n| n.get_f() < best);
+            let on_path = match settled {
+                Some(closed) => closed.get_g() == g,
+                None => g >= 0 && g + costs.heuristic.calculate_h(&pos) == best,
+            };
+            if on_path {
+                parent = Some(Node::with_values(g, pos, neigh_num as i32));
+            }
+        }
+        
+        match parent {
+            Some(parent) => stack.push((parent, 1)),
+            // Not even the goal has a parent on an optimal path
+            None if stack.len() == 1 => return None,
+            None => {
+                let (node, _) = stack.pop()?;
+                dead.insert(node.pos);
+            }
+        }
+    }
+    
+    let mut path: Vec<Node<N>> = stack.into_iter().map(|(node, _)| node).collect();
+    path.reverse();
+    Some(path)
+}
+
+/// Coordinate one move of `neigh_num` before `pos`, if inside the lattice
+fn predecessor<const N: usize>(pos: &Coord<N>, neigh_num: usize) -> Option<Coord<N>> {
+    let mut pred = *pos;
+    for dim in (0..N).filter(|dim| neigh_num & (1 << dim) != 0) {
+        pred.set(dim, pred.get(dim).checked_sub(1)?);
+    }
+    Some(pred)
+}
+
+/// Follow the parent stored in each closed node back to the origin
+fn stored_parent_path<const N: usize, C: ClosedLookup<N> + ?Sized>(
+    final_node: &Node<N>,
+    closed_list: &C,
+) -> Vec<Node<N>> {
     let mut path = Vec::new();
     let mut current = final_node.clone();
     
-    println!("Final Score: Node[pos: {}, f: {}, g: {}, h: {}]", 
-             final_node.pos, final_node.get_f(), final_node.get_g(), final_node.get_h());
-    
-    // Backtrace from final to initial
-    while current.get_g() != 0 {
+    // Zero cost moves exist (e.g. nucleotide matches), so stop on the
+    // origin rather than on g == 0
+    let origin = Coord::new(0);
+    while current.pos != origin {
         path.push(current.clone());
-        let parent_pos = current.get_parent();
         
-        if let Some(parent_node) = closed_list.get(&parent_pos) {
-            current = parent_node.clone();
+        if let Some(parent_node) = closed_list.closed_node(&current.get_parent()) {
+            current = parent_node;
         } else {
             eprintln!("Error: parent not found in closed list");
             break;
         }
     }
     
-    // Add initial node
     path.push(current);
     path.reverse();
+    path
+}
+
+/// Build the best alignment reachable after an interrupted search and save
+/// it to `save_on_interrupt`: the path to the goal if one was found,
+/// otherwise to the closed node that got furthest, with the unaligned rest
+/// of every sequence appended. It may be far from optimal.
+pub fn save_partial<const N: usize, C: ClosedLookup<N> + ?Sized>(
+    final_node: Option<&Node<N>>,
+    closed_list: &C,
+    options: &AStarOpt,
+) -> Result<Alignment, String> {
+    let Some(filename) = &options.save_on_interrupt else {
+        return Err("Search interrupted".to_string());
+    };
     
-    // Reconstruct aligned sequences
-    let alignments = reconstruct_alignment(&path);
+    let origin = Sequences::get_initial_node::<N>();
+    let furthest = final_node.cloned().unwrap_or_else(|| {
+        // Ties go to the lowest coordinate, then parent, whatever the scan order
+        let key = |node: &Node<N>| {
+            (node.pos.get_sum(), Reverse(node.get_g()), Reverse(node.pos), Reverse(node.get_parenti()))
+        };
+        let mut furthest = origin;
+        closed_list.for_each_closed(&mut |node| {
+            if key(node) > key(&furthest) {
+                furthest = node.clone();
+            }
+        });
+        furthest
+    });
+    
+    let path = stored_parent_path(&furthest, closed_list);
+    let mut rows = reconstruct_alignment(&path, false)?;
+    
+    // Append what's left of each sequence, padded to the same length
+    let rests: Vec<Vec<u8>> = (0..N)
+        .map(|i| Sequences::get_seq_slice(i)[furthest.pos.get(i) as usize..].to_vec())
+        .collect();
+    let rest_len = rests.iter().map(|rest| rest.len()).max().unwrap_or(0);
+    for (row, rest) in rows.iter_mut().zip(&rests) {
+        row.push_str(&String::from_utf8_lossy(rest));
+        row.push_str(&"-".repeat(rest_len - rest.len()));
+    }
+    
+    let alignment = Alignment::new(Sequences::restore_input_order(rows));
+    let rows = format_rows(&alignment, options.format, options.match_gap_fraction);
+    backtrace_print_fasta_file(&rows, filename, options.wrap)
+        .map_err(|e| format!("Error writing partial alignment: {}", e))?;
+    Err(format!(
+        "Search interrupted at {}, partial (possibly suboptimal) alignment with score {} saved to {}",
+        furthest.pos, alignment.sp_score(), filename
+    ))
+}
+
+/// Print the alignment statistics and body, and write the FASTA output.
+/// Fails without printing anything if the rows differ in length, and
+/// after the statistics if the output file can't be written. With
+/// `summary_only` it stops after the scores and length. A streamed
+/// alignment has no rows for the other outputs, its stream file is the
+/// alignment; any other gets written to the `--stream-output` file here.
+pub fn report_alignment(alignment: &Alignment, options: &AStarOpt) -> Result<(), String> {
+    let streamed = alignment.column_stats().is_some();
+    if !streamed {
+        alignment.check_rows()?;
+    }
+    let output_file = &options.output_file;
     
     // Print similarity
-    backtrace_print_similarity(&alignments);
+    crate::info!("Similarity: {:.2}%", alignment.similarity(options.identity_mode));
+    crate::info!("Sum-of-pairs score: {}", alignment.sp_score_with(&options.costs()));
+    
+    let length = alignment.length();
+    let (max, sum) = alignment.input_length_bounds();
+    crate::info!("Alignment length: {} (inputs: max={}, sum={})", length, max, sum);
+    if length < max || length > sum {
+        eprintln!("Warning: alignment length {} is outside [{}, {}], the reconstruction is likely wrong",
+                  length, max, sum);
+    }
+    
+    if options.summary_only {
+        return Ok(());
+    }
+    
+    if let Some(filename) = &options.stream_output {
+        if streamed {
+            crate::info!("Alignment streamed to {}", filename);
+            if output_file.is_some() || options.append_output.is_some() || options.consensus.is_some()
+                || options.coord_map.is_some() {
+                eprintln!("Warning: the other output files need the aligned rows, which --stream-output doesn't build");
+            }
+            if options.report_pairs {
+                print_pairwise_scores(alignment.pairwise_scores());
+            }
+            return Ok(());
+        }
+        column_stream::write_rows_to_file(filename, alignment.seqs(), options.wrap)?;
+    }
     
     // Write to file if requested
     if let Some(filename) = output_file {
-        if let Err(e) = backtrace_print_fasta_file::<N>(&alignments, filename) {
-            eprintln!("Error writing FASTA file: {}", e);
-        }
+        FastaFileSink::new(filename, options)?.emit(alignment)?;
+    }
+    if let Some(filename) = &options.append_output {
+        AppendFastaSink::open(filename, options)?.emit(alignment)?;
+    }
+    if let Some(filename) = &options.consensus {
+        let consensus: String = alignment.consensus(options.consensus_threshold).chars().filter(|&c| c != '-').collect();
+        write_consensus(filename, &consensus, options.wrap)
+            .map_err(|e| format!("Error writing consensus file {}: {}", filename, e))?;
+    }
+    if let Some(filename) = &options.coord_map {
+        write_coord_map(filename, alignment)
+            .map_err(|e| format!("Error writing coordinate map {}: {}", filename, e))?;
     }
     
-    // Print alignment to terminal
-    backtrace_print_alignment(&alignments);
+    crate::info!("{}", alignment.gap_stats());
+    
+    if options.report_pairs {
+        print_pairwise_scores(alignment.pairwise_scores());
+    }
     
-    alignments
+    // Print alignment to terminal
+    backtrace_print_alignment(alignment, options.color);
+    Ok(())
 }
 
-fn reconstruct_alignment<const N: usize>(path: &[Node<N>]) -> Vec<String> {
-    let seq_num = N;
-    let mut aligned_seqs: Vec<Vec<u8>> = vec![Vec::new(); seq_num];
-    
-    for window in path.windows(2) {
-        let current = &window[0];
-        let next = &window[1];
-        
-        for i in 0..seq_num {
-            let current_pos = current.pos.get(i);
-            let next_pos = next.pos.get(i);
-            
-            if next_pos > current_pos {
+/// Score an existing aligned FASTA file without running a search
+pub fn run_score_only(filename: &str, options: &AStarOpt) -> Result<Alignment, String> {
+    let records = read_aligned_fasta(filename)?;
+    crate::info!("Scoring {}: {} sequences, {} columns", filename, records.len(), records[0].1.len());
+
+    Sequences::clear();
+    for (name, row) in &records {
+        Sequences::set_name(name.clone());
+        Sequences::set_seq(row.chars().filter(|&c| c != '-').collect())?;
+    }
+
+    let alignment = Alignment::new(records.into_iter().map(|(_, row)| row).collect());
+    report_alignment(&alignment, options)?;
+    Ok(alignment)
+}
+
+/// Columns of the alignment along the lattice path `path`, one residue or
+/// gap per loaded sequence, produced as they're consumed
+pub fn path_columns<const N: usize>(path: &[Node<N>]) -> impl Iterator<Item = [u8; N]> + '_ {
+    let seqs: Vec<_> = (0..N).map(Sequences::get_seq_slice).collect();
+    path.windows(2).map(move |window| {
+        std::array::from_fn(|i| {
+            let current_pos = window[0].pos.get(i);
+            if window[1].pos.get(i) > current_pos {
                 // Sequence advanced - add character
-                let seq = Sequences::get_seq(i);
-                if (current_pos as usize) < seq.len() {
-                    aligned_seqs[i].push(seq[current_pos as usize]);
-                } else {
-                    aligned_seqs[i].push(b'-');
-                }
+                seqs[i].get(current_pos as usize).copied().unwrap_or(GAP)
             } else {
                 // Gap in this sequence
-                aligned_seqs[i].push(b'-');
+                GAP
             }
+        })
+    })
+}
+
+/// Aligned rows of the lattice path `path`, from the origin to the goal.
+/// A sequence already consumed gets a gap in every later column. `verify`
+/// checks every row is its input sequence once gaps are removed, and every
+/// step is a lattice move.
+pub fn reconstruct_alignment<const N: usize>(path: &[Node<N>], verify: bool) -> Result<Vec<String>, String> {
+    let mut aligned_seqs: Vec<Vec<u8>> = vec![Vec::with_capacity(path.len().saturating_sub(1)); N];
+    for column in path_columns(path) {
+        for (aligned, &c) in aligned_seqs.iter_mut().zip(&column) {
+            aligned.push(c);
         }
     }
     
     // Convert to strings
-    aligned_seqs.into_iter()
+    let aligned: Vec<String> = aligned_seqs.into_iter()
         .map(|seq| String::from_utf8_lossy(&seq).to_string())
-        .collect()
+        .collect();
+    
+    if verify {
+        verify_reconstruction(&aligned)?;
+        verify_moves(path)?;
+    }
+    
+    Ok(aligned)
 }
 
-/// Calculate and print similarity percentage between sequences
-fn backtrace_print_similarity(alignments: &[String]) {
-    if alignments.is_empty() {
-        return;
+/// Check that every step of `path` advances each sequence by at most one
+/// residue, at least one sequence, and none past its end. `path_columns`
+/// writes a gap for a sequence pushed past its end, so that and an all-gap
+/// column would pass `verify_reconstruction`.
+fn verify_moves<const N: usize>(path: &[Node<N>]) -> Result<(), String> {
+    let lens: Vec<usize> = (0..N).map(Sequences::get_seq_len).collect();
+    for (step, window) in path.windows(2).enumerate() {
+        let (current, next) = (&window[0].pos, &window[1].pos);
+        let advanced: Vec<bool> = (0..N).map(|i| next.get(i) > current.get(i)).collect();
+        let valid = (0..N).all(|i| next.get(i) == current.get(i) || next.get(i) == current.get(i) + 1)
+            && advanced.contains(&true)
+            && (0..N).all(|i| next.get(i) as usize <= lens[i]);
+        if !valid {
+            return Err(format!("Path step {} from {} to {} is not a lattice move", step, current, next));
+        }
     }
-    
-    let seq_num = alignments.len();
-    let align_len = alignments[0].len();
-    
-    let mut total = 0;
-    let mut equal = 0;
-    
-    for pos in 0..align_len {
-        for i in 0..seq_num {
-            for j in (i + 1)..seq_num {
-                let char_i = alignments[i].as_bytes().get(pos).copied().unwrap_or(b'-');
-                let char_j = alignments[j].as_bytes().get(pos).copied().unwrap_or(b'-');
-                
-                if char_i == char_j {
-                    equal += 1;
-                }
-                total += 1;
-            }
+    Ok(())
+}
+
+/// Check that every aligned sequence degaps back to its input sequence
+fn verify_reconstruction(aligned: &[String]) -> Result<(), String> {
+    for (i, row) in aligned.iter().enumerate() {
+        let degapped: Vec<u8> = row.bytes().filter(|&c| c != b'-').collect();
+        if degapped[..] != *Sequences::get_seq_slice(i) {
+            return Err(format!(
+                "Reconstructed sequence {} does not match its input after removing gaps", i
+            ));
         }
     }
-    
-    let percent = if total > 0 {
-        (equal as f64 * 100.0) / total as f64
-    } else {
-        0.0
-    };
-    
-    println!("Similarity: {:.2}%", percent);
+    Ok(())
+}
+
+/// Print the pairwise optimal costs behind the heuristic as a table
+fn print_pairwise_scores(scores: &[((usize, usize), i32)]) {
+    crate::info!("\nPairwise heuristic scores:");
+    crate::info!("{:>6} {:>6} {:>10}", "Seq", "Seq", "Score");
+    for &((i, j), score) in scores {
+        crate::info!("{:>6} {:>6} {:>10}", i, j, score);
+    }
 }
 
 /// Get terminal width for proper alignment display
@@ -146,63 +483,416 @@ fn get_print_size() -> usize {
     default_width
 }
 
-/// Print alignment in a formatted way, respecting terminal width
-fn backtrace_print_alignment(alignments: &[String]) {
-    if alignments.is_empty() {
-        return;
-    }
-    
-    let size = get_print_size();
-    let align_len = alignments[0].len();
-    let seq_num = alignments.len();
+/// Lines of the terminal view of `alignment`: blocks of `width` columns,
+/// each after an empty line. With `colored` the residues get ANSI colors
+/// (see `color::paint`), bold in columns with one residue throughout.
+fn alignment_lines(alignment: &Alignment, width: usize, colored: bool) -> Vec<String> {
+    let align_len = alignment.length();
+    // Columns holding the same residue in every row
+    let conserved: Vec<bool> = (0..if colored { align_len } else { 0 })
+        .map(|pos| {
+            let mut column = alignment.seqs().iter().map(|row| row.as_bytes()[pos]);
+            column.next().is_some_and(|c| c != GAP && column.all(|other| other == c))
+        })
+        .collect();
+    let nucleotide = Cost::is_nucleotide();
     
+    let mut lines = Vec::new();
     let mut pos = 0;
-    
     while pos < align_len {
-        println!();
-        
-        for i in 0..seq_num {
-            let end = (pos + size).min(align_len);
-            let segment = &alignments[i][pos..end];
-            println!("{}", segment);
+        lines.push(String::new());
+        let end = (pos + width).min(align_len);
+        for row in alignment.seqs() {
+            let segment = &row[pos..end];
+            lines.push(if colored { color::paint(segment, &conserved[pos..end], nucleotide) } else { segment.to_string() });
         }
-        
-        pos += size;
+        pos += width;
     }
+    lines
 }
 
-fn backtrace_print_fasta_file<const N: usize>(
+/// Print alignment in a formatted way, respecting terminal width, colored
+/// as `color` says
+fn backtrace_print_alignment(alignment: &Alignment, color: ColorMode) {
+    let size = get_print_size();
+    let lines = alignment_lines(alignment, size, false);
+    if color.enabled() {
+        for (line, styled) in lines.iter().zip(alignment_lines(alignment, size, true)) {
+            logger::write_styled(line, &styled);
+        }
+    } else {
+        for line in lines {
+            crate::info!("{}", line);
+        }
+    }
+}
+
+fn backtrace_print_fasta_file(
     aligned_seqs: &[String],
     filename: &str,
+    wrap: usize,
 ) -> Result<(), std::io::Error> {
     use std::fs::File;
+    use std::io::BufWriter;
     
-    let mut file = File::create(filename)?;
-    
-    for i in 0..N {
+    let mut file = BufWriter::new(File::create(filename)?);
+    write_fasta(&mut file, aligned_seqs, wrap)?;
+    file.flush()
+}
+
+/// Write the aligned sequences as FASTA, wrapping sequence lines at `wrap`
+/// columns (0 = one line per sequence)
+pub fn write_fasta<W: Write>(out: &mut W, aligned_seqs: &[String], wrap: usize) -> Result<(), std::io::Error> {
+    for (i, aligned) in aligned_seqs.iter().enumerate() {
         let name = Sequences::get_seq_name(i);
-        writeln!(file, "{}", name)?;
-        writeln!(file, "{}", aligned_seqs[i])?;
+        writeln!(out, "{}", name)?;
+        if wrap == 0 {
+            writeln!(out, "{}", aligned)?;
+            continue;
+        }
+        for line in aligned.as_bytes().chunks(wrap) {
+            out.write_all(line)?;
+            writeln!(out)?;
+        }
     }
     
     Ok(())
 }
 
+/// Write `consensus` as a single FASTA record named `Consensus`
+fn write_consensus(filename: &str, consensus: &str, wrap: usize) -> Result<(), std::io::Error> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(filename)?);
+    writeln!(out, ">Consensus")?;
+    if wrap == 0 {
+        writeln!(out, "{}", consensus)?;
+    } else {
+        for line in consensus.as_bytes().chunks(wrap) {
+            out.write_all(line)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
+
+/// Write one `sequence<TAB>residue<TAB>column` line per residue, both
+/// positions 0-based, so annotations on the inputs can be moved onto the
+/// alignment
+fn write_coord_map(filename: &str, alignment: &Alignment) -> Result<(), std::io::Error> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(filename)?);
+    writeln!(out, "sequence\tresidue\tcolumn")?;
+    for i in 0..alignment.seq_num() {
+        let name = Sequences::get_seq_name(i);
+        let name = name.trim_start_matches('>');
+        for (residue, column) in alignment.coord_map(i).into_iter().enumerate() {
+            writeln!(out, "{}\t{}\t{}", name, residue, column)?;
+        }
+    }
+    out.flush()
+}
+
 // Keep the old public function for compatibility
 pub fn write_fasta_output<const N: usize>(
     aligned_seqs: &[String],
     filename: &str,
 ) -> Result<(), std::io::Error> {
-    backtrace_print_fasta_file::<N>(aligned_seqs, filename)
+    backtrace_print_fasta_file(&aligned_seqs[..N], filename, DEFAULT_FASTA_WRAP)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::heuristic_hpair::HeuristicHPair;
+
+    #[test]
+    fn test_print_size() {
+        assert!(get_print_size() >= 40);
+    }
+
+    #[test]
+    fn test_alignment_lines_color() {
+        let _guard = crate::test_lock();
+        crate::cost::Cost::set_cost_pam250();
+        let alignment = Alignment::new(vec!["MKV-LW".to_string(), "MKVELW".to_string()]);
+
+        let plain = alignment_lines(&alignment, 4, ColorMode::Never.enabled());
+        assert_eq!(plain, ["", "MKV-", "MKVE", "", "LW", "LW"]);
+        assert!(plain.iter().all(|line| !line.contains('\x1b')));
+
+        let colored = alignment_lines(&alignment, 4, ColorMode::Always.enabled());
+        assert!(colored[1].contains("\x1b[1;31mK\x1b[0m"));
+        // The gap column isn't conserved
+        assert!(colored[2].ends_with("\x1b[35mE\x1b[0m"));
+        let strip = |line: &String| line.split('\x1b').map(|part| part.split_once('m').map_or(part, |(_, rest)| rest)).collect::<String>();
+        assert_eq!(colored.iter().map(strip).collect::<Vec<_>>(), plain);
+    }
+
+    #[test]
+    fn test_write_fasta_wrap() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        Sequences::set_name(">long".to_string());
+        Sequences::set_seq("A".repeat(150)).unwrap();
+        let rows = vec!["A".repeat(150)];
+
+        let mut out = Vec::new();
+        write_fasta(&mut out, &rows, 60).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], ">long");
+        assert_eq!(lines[1].len(), 60);
+        assert_eq!(lines[3].len(), 30);
+
+        let mut out = Vec::new();
+        write_fasta(&mut out, &rows, 0).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_verify_reconstruction() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        Sequences::set_seq("ACGT".to_string()).unwrap();
+        Sequences::set_seq("AGT".to_string()).unwrap();
+
+        let path: Vec<Node<2>> = [[0, 0], [1, 1], [2, 1], [3, 2], [4, 3]]
+            .into_iter()
+            .map(|c| Node::with_values(0, Coord::from_array(c), 0))
+            .collect();
+        let aligned = reconstruct_alignment(&path, true).unwrap();
+        assert_eq!(aligned, vec!["ACGT".to_string(), "A-GT".to_string()]);
+
+        // Skipping a step drops a residue
+        let corrupted = [&path[..2], &path[3..]].concat();
+        let result = reconstruct_alignment(&corrupted, true);
+        assert!(result.unwrap_err().contains("sequence 0"));
+    }
 
     #[test]
-    fn test_backtrace() {
-        // Test would require setting up full alignment
-        assert!(true);
+    fn test_trailing_gaps_after_short_sequences() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        for seq in ["ACGTACGTAC", "AC", "GTA"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        // The second sequence ends after two steps and the third after
+        // four, the last six only advance the first
+        let coords = [[0, 0, 0], [1, 1, 0], [2, 2, 1], [3, 2, 2], [4, 2, 3], [5, 2, 3],
+                      [6, 2, 3], [7, 2, 3], [8, 2, 3], [9, 2, 3], [10, 2, 3]];
+        let path: Vec<Node<3>> = coords.iter().map(|&c| Node::with_values(0, Coord::from_array(c), 0)).collect();
+        let rows = reconstruct_alignment(&path, true).unwrap();
+        assert_eq!(rows, ["ACGTACGTAC", "AC--------", "-GTA------"]);
+
+        // A final step that pushes a consumed sequence past its end only
+        // adds a gap, caught as an invalid move
+        let mut past_end = path.clone();
+        *past_end.last_mut().unwrap() = Node::with_values(0, Coord::from_array([10, 3, 3]), 0);
+        assert_eq!(reconstruct_alignment(&past_end, false).unwrap()[1], "AC--------");
+        let err = reconstruct_alignment(&past_end, true).unwrap_err();
+        assert!(err.contains("Path step 9"), "{}", err);
+
+        // So does a step that advances nothing
+        let stalled = [&path[..], &path[10..]].concat();
+        assert!(reconstruct_alignment(&stalled, true).unwrap_err().contains("Path step 10"));
+    }
+
+    #[test]
+    fn test_search_with_very_different_lengths() {
+        use crate::astar;
+        use crate::cost::Cost;
+        use crate::heuristic_hpair::HeuristicHPair;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        let seqs = ["ACGTTGCAACGTTGCAAGGT", "ACG", "T", "GCAAGG"];
+        for seq in seqs {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        HeuristicHPair::init().unwrap();
+        let options = AStarOpt::builder().verify_reconstruction(true).build();
+        let alignment = astar::run_astar_uncached(&options).unwrap();
+        assert_eq!(alignment.length(), seqs[0].len());
+        for (row, seq) in alignment.seqs().iter().zip(seqs) {
+            assert_eq!(row.len(), alignment.length());
+            assert_eq!(row.replace('-', ""), seq);
+        }
+    }
+
+    #[test]
+    fn test_backtrace_zero_cost_prefix() {
+        use crate::astar;
+        use crate::cost::Cost;
+        use crate::heuristic_hpair::HeuristicHPair;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        Sequences::set_seq("ACGTA".to_string()).unwrap();
+        Sequences::set_seq("ACGA".to_string()).unwrap();
+        HeuristicHPair::init().unwrap();
+
+        // The matching prefix costs nothing, so g is 0 before the origin
+        let options = AStarOpt::builder().verify_reconstruction(true).build();
+        let alignment = astar::run_astar_for_sequences(&options).unwrap();
+        assert_eq!(alignment.seqs()[0].replace('-', ""), "ACGTA");
+        assert_eq!(alignment.seqs()[1].replace('-', ""), "ACGA");
+
+        let scores = alignment.search_scores().unwrap();
+        assert_eq!((scores.f, scores.g, scores.h), (alignment.sp_score(), alignment.sp_score(), 0));
+        assert!(scores.underestimate() >= 0);
+    }
+
+    #[test]
+    fn test_canonical_path_fails_without_parent() {
+        use crate::cost::Cost;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        Sequences::set_seq("ACGT".to_string()).unwrap();
+        Sequences::set_seq("AGT".to_string()).unwrap();
+        HeuristicHPair::init().unwrap();
+
+        // No path reaches the goal for less than the optimal cost
+        let best = HeuristicHPair::calculate_h(&Coord::<2>::new(0));
+        let goal = Node::with_values(best - 1, Coord::from_array([4, 3]), 0);
+        let closed: AHashMap<Coord<2>, Node<2>> = AHashMap::new();
+        let costs = SearchCosts::for_options(&AStarOpt::builder().build()).unwrap();
+        assert!(canonical_path(&goal, &closed, &costs).is_none());
+
+        let goal = Node::with_values(best, Coord::from_array([4, 3]), 0);
+        let path = canonical_path(&goal, &closed, &costs).unwrap();
+        assert_eq!(reconstruct_alignment(&path, true).unwrap()[1].replace('-', ""), "AGT");
+    }
+
+    #[test]
+    fn test_save_partial_on_cancel() {
+        use crate::astar;
+        use crate::cost::Cost;
+        use crate::heuristic_hpair::HeuristicHPair;
+        use crate::interrupt;
+        use crate::read_fasta::read_fasta_records;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        let inputs = ["ACGTTGCA", "AGTTGA", "ACGTGCAA"];
+        for (i, seq) in inputs.iter().enumerate() {
+            Sequences::set_name(format!(">s{}", i));
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        HeuristicHPair::init().unwrap();
+
+        let path = std::env::temp_dir().join(format!("partial_{}.fasta", std::process::id()));
+        let filename = path.to_str().unwrap();
+        let options = AStarOpt::builder().save_on_interrupt(filename).build();
+
+        interrupt::cancel();
+        let result = astar::run_astar_for_sequences(&options);
+        interrupt::reset();
+        assert!(result.unwrap_err().contains("interrupted"));
+
+        // The saved rows still hold every input residue
+        let records = read_fasta_records(filename).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), inputs.len());
+        let len = records[0].1.len();
+        for ((_, row), input) in records.iter().zip(inputs) {
+            assert_eq!(row.len(), len);
+            assert_eq!(row.replace('-', ""), input);
+        }
+    }
+
+    #[test]
+    fn test_score_only_known_alignment() {
+        use crate::cost::Cost;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+
+        let path = std::env::temp_dir().join(format!("score_only_{}.fasta", std::process::id()));
+        let filename = path.to_str().unwrap();
+        std::fs::write(&path, ">a\nAC-T\n>b\nAG-T\n>c\nA-GT\n").unwrap();
+        let alignment = run_score_only(filename, &AStarOpt::builder().build()).unwrap();
+        assert_eq!(alignment.sp_score(), 11);
+        assert_eq!(Sequences::get_seq(2), b"AGT".to_vec());
+
+        std::fs::write(&path, ">a\nAC-T\n>b\nAGT\n").unwrap();
+        let err = run_score_only(filename, &AStarOpt::builder().build()).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.contains("row 1 has length 3"));
+    }
+
+    #[test]
+    fn test_audit_heuristic_flags_inflated_estimate() {
+        use crate::cost::Cost;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        Sequences::set_seq("ACGT".to_string()).unwrap();
+        Sequences::set_seq("AGT".to_string()).unwrap();
+        HeuristicHPair::init().unwrap();
+
+        // ACGT / A-GT, the gap costs 2
+        let path: Vec<Node<2>> = [([0, 0], 0), ([1, 1], 0), ([2, 1], 2), ([3, 2], 2), ([4, 3], 2)]
+            .into_iter()
+            .map(|(pos, g)| Node::with_values(g, Coord::from_array(pos), 0))
+            .collect();
+        assert!(audit_heuristic(&path, 2, HeuristicHPair::calculate_h).is_empty());
+
+        let inflated = |pos: &Coord<2>| HeuristicHPair::calculate_h(pos) + 1;
+        let violations = audit_heuristic(&path, 2, inflated);
+        assert_eq!(violations.len(), path.len());
+        assert_eq!(violations[0], (Coord::new(0), 3, 2));
+    }
+
+    #[test]
+    fn test_unwritable_output_is_an_error() {
+        use crate::astar;
+        use crate::cost::Cost;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        Sequences::set_seq("ACGT".to_string()).unwrap();
+        Sequences::set_seq("AGT".to_string()).unwrap();
+        HeuristicHPair::init().unwrap();
+
+        let path = std::env::temp_dir().join("no_such_dir").join("out.fasta");
+        let options = AStarOpt::builder().output_file(path.to_str().unwrap()).build();
+        let err = astar::run_astar_for_sequences(&options).unwrap_err();
+        assert!(err.starts_with("Error writing FASTA file"));
+    }
+
+    #[test]
+    fn test_summary_only_skips_body() {
+        use crate::astar;
+        use crate::cost::Cost;
+        use crate::logger;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        Sequences::set_seq("ACGTTGCAACGT".to_string()).unwrap();
+        Sequences::set_seq("ACGTGCAACCT".to_string()).unwrap();
+        HeuristicHPair::init().unwrap();
+        let dir = std::env::temp_dir();
+        let log = dir.join(format!("summary_only_{}.log", std::process::id()));
+        let output = dir.join(format!("summary_only_{}.fasta", std::process::id()));
+
+        logger::open_log_file(log.to_str().unwrap()).unwrap();
+        let options = AStarOpt::builder().summary_only(true).output_file(output.to_str().unwrap()).build();
+        let alignment = astar::run_astar_for_sequences(&options).unwrap();
+        logger::close_log_file();
+
+        let text = std::fs::read_to_string(&log).unwrap();
+        assert!(text.contains(&format!("] Sum-of-pairs score: {}\n", alignment.sp_score())));
+        assert!(text.contains("] Similarity: "));
+        assert!(text.contains("] Final Score: "));
+        assert!(!text.contains(&alignment.seqs()[1]));
+        assert!(!output.exists());
+        std::fs::remove_file(&log).unwrap();
     }
 }
@@ -0,0 +1,87 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Build details reported by `--version --verbose`
+ */
+
+use std::ops::RangeInclusive;
+use crate::{MAX_SEARCH_SEQUENCES, MAX_SEQUENCES, VERSION};
+
+/// Sequence counts the exact searches dispatch to
+pub const SEARCH_SEQUENCES: RangeInclusive<usize> = 2..=MAX_SEARCH_SEQUENCES;
+
+/// Sequence counts `dispatch_by_n!` has an arm for, as listed by `--supported-n`
+pub fn supported_n() -> Vec<usize> {
+    (0..=MAX_SEQUENCES)
+        .filter(|&n| dispatch_by_n!(n, N => Ok::<usize, String>(N)) == Ok(n))
+        .collect()
+}
+
+/// Cargo features compiled into this build
+pub fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "simd") {
+        features.push("simd");
+    }
+    if cfg!(feature = "python") {
+        features.push("python");
+    }
+    if cfg!(feature = "serde") {
+        features.push("serde");
+    }
+    features
+}
+
+/// Version, features and supported sequence counts
+pub fn build_info() -> String {
+    let features = enabled_features();
+    format!(
+        "Version: {} (package {})\nFeatures: {}\nMax sequences: {}\nExact search sequences: {}-{}\n",
+        VERSION,
+        env!("CARGO_PKG_VERSION"),
+        if features.is_empty() { "none".to_string() } else { features.join(", ") },
+        MAX_SEQUENCES,
+        SEARCH_SEQUENCES.start(),
+        SEARCH_SEQUENCES.end(),
+    )
+}
+
+/// Whether the command line asks for `--version --verbose`, which clap's
+/// own version flag can't express
+pub fn verbose_version_requested<I: IntoIterator<Item = String>>(args: I) -> bool {
+    let (mut version, mut verbose) = (false, false);
+    for arg in args {
+        match arg.as_str() {
+            "-V" | "--version" => version = true,
+            "--verbose" => verbose = true,
+            _ => {}
+        }
+    }
+    version && verbose
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert!(info.contains(&format!("Version: {}", VERSION)));
+        assert!(info.contains("Exact search sequences: 2-8"));
+        assert_eq!(enabled_features().contains(&"simd"), cfg!(feature = "simd"));
+
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(verbose_version_requested(args(&["msa_astar", "--version", "--verbose"])));
+        assert!(verbose_version_requested(args(&["msa_astar", "--verbose", "-V"])));
+        assert!(!verbose_version_requested(args(&["msa_astar", "--version"])));
+    }
+
+    #[test]
+    fn test_dispatch_table_matches_search_range() {
+        assert_eq!(supported_n(), SEARCH_SEQUENCES.collect::<Vec<_>>());
+        let err = dispatch_by_n!(MAX_SEARCH_SEQUENCES + 1, N => Ok::<usize, String>(N)).unwrap_err();
+        assert!(err.contains("Supported: 2-8"));
+    }
+}
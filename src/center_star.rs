@@ -0,0 +1,256 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Center-star progressive alignment, a fast approximate fallback
+ * when the exact search space is too large
+ */
+
+use crate::alignment::Alignment;
+use crate::backtrace;
+use crate::heuristic_hpair::{PairHeuristic, SearchCosts};
+use crate::msa_options::AStarOpt;
+use crate::sequences::Sequences;
+use crate::time_counter::TimeCounter;
+
+/// Whether the options ask for the approximate alignment, either forced or
+/// because the lattice exceeds the `auto_approx` threshold
+pub fn use_approximation(options: &AStarOpt) -> bool {
+    if options.approx {
+        return true;
+    }
+    match options.auto_approx {
+        Some(threshold) if exceeds_lattice(threshold) => {
+            match Sequences::lattice_size() {
+                Some(size) => crate::info!("Lattice size {} exceeds {}, switching to center-star", size, threshold),
+                None => crate::info!("Lattice size overflows u128, switching to center-star"),
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Whether the search lattice has more than `threshold` nodes, counting an
+/// overflowing size as larger than any threshold
+pub fn exceeds_lattice(threshold: u128) -> bool {
+    Sequences::lattice_size().is_none_or(|size| size > threshold)
+}
+
+/// Build the center-star alignment of the loaded sequences from the
+/// pairwise alignments in `heuristic`
+pub fn center_star(heuristic: &PairHeuristic) -> Alignment {
+    let seq_num = Sequences::get_seq_num();
+    let mut totals = vec![0i64; seq_num];
+    for align in heuristic.aligns() {
+        let (i, j) = align.get_pair();
+        totals[i] += align.get_final_score() as i64;
+        totals[j] += align.get_final_score() as i64;
+    }
+    let center = (0..seq_num).min_by_key(|&i| totals[i]).unwrap_or(0);
+    star_alignment(heuristic, center)
+}
+
+/// Merge the pairwise alignments of `center` against every other sequence
+/// into one MSA, adding gaps to the center where any of them has an insert.
+/// `heuristic` must hold the pairs of `center`.
+pub fn star_alignment(heuristic: &PairHeuristic, center: usize) -> Alignment {
+    let seq_num = Sequences::get_seq_num();
+    let seqs: Vec<Vec<u8>> = (0..seq_num).map(Sequences::get_seq).collect();
+
+    // Pairwise alignments of the center against each other sequence, with
+    // the center always as the first row
+    let rows: Vec<(usize, Vec<u8>, Vec<u8>)> = heuristic.aligns()
+        .iter()
+        .filter_map(|align| {
+            let (i, j) = align.get_pair();
+            if i == center {
+                let (c, o) = align.traceback(&seqs[i], &seqs[j]);
+                Some((j, c, o))
+            } else if j == center {
+                let (o, c) = align.traceback(&seqs[i], &seqs[j]);
+                Some((i, c, o))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    // Progressive merge: once a gap, always a gap
+    let mut msa: Vec<(usize, Vec<u8>)> = vec![(center, seqs[center].clone())];
+    for (index, center_row, other_row) in rows {
+        let mut merged: Vec<Vec<u8>> = vec![Vec::new(); msa.len()];
+        let mut new_row = Vec::new();
+        let msa_center = msa[0].1.clone();
+        let (mut p, mut q) = (0, 0);
+
+        while p < msa_center.len() || q < center_row.len() {
+            let msa_gap = p < msa_center.len() && msa_center[p] == b'-';
+            let pair_gap = q < center_row.len() && center_row[q] == b'-';
+
+            if p < msa_center.len() && (msa_gap && !pair_gap || q >= center_row.len()) {
+                // Gap column already in the MSA
+                for (k, row) in merged.iter_mut().enumerate() {
+                    row.push(msa[k].1[p]);
+                }
+                new_row.push(b'-');
+                p += 1;
+            } else if q < center_row.len() && (pair_gap && !msa_gap || p >= msa_center.len()) {
+                // New gap column from the pairwise alignment
+                for row in merged.iter_mut() {
+                    row.push(b'-');
+                }
+                new_row.push(other_row[q]);
+                q += 1;
+            } else {
+                for (k, row) in merged.iter_mut().enumerate() {
+                    row.push(msa[k].1[p]);
+                }
+                new_row.push(other_row[q]);
+                p += 1;
+                q += 1;
+            }
+        }
+
+        for (k, row) in merged.into_iter().enumerate() {
+            msa[k].1 = row;
+        }
+        msa.push((index, new_row));
+    }
+
+    msa.sort_by_key(|(index, _)| *index);
+    let pairwise_scores = heuristic.pairwise_scores();
+    Alignment::new(Sequences::restore_input_order(
+        msa.into_iter()
+            .map(|(_, row)| String::from_utf8_lossy(&row).to_string())
+            .collect(),
+    ))
+    .with_pairwise_scores(pairwise_scores)
+}
+
+/// Fail if the approximate `alignment` costs more than `--max-cost`. It
+/// isn't optimal, so a cheaper alignment may still exist.
+fn check_max_cost(alignment: &Alignment, options: &AStarOpt) -> Result<(), String> {
+    match options.max_cost {
+        Some(max_cost) => {
+            let cost = alignment.sp_score_with(&options.costs());
+            if cost > max_cost {
+                return Err(format!("Approximate alignment costs {}, over the maximum cost {}", cost, max_cost));
+            }
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Run the center-star fallback and report it like an exact alignment
+pub fn run_center_star(options: &AStarOpt) -> Result<Alignment, String> {
+    let _timer = TimeCounter::new("\nPhase 2: Center-star running time:");
+    crate::info!("Warning: approximate alignment (center-star), not guaranteed optimal");
+
+    let alignment = center_star(&SearchCosts::for_options(options)?.heuristic);
+    check_max_cost(&alignment, options)?;
+    backtrace::report_alignment(&alignment, options)?;
+    Ok(alignment)
+}
+
+/// Align every sequence to sequence 0 on its own and report the stack
+pub fn run_to_reference(options: &AStarOpt) -> Result<Alignment, String> {
+    let _timer = TimeCounter::new("\nPhase 2: Reference alignment running time:");
+    crate::info!("Aligning to reference {}", Sequences::get_seq_name(0));
+
+    let alignment = star_alignment(&SearchCosts::for_options(options)?.heuristic, 0);
+    check_max_cost(&alignment, options)?;
+    backtrace::report_alignment(&alignment, options)?;
+    Ok(alignment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar;
+    use crate::cost::Cost;
+    use crate::heuristic_hpair::HeuristicHPair;
+
+    fn degap(row: &str) -> String {
+        row.chars().filter(|&c| c != '-').collect()
+    }
+
+    #[test]
+    fn test_center_star_bounds_optimal() {
+        let _guard = crate::test_lock();
+        let inputs = ["VLSPADKTNVKAAWGKV", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVE", "VLSEGEWQLVLHVWAKV"];
+        crate::test_support::pam_fixture(&inputs);
+
+        let approx = center_star(&HeuristicHPair::installed());
+        let len = approx.seqs()[0].len();
+        for (row, input) in approx.seqs().iter().zip(inputs) {
+            assert_eq!(row.len(), len);
+            assert_eq!(degap(row), input);
+        }
+
+        let exact = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+        assert!(approx.sp_score() >= exact.sp_score());
+
+        // --max-cost applies to the approximation too
+        for options in [AStarOpt::builder().approx(true), AStarOpt::builder().to_reference(true)] {
+            let cost = astar::run_astar_for_sequences(&options.clone().build()).unwrap().sp_score();
+            let err = astar::run_astar_for_sequences(&options.clone().max_cost(cost - 1).build()).unwrap_err();
+            assert_eq!(err, format!("Approximate alignment costs {}, over the maximum cost {}", cost, cost - 1));
+            assert!(astar::run_astar_for_sequences(&options.max_cost(cost).build()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_to_reference_stacks_pairwise_alignments() {
+        use crate::pair_align::PairAlign;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        let inputs = ["ACGTACGTAC", "ACGTTACGAC", "AGTACGTAC", "ACGTACGTACGG", "CGTACTAC",
+                      "ACGACGTAC", "ACGTACCGTAC", "TACGTAC", "ACGTAGTAC", "ACGTACGAAC"];
+        for seq in inputs {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        // Only the reference pairs get aligned, not all 45
+        HeuristicHPair::init_reference_with(true).unwrap();
+        assert_eq!(HeuristicHPair::pairwise_scores().len(), inputs.len() - 1);
+        assert!(HeuristicHPair::pairwise_scores().iter().all(|((i, _), _)| *i == 0));
+
+        // More sequences than the exact search supports
+        let options = AStarOpt::builder().to_reference(true).build();
+        let alignment = astar::run_astar_for_sequences(&options).unwrap();
+        let rows = alignment.seqs();
+        assert_eq!(rows.len(), inputs.len());
+        for (row, input) in rows.iter().zip(inputs) {
+            assert_eq!(row.len(), rows[0].len());
+            assert_eq!(degap(row), input);
+        }
+
+        // Dropping shared gap columns gives back each pairwise alignment
+        for (j, input) in inputs.iter().enumerate().skip(1) {
+            let (reference, other): (String, String) = rows[0].chars()
+                .zip(rows[j].chars())
+                .filter(|&(a, b)| a != '-' || b != '-')
+                .unzip();
+            let pair = PairAlign::new((0, j), inputs[0].as_bytes(), input.as_bytes());
+            let (pair_ref, pair_other) = pair.traceback(inputs[0].as_bytes(), input.as_bytes());
+            assert_eq!((reference.into_bytes(), other.into_bytes()), (pair_ref, pair_other));
+        }
+    }
+
+    #[test]
+    fn test_use_approximation() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        Sequences::set_seq("ACGT".to_string()).unwrap();
+        Sequences::set_seq("ACG".to_string()).unwrap();
+        assert_eq!(Sequences::lattice_size(), Some(20));
+
+        assert!(AStarOpt::builder().approx(true).build().approx);
+        assert!(use_approximation(&AStarOpt::builder().auto_approx(19).build()));
+        assert!(!use_approximation(&AStarOpt::builder().auto_approx(20).build()));
+        assert!(!use_approximation(&AStarOpt::builder().build()));
+    }
+}
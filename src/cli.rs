@@ -0,0 +1,356 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Setup and reporting shared by the msa_astar and msa_pastar
+ * binaries, which only parse their options and pick the search
+ */
+
+use crate::align::PreparedProblem;
+use crate::alignment::Alignment;
+use crate::alignment_diff;
+use crate::backtrace;
+use crate::build_info;
+use crate::center_star;
+use crate::cost::{Cost, Matrix, StructureScoring, DEFAULT_IDENTITY_MISMATCH};
+use crate::heuristic_hpair::HeuristicHPair;
+use crate::interrupt;
+use crate::logger;
+use crate::msa_options::{AStarOptions, AStarOpt, PAStarOptions, PAStarOpt};
+use crate::pair_align;
+use crate::read_fasta::{read_fasta_file_bytes, read_gap_profile, read_inline_sequences, read_ss_file, write_fasta_index};
+use crate::sequences::{Region, Sequences, StopHandling};
+use crate::stats_csv;
+use crate::time_counter;
+use crate::translate::{GeneticCode, Translation};
+use crate::VERSION;
+
+/// Command-line settings both binaries handle the same way, outside the
+/// search options
+#[derive(Clone, Debug)]
+pub struct CliArgs {
+    pub json: bool,
+    pub supported_n: bool,
+    pub quiet: bool,
+    pub log_file: Option<String>,
+    pub input_file: Option<String>,
+    pub seq: Vec<String>,
+    pub name: Vec<String>,
+    pub strict_fasta: bool,
+    pub fai: bool,
+    pub score_only: Option<String>,
+    /// Only `msa_astar` aligns profiles
+    pub profile: Option<Vec<String>>,
+    pub nucleotide: bool,
+    pub matrix: Option<Matrix>,
+    pub mismatch: Option<i32>,
+    pub matrix_file: Option<String>,
+    pub matrix_scale: Option<f64>,
+    pub translate: bool,
+    pub frame: u8,
+    pub genetic_code: GeneticCode,
+    pub back_translate: Option<String>,
+    pub stop_handling: StopHandling,
+    pub gap_profile: Option<String>,
+    pub ss: Option<String>,
+    pub ss_bonus: i32,
+    pub ss_penalty: i32,
+    pub region: Vec<Region>,
+    /// Only `msa_pastar` reorders the sequences
+    pub first_seq: Option<usize>,
+    pub print_matrix: bool,
+    pub self_reverse: bool,
+    pub approx: bool,
+    pub auto_approx: Option<u128>,
+    pub to_reference: bool,
+    pub save_on_interrupt: bool,
+    pub no_heuristic_parallel: bool,
+    pub repeat: Option<u32>,
+    pub diff: Option<String>,
+    pub stats_csv: Option<String>,
+}
+
+/// `CliArgs` from an options struct, both name these fields alike
+macro_rules! cli_args {
+    ($opts:expr, profile: $profile:expr, first_seq: $first_seq:expr) => {
+        CliArgs {
+            json: $opts.json,
+            supported_n: $opts.supported_n,
+            quiet: $opts.quiet,
+            log_file: $opts.log_file.clone(),
+            input_file: $opts.input_file.clone(),
+            seq: $opts.seq.clone(),
+            name: $opts.name.clone(),
+            strict_fasta: $opts.strict_fasta,
+            fai: $opts.fai,
+            score_only: $opts.score_only.clone(),
+            profile: $profile,
+            nucleotide: $opts.nucleotide,
+            matrix: $opts.matrix,
+            mismatch: $opts.mismatch,
+            matrix_file: $opts.matrix_file.clone(),
+            matrix_scale: $opts.matrix_scale,
+            translate: $opts.translate,
+            frame: $opts.frame,
+            genetic_code: $opts.genetic_code,
+            back_translate: $opts.back_translate.clone(),
+            stop_handling: $opts.stop_handling,
+            gap_profile: $opts.gap_profile.clone(),
+            ss: $opts.ss.clone(),
+            ss_bonus: $opts.ss_bonus,
+            ss_penalty: $opts.ss_penalty,
+            region: $opts.region.clone(),
+            first_seq: $first_seq,
+            print_matrix: $opts.print_matrix,
+            self_reverse: $opts.self_reverse,
+            approx: $opts.approx,
+            auto_approx: $opts.auto_approx,
+            to_reference: $opts.to_reference,
+            save_on_interrupt: $opts.save_on_interrupt.is_some(),
+            no_heuristic_parallel: $opts.no_heuristic_parallel,
+            repeat: $opts.repeat,
+            diff: $opts.diff.clone(),
+            stats_csv: $opts.stats_csv.clone(),
+        }
+    };
+}
+
+impl From<&AStarOptions> for CliArgs {
+    fn from(opts: &AStarOptions) -> Self {
+        cli_args!(opts, profile: opts.profile.clone(), first_seq: None)
+    }
+}
+
+impl From<&PAStarOptions> for CliArgs {
+    fn from(opts: &PAStarOptions) -> Self {
+        cli_args!(opts, profile: None, first_seq: opts.first_seq)
+    }
+}
+
+/// Print `Error<context>: <e>` and quit on an error
+fn or_exit<T, E: std::fmt::Display>(result: Result<T, E>, context: &str) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("Error{}: {}", context, e);
+        std::process::exit(1);
+    })
+}
+
+/// Answer `--supported-n`, start the logger, print the banner naming
+/// `program` and select the cost matrix. Quits after `--supported-n`.
+pub fn start(args: &CliArgs, program: &str) {
+    if args.supported_n {
+        let counts: Vec<String> = build_info::supported_n().iter().map(|n| n.to_string()).collect();
+        println!("{}", counts.join(" "));
+        std::process::exit(0);
+    }
+
+    logger::set_quiet(args.quiet);
+    if let Some(file) = &args.log_file {
+        or_exit(logger::open_log_file(file), "");
+    }
+
+    crate::info!("{} version {}", program, VERSION);
+    match (&args.input_file, &args.profile) {
+        (Some(file), _) => crate::info!("Input file: {}", file),
+        (None, Some(files)) => crate::info!("Input profiles: {} and {}", files[0], files[1]),
+        (None, None) if args.score_only.is_some() => {}
+        (None, None) => crate::info!("Input: {} inline sequences", args.seq.len()),
+    }
+
+    select_matrix(args);
+}
+
+/// Make the matrix the options ask for the active costs
+fn select_matrix(args: &CliArgs) {
+    let matrix = args.matrix.unwrap_or(if args.nucleotide { Matrix::Nucleotide } else { Matrix::Pam250 });
+    if args.mismatch.is_some() && matrix != Matrix::Identity {
+        eprintln!("Error: --mismatch needs --matrix identity");
+        std::process::exit(1);
+    }
+    if let Some(file) = &args.matrix_file {
+        let scale = args.matrix_scale.unwrap_or(1.0);
+        or_exit(Cost::set_matrix_file(file, scale), "");
+        crate::info!("Using cost matrix {} (scale {})", file, scale);
+        return;
+    }
+    match matrix {
+        Matrix::Nucleotide => {
+            crate::info!("Using nucleotide cost matrix");
+            Cost::set_cost_nuc();
+        }
+        Matrix::Pam250 => {
+            crate::info!("Using PAM250 cost matrix");
+            Cost::set_cost_pam250();
+        }
+        Matrix::Blosum62 => {
+            crate::info!("Using BLOSUM62 cost matrix");
+            Cost::set_cost_blosum62();
+        }
+        Matrix::Identity => {
+            let mismatch = args.mismatch.unwrap_or(DEFAULT_IDENTITY_MISMATCH);
+            crate::info!("Using identity costs: mismatch {}, gap {}", mismatch, 2 * mismatch);
+            Cost::set_cost_identity(mismatch, 2 * mismatch);
+        }
+    }
+}
+
+/// `--score-only`: score the aligned file and quit
+pub fn score_only(file: &str, options: &AStarOpt) -> ! {
+    or_exit(backtrace::run_score_only(file, options), " scoring alignment");
+    std::process::exit(0);
+}
+
+/// Load the sequences and everything the options attach to them, then
+/// build the heuristic. Returns the translation `--back-translate` needs.
+/// Quits after `--self-reverse`, which needs no search.
+pub fn load(args: &CliArgs) -> Option<Translation> {
+    let loaded = match &args.input_file {
+        Some(file) => read_fasta_file_bytes(file, args.strict_fasta),
+        None => read_inline_sequences(&args.seq, &args.name),
+    };
+    or_exit(loaded, " reading sequences");
+
+    if args.fai
+        && let Some(file) = &args.input_file {
+        match write_fasta_index(file) {
+            Ok(path) => crate::info!("Wrote FASTA index {}", path.display()),
+            Err(e) => eprintln!("Warning: --fai: {}", e),
+        }
+    }
+
+    // Coding sequences are aligned as their proteins
+    let translation = args.translate
+        .then(|| or_exit(Translation::apply(args.frame as usize, args.genetic_code), ": --translate"));
+
+    or_exit(Sequences::normalize(args.stop_handling), "");
+    if let Some(file) = &args.gap_profile {
+        or_exit(read_gap_profile(file), ": --gap-profile");
+    }
+    if let Some(file) = &args.ss {
+        or_exit(read_ss_file(file), ": --ss");
+        Cost::set_structure_scoring(StructureScoring { bonus: args.ss_bonus, penalty: args.ss_penalty });
+    }
+    for region in &args.region {
+        or_exit(Sequences::restrict_to_region(region), ": --region");
+    }
+    if let Some(index) = args.first_seq {
+        or_exit(Sequences::move_to_front(index), ": --first-seq");
+        crate::info!("Sequence {} placed in dimension 0", index);
+    }
+
+    let seq_num = Sequences::get_seq_num();
+    crate::info!("Number of sequences: {}", seq_num);
+    for i in 0..seq_num {
+        crate::info!("Sequence {}: {} (length: {})", i, Sequences::get_seq_name(i), Sequences::get_seq_len(i));
+    }
+
+    if args.print_matrix {
+        crate::info!("\nCost matrix:\n{}", Cost::matrix_snapshot().format(&Sequences::residues()));
+    }
+
+    if args.self_reverse {
+        for i in 0..seq_num {
+            let (reverse, itself) = or_exit(pair_align::reverse_score(i), "");
+            crate::info!("Sequence {} against its reverse: cost {} (against itself: {})", i, reverse, itself);
+        }
+        std::process::exit(0);
+    }
+
+    // Fail before the heuristic phase if the search can't handle this many
+    let approx = args.approx || args.auto_approx.is_some_and(center_star::exceeds_lattice);
+    if !approx && !args.to_reference {
+        or_exit(Sequences::check_search_count(), "");
+    }
+
+    if args.save_on_interrupt
+        && let Err(e) = interrupt::install_handler() {
+        eprintln!("Warning: {}", e);
+    }
+
+    // Initialize heuristic. Aligning to the reference needs only its pairs
+    crate::info!("\nPhase 1: Initializing heuristic...");
+    let parallel = !args.no_heuristic_parallel;
+    let init = if args.to_reference {
+        HeuristicHPair::init_reference_with(parallel)
+    } else {
+        HeuristicHPair::init_with(parallel)
+    };
+    or_exit(init, "");
+    translation
+}
+
+/// Run `search` once, or `--repeat` times for timing: those runs skip the
+/// result cache, and all but the first the output. `search` gets the
+/// options to run with and whether it may use the cache.
+pub fn run_search(
+    args: &CliArgs,
+    options: &AStarOpt,
+    search: impl Fn(&AStarOpt, bool) -> Result<Alignment, String>,
+) -> Result<Alignment, String> {
+    match args.repeat {
+        Some(times) => PreparedProblem::capture()
+            .repeat(times as usize, |first| {
+                let mut run_options = options.clone();
+                run_options.summary_only |= !first;
+                search(&run_options, false)
+            })
+            .map(|(alignment, _)| alignment),
+        None => search(options, true),
+    }
+}
+
+/// Report `result` and write what the options ask for after the search,
+/// quitting on a failed search. `parallel` are the PA-Star options, for
+/// the statistics row.
+pub fn finish(
+    args: &CliArgs,
+    result: Result<Alignment, String>,
+    translation: Option<Translation>,
+    options: &AStarOpt,
+    parallel: Option<&PAStarOpt>,
+) {
+    let alignment = or_exit(result, " during alignment");
+    crate::info!("\nAlignment completed successfully!");
+    if let Some(file) = &args.diff
+        && let Err(e) = alignment_diff::report_diff(file, &alignment) {
+        eprintln!("Warning: --diff: {}", e);
+    }
+    if let Some((file, translation)) = args.back_translate.as_ref().zip(translation) {
+        or_exit(translation.write_codons(file, &alignment, options), ": --back-translate");
+    }
+    if let Some(file) = &args.stats_csv {
+        let input = args.input_file.as_deref().unwrap_or("inline");
+        let row = stats_csv::stats_row(input, &alignment, options.identity_mode, parallel);
+        if let Err(e) = stats_csv::append_row(file, &row) {
+            eprintln!("Warning: --stats-csv: {}", e);
+        }
+    }
+    if args.json {
+        println!("{}", time_counter::timings_json());
+    }
+    if options.force_quit {
+        std::process::exit(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_both_binaries_share_the_cli_args() {
+        let argv = ["msa", "--seq", "ACGT", "--seq", "AGT", "--name", "a", "--name", "b", "--matrix", "identity",
+                    "--mismatch", "3", "--translate", "--frame", "2", "--back-translate", "codons.fasta",
+                    "--ss", "labels.txt", "--ss-bonus", "4", "--repeat", "3", "--stats-csv", "stats.csv", "--save-on-interrupt", "partial.fasta"];
+        let serial = CliArgs::from(&AStarOptions::try_parse_from(argv).unwrap());
+        let mut parallel = CliArgs::from(&PAStarOptions::try_parse_from(argv.iter().chain(&["--first-seq", "1"])).unwrap());
+        assert_eq!(parallel.first_seq, Some(1));
+        assert_eq!((serial.profile.clone(), serial.first_seq), (None, None));
+
+        parallel.first_seq = None;
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", serial));
+        assert_eq!((serial.seq.len(), serial.name.len(), serial.frame, serial.repeat), (2, 2, 2, Some(3)));
+        assert!(serial.translate && serial.save_on_interrupt);
+    }
+}
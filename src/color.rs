@@ -0,0 +1,113 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief ANSI colors for the alignment printed to the terminal
+ */
+
+use std::ffi::OsStr;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+/// When the terminal view of the alignment is colored
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorMode {
+    /// Only when stdout is a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(format!("Unknown color mode: {}", s)),
+        }
+    }
+}
+
+impl ColorMode {
+    /// Whether to color output printed now
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => auto_color(std::io::stdout().is_terminal(), std::env::var_os("NO_COLOR").as_deref()),
+        }
+    }
+}
+
+/// `ColorMode::Auto` for a stdout that is or isn't a terminal and the value
+/// of `NO_COLOR`, which turns colors off unless empty (no-color.org)
+fn auto_color(is_terminal: bool, no_color: Option<&OsStr>) -> bool {
+    is_terminal && no_color.is_none_or(OsStr::is_empty)
+}
+
+/// ANSI foreground color of a residue: amino acids by class, or bases
+fn residue_color(c: u8, nucleotide: bool) -> Option<u8> {
+    let c = c.to_ascii_uppercase();
+    if nucleotide {
+        return match c {
+            b'A' => Some(32),
+            b'C' => Some(34),
+            b'G' => Some(33),
+            b'T' | b'U' => Some(31),
+            _ => None,
+        };
+    }
+    match c {
+        // Hydrophobic
+        b'A' | b'V' | b'L' | b'I' | b'M' | b'F' | b'W' | b'C' => Some(34),
+        // Positive and negative charge
+        b'K' | b'R' => Some(31),
+        b'D' | b'E' => Some(35),
+        // Polar
+        b'N' | b'Q' | b'S' | b'T' => Some(32),
+        b'G' | b'P' => Some(33),
+        // Aromatic and polar
+        b'H' | b'Y' => Some(36),
+        _ => None,
+    }
+}
+
+/// `segment` of an alignment row with each residue in its color, bold in
+/// the columns marked `conserved`. Gaps and unknown residues stay plain.
+pub fn paint(segment: &str, conserved: &[bool], nucleotide: bool) -> String {
+    let mut out = String::with_capacity(segment.len() * 8);
+    for (c, &bold) in segment.bytes().zip(conserved) {
+        match residue_color(c, nucleotide) {
+            Some(color) if bold => out.push_str(&format!("\x1b[1;{}m{}\x1b[0m", color, c as char)),
+            Some(color) => out.push_str(&format!("\x1b[{}m{}\x1b[0m", color, c as char)),
+            None => out.push(c as char),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_modes() {
+        assert!(ColorMode::Always.enabled());
+        assert!(!ColorMode::Never.enabled());
+        assert_eq!("NEVER".parse::<ColorMode>(), Ok(ColorMode::Never));
+        assert!("sometimes".parse::<ColorMode>().is_err());
+
+        assert!(auto_color(true, None));
+        assert!(auto_color(true, Some(OsStr::new(""))));
+        assert!(!auto_color(true, Some(OsStr::new("1"))));
+        assert!(!auto_color(false, None));
+
+        assert_eq!(paint("K-", &[true, true], false), "\x1b[1;31mK\x1b[0m-");
+        assert_eq!(paint("T", &[false], true), "\x1b[31mT\x1b[0m");
+    }
+}
@@ -0,0 +1,289 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Alignment output written column by column from the search path,
+ * without building the aligned rows first, for genome-length inputs
+ */
+
+use std::io::{self, Write};
+use crate::alignment::ColumnStats;
+use crate::backtrace::path_columns;
+use crate::cost::ScoringScheme;
+use crate::node::Node;
+use crate::sequences::Sequences;
+
+/// Columns per block of CLUSTAL output
+pub const CLUSTAL_BLOCK: usize = 60;
+
+/// Formats that can be written from a stream of columns
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StreamFormat {
+    /// One record per sequence, read off the path once per sequence
+    Fasta,
+    /// Interleaved blocks, holding one block of columns at a time
+    Clustal,
+}
+
+impl StreamFormat {
+    /// CLUSTAL for `.aln` and `.clustal` files, FASTA otherwise
+    pub fn for_filename(filename: &str) -> Self {
+        let lower = filename.to_lowercase();
+        if lower.ends_with(".aln") || lower.ends_with(".clustal") {
+            StreamFormat::Clustal
+        } else {
+            StreamFormat::Fasta
+        }
+    }
+}
+
+/// Loaded sequence written as each output row, so rows come out in input
+/// order like `Sequences::restore_input_order` puts them
+fn output_order<const N: usize>() -> [usize; N] {
+    let mut order = [0; N];
+    for i in 0..N {
+        order[Sequences::input_index(i)] = i;
+    }
+    order
+}
+
+/// Names of the output rows, in input order
+fn output_names(seq_num: usize) -> Vec<String> {
+    let mut names = vec![String::new(); seq_num];
+    for (i, name) in (0..seq_num).map(Sequences::get_seq_name).enumerate() {
+        names[Sequences::input_index(i)] = name;
+    }
+    names
+}
+
+/// Columns along `path` with the residues in output row order
+fn output_columns<const N: usize>(path: &[Node<N>]) -> impl Iterator<Item = [u8; N]> + '_ {
+    let order = output_order::<N>();
+    path_columns(path).map(move |column| order.map(|i| column[i]))
+}
+
+/// Write one FASTA record per name, reading its row off a fresh pass of
+/// `columns`, wrapping at `wrap` columns (0 = one line per sequence)
+fn write_fasta_columns<W, I, C>(out: &mut W, names: &[String], columns: impl Fn() -> I, wrap: usize) -> io::Result<()>
+where
+    W: Write,
+    I: Iterator<Item = C>,
+    C: AsRef<[u8]>,
+{
+    for (row, name) in names.iter().enumerate() {
+        writeln!(out, "{}", name)?;
+        let mut written = 0;
+        for column in columns() {
+            out.write_all(&column.as_ref()[row..=row])?;
+            written += 1;
+            if wrap > 0 && written % wrap == 0 {
+                writeln!(out)?;
+            }
+        }
+        if wrap == 0 || written % wrap != 0 {
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the alignment along `path` as FASTA, wrapping at `wrap` columns
+/// (0 = one line per sequence). Same output as `backtrace::write_fasta` on
+/// the reconstructed rows.
+pub fn stream_fasta<W: Write, const N: usize>(out: &mut W, path: &[Node<N>], wrap: usize) -> io::Result<()> {
+    write_fasta_columns(out, &output_names(N), || output_columns(path), wrap)
+}
+
+/// `name` as a CLUSTAL row label, which can't hold spaces. `row` numbers
+/// an unnamed sequence.
+fn clustal_name(name: &str, row: usize) -> String {
+    let words: Vec<&str> = name.trim_start_matches('>').split_whitespace().collect();
+    if words.is_empty() {
+        format!("Sequence_{}", row + 1)
+    } else {
+        words.join("_")
+    }
+}
+
+/// Write `columns` as CLUSTAL rows labeled by `names`, holding one block
+/// of `CLUSTAL_BLOCK` columns at a time, without conservation lines
+fn write_clustal_columns<W, C>(out: &mut W, names: &[String], mut columns: impl Iterator<Item = C>) -> io::Result<()>
+where
+    W: Write,
+    C: AsRef<[u8]>,
+{
+    let names: Vec<String> = names.iter().enumerate().map(|(row, name)| clustal_name(name, row)).collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0) + 4;
+
+    writeln!(out, "CLUSTAL W multiple sequence alignment")?;
+    let mut block: Vec<C> = Vec::with_capacity(CLUSTAL_BLOCK);
+    loop {
+        block.clear();
+        block.extend(columns.by_ref().take(CLUSTAL_BLOCK));
+        if block.is_empty() {
+            return Ok(());
+        }
+        writeln!(out)?;
+        for (row, name) in names.iter().enumerate() {
+            write!(out, "{:<width$}", name)?;
+            let residues: Vec<u8> = block.iter().map(|column| column.as_ref()[row]).collect();
+            out.write_all(&residues)?;
+            writeln!(out)?;
+        }
+    }
+}
+
+/// Write the alignment along `path` as CLUSTAL, `CLUSTAL_BLOCK` columns per
+/// block, without conservation lines
+pub fn stream_clustal<W: Write, const N: usize>(out: &mut W, path: &[Node<N>]) -> io::Result<()> {
+    write_clustal_columns(out, &output_names(N), output_columns(path))
+}
+
+/// Create `filename` and fill it with `write` in the format its name asks
+/// for (see `StreamFormat::for_filename`)
+fn write_file(
+    filename: &str,
+    write: impl FnOnce(&mut io::BufWriter<std::fs::File>, StreamFormat) -> io::Result<()>,
+) -> Result<(), String> {
+    let written = std::fs::File::create(filename).and_then(|file| {
+        let mut out = io::BufWriter::new(file);
+        write(&mut out, StreamFormat::for_filename(filename))?;
+        out.flush()
+    });
+    written.map_err(|e| format!("Error writing streamed alignment {}: {}", filename, e))
+}
+
+/// Write the alignment along `path` to `filename` in the format its name
+/// asks for (see `StreamFormat::for_filename`), never holding more than a
+/// block of columns. Returns its scores under `costs`, added up over the
+/// columns, in place of the rows it didn't build.
+pub fn stream_to_file<const N: usize>(
+    filename: &str,
+    path: &[Node<N>],
+    wrap: usize,
+    costs: &ScoringScheme,
+) -> Result<ColumnStats, String> {
+    write_file(filename, |out, format| match format {
+        StreamFormat::Fasta => stream_fasta(out, path, wrap),
+        StreamFormat::Clustal => stream_clustal(out, path),
+    })?;
+
+    let lens: Vec<usize> = (0..N).map(Sequences::get_seq_len).collect();
+    let mut stats = ColumnStats::new(&lens);
+    for column in path_columns(path) {
+        stats.add_column(costs, &column);
+    }
+    Ok(stats)
+}
+
+/// Write the rows of an alignment already in memory, in input order, to
+/// `filename` like `stream_to_file`. For results that didn't come from a
+/// search path: cache hits and the approximate alignments.
+pub fn write_rows_to_file(filename: &str, rows: &[String], wrap: usize) -> Result<(), String> {
+    let len = rows.first().map_or(0, String::len);
+    let columns = || (0..len).map(|pos| rows.iter().map(|row| row.as_bytes()[pos]).collect::<Vec<u8>>());
+    let names = output_names(rows.len());
+    write_file(filename, |out, format| match format {
+        StreamFormat::Fasta => write_fasta_columns(out, &names, columns, wrap),
+        StreamFormat::Clustal => write_clustal_columns(out, &names, columns()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtrace::{reconstruct_alignment, write_fasta};
+    use crate::coord::Coord;
+    use crate::cost::Cost;
+    use crate::read_fasta::read_clustal_records;
+
+    #[test]
+    fn test_streamed_output_matches_rows() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        Sequences::set_name(">first sequence".to_string());
+        Sequences::set_seq("ACGTTGCA".repeat(10)).unwrap();
+        Sequences::set_name(">second".to_string());
+        Sequences::set_seq("AGTTGA".repeat(10)).unwrap();
+
+        // Residue pairs, with a gap in the second sequence every fourth column
+        let mut path = vec![Node::with_values(0, Coord::<2>::from_array([0, 0]), 0)];
+        let (mut i, mut j) = (0, 0);
+        while i < 80 {
+            i += 1;
+            if i % 4 != 0 && j < 60 {
+                j += 1;
+            }
+            path.push(Node::with_values(0, Coord::from_array([i, j]), 0));
+        }
+        while j < 60 {
+            j += 1;
+            path.push(Node::with_values(0, Coord::from_array([i, j]), 0));
+        }
+        let rows = reconstruct_alignment(&path, true).unwrap();
+
+        for wrap in [0, 7, 60] {
+            let (mut streamed, mut in_memory) = (Vec::new(), Vec::new());
+            stream_fasta(&mut streamed, &path, wrap).unwrap();
+            write_fasta(&mut in_memory, &rows, wrap).unwrap();
+            assert_eq!(String::from_utf8(streamed).unwrap(), String::from_utf8(in_memory).unwrap());
+        }
+
+        let file = std::env::temp_dir().join(format!("column_stream_{}.aln", std::process::id()));
+        let file = file.to_str().unwrap();
+        stream_to_file(file, &path, 0, &Cost::matrix_snapshot()).unwrap();
+        let records = read_clustal_records(file).unwrap();
+        assert_eq!(records, [(">first_sequence".to_string(), rows[0].clone()), (">second".to_string(), rows[1].clone())]);
+        assert_eq!(std::fs::read_to_string(file).unwrap().lines().count(), 1 + 2 * 3);
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_every_path_writes_the_stream() {
+        use crate::alignment::{Alignment, IdentityMode};
+        use crate::astar;
+        use crate::msa_options::AStarOpt;
+
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKV", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVE"]);
+        let dir = std::env::temp_dir().join(format!("column_stream_paths_{}", std::process::id()));
+        let file = std::env::temp_dir().join(format!("column_stream_paths_{}.fasta", std::process::id()));
+        let (dir, file) = (dir.to_str().unwrap(), file.to_str().unwrap());
+        let fasta = |alignment: &Alignment| {
+            let mut out = Vec::new();
+            write_fasta(&mut out, alignment.seqs(), 7).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let run = |options: AStarOpt| {
+            let alignment = astar::run_astar_for_sequences(&options).unwrap();
+            (alignment, std::fs::read_to_string(file).unwrap())
+        };
+
+        // The searches score the columns instead of building the rows
+        let in_memory = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+        for options in [AStarOpt::builder(), AStarOpt::builder().max_gap(100)] {
+            let (streamed, written) = run(options.stream_output(file).wrap(7).build());
+            assert!(streamed.seqs().is_empty());
+            assert_eq!(written, fasta(&in_memory));
+            assert_eq!(streamed.seq_num(), in_memory.seq_num());
+            assert_eq!(streamed.length(), in_memory.length());
+            assert_eq!(streamed.input_length_bounds(), in_memory.input_length_bounds());
+            assert_eq!(streamed.sp_score(), in_memory.sp_score());
+            for mode in [IdentityMode::Strict, IdentityMode::Lenient] {
+                assert_eq!(streamed.similarity(mode), in_memory.similarity(mode));
+            }
+        }
+
+        // A cache hit and the approximation write the rows they have
+        astar::run_astar_for_sequences(&AStarOpt::builder().cache_dir(dir).build()).unwrap();
+        std::fs::remove_file(file).unwrap();
+        let (cached, written) = run(AStarOpt::builder().cache_dir(dir).stream_output(file).wrap(7).build());
+        assert_eq!(cached.seqs(), in_memory.seqs());
+        assert_eq!(written, fasta(&in_memory));
+        let (approx, written) = run(AStarOpt::builder().approx(true).stream_output(file).wrap(7).build());
+        assert_eq!(written, fasta(&approx));
+
+        std::fs::remove_file(file).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
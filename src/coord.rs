@@ -6,13 +6,34 @@
  */
 
 use std::fmt;
-use crate::coord_hash::HashType;
+use crate::coord_hash::{shifted, HashType};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Coord<const N: usize> {
     coords: [u16; N],
 }
 
+// serde only implements arrays up to a fixed size, so the coordinates go
+// through a sequence
+#[cfg(feature = "serde")]
+impl<const N: usize> serde::Serialize for Coord<N> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.coords.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const N: usize> serde::Deserialize<'de> for Coord<N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let coords: Vec<u16> = serde::Deserialize::deserialize(deserializer)?;
+        let len = coords.len();
+        let coords = coords.try_into().map_err(|_| {
+            serde::de::Error::invalid_length(len, &format!("{} coordinates", N).as_str())
+        })?;
+        Ok(Coord { coords })
+    }
+}
+
 impl<const N: usize> Coord<N> {
     pub fn new(init: u16) -> Self {
         Coord {
@@ -39,6 +60,28 @@ impl<const N: usize> Coord<N> {
         new_coord
     }
 
+    /// Advance dimension `dim` by one in place
+    pub fn incr(&mut self, dim: usize) {
+        self.coords[dim] += 1;
+    }
+
+    /// Undo `incr(dim)`
+    pub fn decr(&mut self, dim: usize) {
+        self.coords[dim] -= 1;
+    }
+
+    /// Componentwise `<=`. Unlike the lexicographic `Ord`, every
+    /// dimension must be at most the matching one in `other`.
+    pub fn le(&self, other: &Self) -> bool {
+        self.coords.iter().zip(other.coords.iter()).all(|(a, b)| a <= b)
+    }
+
+    /// Whether the goal can still be reached from here, i.e. no dimension
+    /// went past the end of its sequence
+    pub fn is_goal_reachable(&self, final_coord: &Self) -> bool {
+        self.le(final_coord)
+    }
+
     /// Calculate sum of all coordinates
     pub fn get_sum(&self) -> u32 {
         self.coords.iter().map(|&x| x as u32).sum()
@@ -62,31 +105,50 @@ impl<const N: usize> Coord<N> {
         result
     }
 
+    /// Z-order curve value without the first dimension's bits
+    pub fn get_part_z_order_curve(&self) -> u64 {
+        let z = self.get_z_order_curve();
+        // Remove first dimension bits
+        let mut result = 0u64;
+        let mut pos = 0;
+        for bit_idx in 0..64 {
+            if bit_idx % N != 0 {
+                result |= ((z >> bit_idx) & 1) << pos;
+                pos += 1;
+            }
+        }
+        result
+    }
+
+    /// Value `hash_type` distributes before shifting. The partial hashes
+    /// ignore dimension 0 (see `--first-seq`); with two sequences that
+    /// would key on a single coordinate, so they fall back to the full ones.
+    pub fn hash_value(&self, hash_type: HashType) -> u64 {
+        match hash_type {
+            HashType::FZorder => self.get_z_order_curve(),
+            HashType::PZorder if N <= 2 => self.get_z_order_curve(),
+            HashType::PZorder => self.get_part_z_order_curve(),
+            HashType::FSum => self.get_sum() as u64,
+            HashType::PSum if N <= 2 => self.get_sum() as u64,
+            HashType::PSum => self.get_part_sum() as u64,
+        }
+    }
+
     /// Hash functions for thread distribution
     pub fn sum_hash(&self, size: usize, shift: usize) -> usize {
-        ((self.get_sum() as usize) >> shift) % size
+        (shifted(self.hash_value(HashType::FSum), shift) as usize) % size
     }
 
     pub fn part_sum_hash(&self, size: usize, shift: usize) -> usize {
-        ((self.get_part_sum() as usize) >> shift) % size
+        (shifted(self.hash_value(HashType::PSum), shift) as usize) % size
     }
 
     pub fn z_order_hash(&self, size: usize, shift: usize) -> usize {
-        ((self.get_z_order_curve() as usize) >> shift) % size
+        (shifted(self.hash_value(HashType::FZorder), shift) as usize) % size
     }
 
     pub fn part_z_order_hash(&self, size: usize, shift: usize) -> usize {
-        let z = self.get_z_order_curve();
-        // Remove first dimension bits
-        let mut result = 0u64;
-        let mut pos = 0;
-        for bit_idx in 0..64 {
-            if bit_idx % N != 0 {
-                result |= ((z >> bit_idx) & 1) << pos;
-                pos += 1;
-            }
-        }
-        ((result as usize) >> shift) % size
+        (shifted(self.hash_value(HashType::PZorder), shift) as usize) % size
     }
 
     /// Get thread ID based on hash type and thread map
@@ -152,9 +214,59 @@ mod tests {
         assert_eq!(neigh.get(2), 5);
     }
 
+    #[test]
+    fn test_incr_decr() {
+        let mut coord: Coord<3> = Coord::from_array([1, 2, 3]);
+        coord.incr(1);
+        assert_eq!(coord, Coord::from_array([1, 2, 3]).neigh(1));
+        coord.decr(1);
+        assert_eq!(coord, Coord::from_array([1, 2, 3]));
+    }
+
     #[test]
     fn test_coord_sum() {
         let coord: Coord<3> = Coord::from_array([1, 2, 3]);
         assert_eq!(coord.get_sum(), 6);
     }
+
+    #[test]
+    fn test_partial_hash_spread_two_sequences() {
+        // A long sequence against a two residue one: keying on dimension 1
+        // alone would use only 3 of the 8 threads
+        let mut buckets = [0usize; 8];
+        for x in 0..100u16 {
+            for y in 0..3u16 {
+                let coord: Coord<2> = Coord::from_array([x, y]);
+                buckets[coord.get_id(8, HashType::PSum, 0, &[])] += 1;
+            }
+        }
+        assert!(buckets.iter().all(|&n| n > 0));
+        assert!(buckets.iter().max().unwrap() - buckets.iter().min().unwrap() <= 3);
+
+        let coord: Coord<2> = Coord::from_array([5, 9]);
+        assert_eq!(coord.part_z_order_hash(8, 0), coord.z_order_hash(8, 0));
+
+        // Three or more sequences still ignore dimension 0
+        let a: Coord<3> = Coord::from_array([1, 2, 3]);
+        let b: Coord<3> = Coord::from_array([7, 2, 3]);
+        assert_eq!(a.part_sum_hash(8, 0), b.part_sum_hash(8, 0));
+    }
+
+    #[test]
+    fn test_coord_componentwise_le() {
+        let a: Coord<3> = Coord::from_array([1, 2, 3]);
+        let b: Coord<3> = Coord::from_array([1, 3, 3]);
+        let c: Coord<3> = Coord::from_array([2, 0, 0]);
+
+        assert!(a.le(&a));
+        assert!(a.le(&b));
+        assert!(!b.le(&a));
+        // Lexicographically smaller but not componentwise
+        assert!(a < c);
+        assert!(!a.le(&c));
+        assert!(!c.le(&a));
+
+        assert!(a.is_goal_reachable(&b));
+        assert!(!a.neigh(0).is_goal_reachable(&b));
+    }
 }
@@ -5,7 +5,11 @@
  * \brief Hash type configuration for coordinate hashing
  */
 
+use std::str::FromStr;
+use crate::coord::Coord;
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HashType {
     FZorder,  // Full Z-order curve
     PZorder,  // Partial Z-order curve (skip first dimension)
@@ -13,6 +17,10 @@ pub enum HashType {
     PSum,     // Partial sum (skip first dimension)
 }
 
+impl HashType {
+    pub const ALL: [HashType; 4] = [HashType::FZorder, HashType::PZorder, HashType::FSum, HashType::PSum];
+}
+
 pub struct CoordHash;
 
 impl CoordHash {
@@ -26,14 +34,122 @@ impl CoordHash {
     }
 }
 
-impl HashType {
-    pub fn from_str(s: &str) -> Option<Self> {
+impl FromStr for HashType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
-            "fzorder" | "full-zorder" => Some(HashType::FZorder),
-            "pzorder" | "partial-zorder" => Some(HashType::PZorder),
-            "fsum" | "full-sum" => Some(HashType::FSum),
-            "psum" | "partial-sum" => Some(HashType::PSum),
-            _ => None,
+            "fzorder" | "full-zorder" => Ok(HashType::FZorder),
+            "pzorder" | "partial-zorder" => Ok(HashType::PZorder),
+            "fsum" | "full-sum" => Ok(HashType::FSum),
+            "psum" | "partial-sum" => Ok(HashType::PSum),
+            _ => Err(format!("Unknown hash type: {}", s)),
+        }
+    }
+}
+
+/// Policy deciding which PA-Star thread expands a generated node. `route`
+/// must return a thread id below `num_threads`.
+pub trait NodeRouter<const N: usize>: Send + Sync {
+    fn route(&self, coord: &Coord<N>, num_threads: usize) -> usize;
+}
+
+/// Default routing: hash the coordinate with `hash_type` and `shift`. A
+/// non-empty `thread_map` (hybrid CPUs) is indexed by the hash instead,
+/// so threads listed several times get a bigger share.
+pub struct HashRouter {
+    pub hash_type: HashType,
+    pub shift: usize,
+    pub thread_map: Vec<usize>,
+}
+
+impl<const N: usize> NodeRouter<N> for HashRouter {
+    fn route(&self, coord: &Coord<N>, num_threads: usize) -> usize {
+        if self.thread_map.is_empty() {
+            coord.get_id(num_threads, self.hash_type, self.shift, &[])
+        } else {
+            coord.get_id(self.thread_map.len(), self.hash_type, self.shift, &self.thread_map)
         }
     }
 }
+
+/// Largest `threads << hash_shift`, in lattice sum units, at which the sum
+/// hashes still spread a wavefront evenly (see `benches/hash_type.rs`)
+pub const SUM_HASH_SPREAD: usize = 32;
+
+/// Default hash type for `threads` threads at the shift in use. Sum hashes
+/// put whole wavefronts (equal coordinate sums) on one thread, so they only
+/// balance while `threads << hash_shift` is within `SUM_HASH_SPREAD`; there
+/// they beat Z-order on balance at any sequence count. Past it, which
+/// includes the default `HASH_SHIFT`, full Z-order spreads the load and
+/// keeps more successors on their thread.
+pub fn choose_default_hash(threads: usize, hash_shift: usize) -> HashType {
+    let spread = (threads as u128) << hash_shift.min(64);
+    if spread <= SUM_HASH_SPREAD as u128 {
+        HashType::FSum
+    } else {
+        HashType::FZorder
+    }
+}
+
+/// `value >> shift`, 0 once the shift moves every bit out
+pub fn shifted(value: u64, shift: usize) -> u64 {
+    u32::try_from(shift).ok().and_then(|shift| value.checked_shr(shift)).unwrap_or(0)
+}
+
+/// Warning when `shift` leaves fewer distinct hash values than `threads`
+/// and fewer than shift 0 would, given the largest unshifted hash value of
+/// the lattice (that of the final coordinate). Suggests the largest shift
+/// that keeps as many threads busy as shift 0.
+pub fn hash_shift_warning(max_value: u64, threads: usize, shift: usize) -> Option<String> {
+    let buckets = |shift| (shifted(max_value, shift) as u128 + 1).min(threads as u128);
+    let wanted = buckets(0);
+    let used = buckets(shift);
+    if used >= wanted {
+        return None;
+    }
+    let suggested = (0..shift).rev().find(|&s| buckets(s) == wanted).unwrap_or(0);
+    Some(format!(
+        "hash shift {} leaves {} of {} threads with work for this input, use --hash-shift {} or less",
+        shift, used, threads, suggested
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_default_hash() {
+        assert_eq!(choose_default_hash(4, 0), HashType::FSum);
+        assert_eq!(choose_default_hash(32, 0), HashType::FSum);
+        assert_eq!(choose_default_hash(4, 3), HashType::FSum);
+        assert_eq!(choose_default_hash(4, 4), HashType::FZorder);
+        assert_eq!(choose_default_hash(64, 0), HashType::FZorder);
+        assert_eq!(choose_default_hash(4, 62), HashType::FZorder);
+        assert_eq!(choose_default_hash(4, 200), HashType::FZorder);
+        // Even one thread spreads past the sum hashes' range at the default shift
+        assert_eq!(choose_default_hash(1, crate::HASH_SHIFT), HashType::FZorder);
+    }
+
+    #[test]
+    fn test_hash_shift_warning() {
+        // Three short sequences: the final coordinate sums to 30
+        let final_coord: Coord<3> = Coord::from_array([10, 12, 8]);
+        let max_sum = final_coord.hash_value(HashType::FSum);
+        assert_eq!(max_sum, 30);
+        assert_eq!(hash_shift_warning(max_sum, 4, 0), None);
+        assert_eq!(hash_shift_warning(max_sum, 4, 3), None);
+
+        let warning = hash_shift_warning(max_sum, 4, 12).unwrap();
+        assert!(warning.contains("leaves 1 of 4 threads"), "{}", warning);
+        assert!(warning.ends_with("--hash-shift 3 or less"), "{}", warning);
+        assert!(hash_shift_warning(max_sum, 4, 200).is_some());
+        assert_eq!(final_coord.sum_hash(4, 200), 0);
+
+        // More threads than lattice values can't be helped by any shift
+        assert_eq!(hash_shift_warning(max_sum, 64, 0), None);
+        let max_z = final_coord.hash_value(HashType::FZorder);
+        assert!(hash_shift_warning(max_z, 8, 60).is_some());
+    }
+}
@@ -5,33 +5,395 @@
  * \brief Class that calculates match, mismatch and gap cost
  */
 
+use std::str::FromStr;
+use std::sync::Arc;
 use once_cell::sync::Lazy;
-use std::sync::Mutex;
+use parking_lot::RwLock;
+use crate::error::MsaError;
 
 pub struct Cost;
 
-static COST_MATRIX: Lazy<Mutex<[[i32; 256]; 256]>> = Lazy::new(|| Mutex::new([[0; 256]; 256]));
-static GAP_COST: Lazy<Mutex<i32>> = Lazy::new(|| Mutex::new(30));
-static GAP_GAP: Lazy<Mutex<i32>> = Lazy::new(|| Mutex::new(30));
+/// Built-in cost matrices
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Matrix {
+    Nucleotide,
+    Pam250,
+    Blosum62,
+    /// 0 for identical residues and one cost for any mismatch, see
+    /// `Cost::set_cost_identity`
+    Identity,
+}
+
+/// Mismatch and gap costs of `Matrix::Identity` when none are given
+pub const DEFAULT_IDENTITY_MISMATCH: i32 = 1;
+pub const DEFAULT_IDENTITY_GAP: i32 = 2;
+
+impl FromStr for Matrix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nuc" | "nucleotide" => Ok(Matrix::Nucleotide),
+            "pam250" => Ok(Matrix::Pam250),
+            "blosum62" => Ok(Matrix::Blosum62),
+            "identity" => Ok(Matrix::Identity),
+            _ => Err(format!("Unknown matrix: {}", s)),
+        }
+    }
+}
+
+/// Structure cost adjustments of `--ss` when none are given
+pub const DEFAULT_SS_BONUS: i32 = 5;
+pub const DEFAULT_SS_PENALTY: i32 = 5;
+
+/// Cost change of a residue pair by secondary structure labels (see
+/// `Sequences::set_ss_labels`). Both zero, the default, leaves costs as
+/// the matrix has them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct StructureScoring {
+    /// Taken off the cost of residues with the same label, down to 0
+    pub bonus: i32,
+    /// Added to the cost of residues with different labels
+    pub penalty: i32,
+}
+
+impl StructureScoring {
+    /// `cost` of a residue pair labeled `l1` and `l2`, 0 for no label
+    pub fn apply(&self, cost: i32, l1: u8, l2: u8) -> i32 {
+        match (l1, l2) {
+            (0, _) | (_, 0) => cost,
+            _ if l1 == l2 => (cost - self.bonus).max(0),
+            _ => cost + self.penalty,
+        }
+    }
+}
+
+/// Bases each IUPAC nucleotide code stands for, U read as T
+const IUPAC_CODES: [(u8, &[u8]); 16] = [
+    (b'A', b"A"), (b'C', b"C"), (b'G', b"G"), (b'T', b"T"), (b'U', b"T"),
+    (b'R', b"AG"), (b'Y', b"CT"), (b'S', b"CG"), (b'W', b"AT"), (b'K', b"GT"), (b'M', b"AC"),
+    (b'B', b"CGT"), (b'D', b"AGT"), (b'H', b"ACT"), (b'V', b"ACG"), (b'N', b"ACGT"),
+];
+
+/// Mean mismatch cost over every pair of bases two IUPAC codes stand for,
+/// rounded to the nearest integer, halves up: a two-base code is as far
+/// from either of its bases as a mismatch.
+fn expected_nuc_cost(r: &[u8], l: &[u8]) -> i32 {
+    let pairs = (r.len() * l.len()) as i32;
+    let mismatches = r.iter()
+        .flat_map(|x| l.iter().map(move |y| (x != y) as i32))
+        .sum::<i32>();
+    (2 * mismatches + pairs) / (2 * pairs)
+}
+
+/// BLOSUM62 scores in `BLOSUM62_ORDER`, turned into costs by
+/// `set_cost_blosum62`
+const BLOSUM62_ORDER: &[u8; 20] = b"ARNDCQEGHILKMFPSTWYV";
+const BLOSUM62_SCORES: [[i32; 20]; 20] = [
+    [ 4, -1, -2, -2,  0, -1, -1,  0, -2, -1, -1, -1, -1, -2, -1,  1,  0, -3, -2,  0],
+    [-1,  5,  0, -2, -3,  1,  0, -2,  0, -3, -2,  2, -1, -3, -2, -1, -1, -3, -2, -3],
+    [-2,  0,  6,  1, -3,  0,  0,  0,  1, -3, -3,  0, -2, -3, -2,  1,  0, -4, -2, -3],
+    [-2, -2,  1,  6, -3,  0,  2, -1, -1, -3, -4, -1, -3, -3, -1,  0, -1, -4, -3, -3],
+    [ 0, -3, -3, -3,  9, -3, -4, -3, -3, -1, -1, -3, -1, -2, -3, -1, -1, -2, -2, -1],
+    [-1,  1,  0,  0, -3,  5,  2, -2,  0, -3, -2,  1,  0, -3, -1,  0, -1, -2, -1, -2],
+    [-1,  0,  0,  2, -4,  2,  5, -2,  0, -3, -3,  1, -2, -3, -1,  0, -1, -3, -2, -2],
+    [ 0, -2,  0, -1, -3, -2, -2,  6, -2, -4, -4, -2, -3, -3, -2,  0, -2, -2, -3, -3],
+    [-2,  0,  1, -1, -3,  0,  0, -2,  8, -3, -3, -1, -2, -1, -2, -1, -2, -2,  2, -3],
+    [-1, -3, -3, -3, -1, -3, -3, -4, -3,  4,  2, -3,  1,  0, -3, -2, -1, -3, -1,  3],
+    [-1, -2, -3, -4, -1, -2, -3, -4, -3,  2,  4, -2,  2,  0, -3, -2, -1, -2, -1,  1],
+    [-1,  2,  0, -1, -3,  1,  1, -2, -1, -3, -2,  5, -1, -3, -1,  0, -1, -3, -2, -2],
+    [-1, -1, -2, -3, -1,  0, -2, -3, -2,  1,  2, -1,  5,  0, -2, -1, -1, -1, -1,  1],
+    [-2, -3, -3, -3, -2, -3, -3, -3, -1,  0,  0, -3,  0,  6, -4, -2, -2,  1,  3, -1],
+    [-1, -2, -2, -1, -3, -1, -1, -2, -2, -3, -3, -1, -2, -4,  7, -1, -1, -4, -3, -2],
+    [ 1, -1,  1,  0, -1,  0,  0,  0, -1, -2, -2,  0, -1, -2, -1,  4,  1, -3, -2, -2],
+    [ 0, -1,  0, -1, -1, -1, -1, -2, -2, -1, -1, -1, -1, -2, -1,  1,  5, -2, -2,  0],
+    [-3, -3, -4, -4, -2, -2, -3, -2, -2, -3, -2, -3, -1,  1, -4, -3, -2, 11,  2, -3],
+    [-2, -2, -2, -3, -2, -1, -2, -3,  2, -1, -1, -2, -1,  3, -3, -2, -2,  2,  7, -1],
+    [ 0, -3, -3, -3, -1, -2, -2, -3, -3,  3,  1, -2,  1, -1, -2, -2,  0, -3, -1,  4],
+];
+
+/// Gap symbol, a row and column of the cost matrix like any residue
+pub const GAP: u8 = b'-';
+
+/// Substitution matrix, gap costs and structure scoring as one value.
+/// `Cost` holds the active one; a scheme can also be passed straight to
+/// `Node`, `PairAlign` and the heuristic, so alignments with different
+/// schemes don't share state. Gaps are linear: the gap costs are the `GAP`
+/// row and column, `cost(GAP, x)` a residue against a gap and
+/// `cost(GAP, GAP)` two gaps. Clones share the matrix until one changes.
+#[derive(Clone, PartialEq)]
+pub struct ScoringScheme {
+    matrix: Arc<[[i32; 256]; 256]>,
+    structure: StructureScoring,
+    kind: SchemeKind,
+}
+
+/// Where the costs of a scheme came from
+#[derive(Clone, PartialEq, Debug)]
+enum SchemeKind {
+    /// Placeholder active before a matrix is selected
+    Unselected,
+    Builtin(Matrix),
+    /// Read by `ScoringScheme::from_matrix_file`, with the residues it
+    /// defines
+    File(Arc<[bool; 256]>),
+}
+
+impl ScoringScheme {
+    fn new(kind: Matrix, gap_cost: i32, gap_gap: i32) -> Self {
+        let mut data = ScoringScheme {
+            matrix: Arc::new([[0; 256]; 256]),
+            structure: StructureScoring::default(),
+            kind: SchemeKind::Builtin(kind),
+        };
+        data.set_gaps(gap_cost, gap_gap);
+        data
+    }
+
+    /// Fill the `GAP` row and column
+    fn set_gaps(&mut self, gap_cost: i32, gap_gap: i32) {
+        let matrix = Arc::make_mut(&mut self.matrix);
+        for row in matrix.iter_mut() {
+            row[GAP as usize] = gap_cost;
+        }
+        matrix[GAP as usize] = [gap_cost; 256];
+        matrix[GAP as usize][GAP as usize] = gap_gap;
+    }
+
+    /// One of the built-in matrices with its default gap costs
+    pub fn for_matrix(matrix: Matrix) -> Self {
+        match matrix {
+            Matrix::Nucleotide => ScoringScheme::nucleotide(),
+            Matrix::Pam250 => ScoringScheme::pam250(),
+            Matrix::Blosum62 => ScoringScheme::blosum62(),
+            Matrix::Identity => ScoringScheme::identity(DEFAULT_IDENTITY_MISMATCH, DEFAULT_IDENTITY_GAP),
+        }
+    }
+
+    /// The same scheme with residue/gap cost `gap_cost`
+    pub fn with_gap_cost(mut self, gap_cost: i32) -> Self {
+        let gap_gap = self.gap_gap();
+        self.set_gaps(gap_cost, gap_gap);
+        self
+    }
+
+    /// The same scheme adjusting residue pairs by their structure labels
+    pub fn with_structure(mut self, structure: StructureScoring) -> Self {
+        self.structure = structure;
+        self
+    }
+
+    /// All-zero placeholder used until a matrix is selected
+    fn unselected() -> Self {
+        ScoringScheme {
+            kind: SchemeKind::Unselected,
+            ..ScoringScheme::new(Matrix::Pam250, 30, 30)
+        }
+    }
+
+    /// Where the costs came from: a `Matrix` name `--matrix` accepts,
+    /// `file` or `none`
+    pub fn name(&self) -> &'static str {
+        match &self.kind {
+            SchemeKind::Unselected => "none",
+            SchemeKind::Builtin(Matrix::Nucleotide) => "nucleotide",
+            SchemeKind::Builtin(Matrix::Pam250) => "pam250",
+            SchemeKind::Builtin(Matrix::Blosum62) => "blosum62",
+            SchemeKind::Builtin(Matrix::Identity) => "identity",
+            SchemeKind::File(_) => "file",
+        }
+    }
+
+    pub fn cost(&self, r: u8, l: u8) -> i32 {
+        self.matrix[r as usize][l as usize]
+    }
+
+    /// Cost of a residue against a gap
+    pub fn gap_cost(&self) -> i32 {
+        self.cost(GAP, b'A')
+    }
+
+    pub fn gap_gap(&self) -> i32 {
+        self.cost(GAP, GAP)
+    }
+
+    pub fn structure(&self) -> StructureScoring {
+        self.structure
+    }
+
+    /// Whether the scheme can score residue `c`. The nucleotide matrix
+    /// takes bases and IUPAC codes and a matrix file the residues it lists;
+    /// the protein matrices take anything, as protein inputs often hold B,
+    /// Z or X they leave at 0
+    pub fn is_valid_residue(&self, c: u8) -> bool {
+        match &self.kind {
+            SchemeKind::Builtin(Matrix::Nucleotide) => IUPAC_CODES.iter().any(|&(code, _)| code == c),
+            SchemeKind::File(defined) => defined[c as usize],
+            _ => true,
+        }
+    }
+
+    /// Error unless this is a selected matrix that passes `validate`
+    pub fn check_selected(&self) -> Result<(), MsaError> {
+        if self.kind == SchemeKind::Unselected {
+            return Err(MsaError::NoCostMatrix);
+        }
+        self.validate().map_err(MsaError::InvalidCosts)
+    }
+
+    /// Check the costs keep the search correct: none negative, and every
+    /// residue cheapest against itself, so identical sequences align
+    /// without gaps or substitutions. Only residues the matrix scores (a
+    /// nonzero cost against another letter) are compared; `X`, the unknown
+    /// residue, is exempt, as standard matrices score it below some
+    /// substitutions.
+    pub fn validate(&self) -> Result<(), String> {
+        let (gap_cost, gap_gap) = (self.gap_cost(), self.gap_gap());
+        if gap_cost < 0 || gap_gap < 0 {
+            return Err(format!("gap costs must not be negative, got {} and {} for two gaps", gap_cost, gap_gap));
+        }
+        let scored: Vec<u8> = (0..=255u8)
+            .filter(|&r| r != GAP && (b'A'..=b'Z').any(|l| l != r && self.cost(r, l) != 0))
+            .collect();
+        for r in 0..=255u8 {
+            if let Some(l) = (0..=255u8).find(|&l| self.cost(r, l) < 0) {
+                return Err(format!("cost of {:?} against {:?} is negative ({})", r as char, l as char, self.cost(r, l)));
+            }
+        }
+        for &r in scored.iter().filter(|&&r| r != b'X') {
+            if let Some(&l) = scored.iter().find(|&&l| self.cost(r, l) < self.cost(r, r)) {
+                return Err(format!(
+                    "{:?} costs {} against itself but {} against {:?}",
+                    r as char, self.cost(r, r), self.cost(r, l), l as char
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Table of the costs between `residues`, followed by the gap costs
+    pub fn format(&self, residues: &[u8]) -> String {
+        let mut out = String::from("   ");
+        for &l in residues {
+            out.push_str(&format!("{:>4}", l as char));
+        }
+        out.push('\n');
+        for &r in residues {
+            out.push_str(&format!("{:>3}", r as char));
+            for &l in residues {
+                out.push_str(&format!("{:>4}", self.cost(r, l)));
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("Gap cost: {}\nGap-gap cost: {}\n", self.gap_cost(), self.gap_gap()));
+        out
+    }
+}
+
+/// The matrix itself is left out, it's 64K entries
+impl std::fmt::Debug for ScoringScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScoringScheme")
+            .field("name", &self.name())
+            .field("gap_cost", &self.gap_cost())
+            .field("gap_gap", &self.gap_gap())
+            .field("structure", &self.structure)
+            .finish()
+    }
+}
+
+static COST_DATA: Lazy<RwLock<ScoringScheme>> = Lazy::new(|| RwLock::new(ScoringScheme::unselected()));
 
 impl Cost {
+    /// `cost(GAP, 'A')`, the cost of any residue against a gap
     pub fn get_gap_cost() -> i32 {
-        *GAP_COST.lock().unwrap()
+        COST_DATA.read().gap_cost()
     }
 
     pub fn get_gap_gap() -> i32 {
-        *GAP_GAP.lock().unwrap()
+        COST_DATA.read().gap_gap()
     }
 
-    pub fn set_cost_pam250() {
-        let mut matrix = COST_MATRIX.lock().unwrap();
-        let mut gap = GAP_COST.lock().unwrap();
-        let mut gap_gap = GAP_GAP.lock().unwrap();
+    /// Consistent copy of the active matrix and gap costs
+    pub fn matrix_snapshot() -> ScoringScheme {
+        COST_DATA.read().clone()
+    }
 
-        // Initialize all to 0
-        for row in matrix.iter_mut() {
-            row.fill(0);
+    /// Make a snapshot from `matrix_snapshot` the active costs again
+    pub fn restore(snapshot: &ScoringScheme) {
+        *COST_DATA.write() = snapshot.clone();
+    }
+
+    /// Whether a matrix was selected; without one every alignment costs 0
+    pub fn is_selected() -> bool {
+        COST_DATA.read().kind != SchemeKind::Unselected
+    }
+
+    /// Error unless a cost matrix was selected and passes
+    /// `ScoringScheme::validate`
+    pub fn check_selected() -> Result<(), MsaError> {
+        COST_DATA.read().check_selected()
+    }
+
+    /// Score `*` (stop codon) as the worst substitution of the active
+    /// matrix against any residue, and free against another `*`
+    pub fn score_stop_codons() {
+        let mut data = COST_DATA.write();
+        let residues = || (0..256).filter(|&c| c != GAP as usize);
+        let worst = residues()
+            .flat_map(|r| residues().map(move |l| (r, l)))
+            .map(|(r, l)| data.matrix[r][l])
+            .max()
+            .unwrap_or(0);
+        let stop = b'*' as usize;
+        let matrix = Arc::make_mut(&mut data.matrix);
+        for c in residues() {
+            matrix[stop][c] = worst;
+            matrix[c][stop] = worst;
         }
+        matrix[stop][stop] = 0;
+    }
+
+    /// Whether the nucleotide matrix is active
+    pub fn is_nucleotide() -> bool {
+        COST_DATA.read().kind == SchemeKind::Builtin(Matrix::Nucleotide)
+    }
+
+    /// Whether the active matrix can score residue `c` (see
+    /// `ScoringScheme::is_valid_residue`)
+    pub fn is_valid_residue(c: u8) -> bool {
+        COST_DATA.read().is_valid_residue(c)
+    }
+
+    /// Drop the active matrix, back to the unselected state
+    pub fn clear() {
+        *COST_DATA.write() = ScoringScheme::unselected();
+    }
+
+    /// Override the residue/gap cost of the active matrix
+    pub fn set_gap_cost(gap_cost: i32) {
+        let mut data = COST_DATA.write();
+        let gap_gap = data.gap_gap();
+        data.set_gaps(gap_cost, gap_gap);
+    }
+
+    /// Adjust residue pair costs by their structure labels. Selecting a
+    /// matrix resets this.
+    pub fn set_structure_scoring(structure: StructureScoring) {
+        COST_DATA.write().structure = structure;
+    }
+
+    pub fn structure_scoring() -> StructureScoring {
+        COST_DATA.read().structure
+    }
+
+}
+
+/// The built-in schemes, each also activated by a `Cost::set_cost_*`
+impl ScoringScheme {
+    pub fn pam250() -> Self {
+        let mut data = ScoringScheme::new(Matrix::Pam250, 30, 30);
+        let matrix = Arc::make_mut(&mut data.matrix);
 
         // Set PAM250 costs (complete matrix from C++ code)
         // C costs
@@ -284,19 +646,12 @@ impl Cost {
         // W costs
         matrix[b'W' as usize][b'W' as usize] = 0;
 
-        *gap = 30;
-        *gap_gap = 30;
+        data
     }
 
-    pub fn set_cost_nuc() {
-        let mut matrix = COST_MATRIX.lock().unwrap();
-        let mut gap = GAP_COST.lock().unwrap();
-        let mut gap_gap = GAP_GAP.lock().unwrap();
-
-        // Initialize all to 0
-        for row in matrix.iter_mut() {
-            row.fill(0);
-        }
+    pub fn nucleotide() -> Self {
+        let mut data = ScoringScheme::new(Matrix::Nucleotide, 2, 2);
+        let matrix = Arc::make_mut(&mut data.matrix);
 
         // Nucleotide costs
         matrix[b'A' as usize][b'A' as usize] = 0;
@@ -319,13 +674,163 @@ impl Cost {
 
         matrix[b'U' as usize][b'U' as usize] = 0;
 
-        *gap = 2;
-        *gap_gap = 2;
+        // Ambiguity codes cost the expected mismatch of their bases
+        for &(r, r_bases) in &IUPAC_CODES {
+            for &(l, l_bases) in &IUPAC_CODES {
+                if r_bases.len() > 1 || l_bases.len() > 1 {
+                    matrix[r as usize][l as usize] = expected_nuc_cost(r_bases, l_bases);
+                }
+            }
+        }
+
+        data
+    }
+
+    /// BLOSUM62 as costs: the highest score (W/W, 11) costs 0 and a gap
+    /// costs as much as a score of -8
+    pub fn blosum62() -> Self {
+        let mut data = ScoringScheme::new(Matrix::Blosum62, 19, 19);
+        let matrix = Arc::make_mut(&mut data.matrix);
+        for (i, &r) in BLOSUM62_ORDER.iter().enumerate() {
+            for (j, &l) in BLOSUM62_ORDER.iter().enumerate() {
+                matrix[r as usize][l as usize] = 11 - BLOSUM62_SCORES[i][j];
+            }
+        }
+
+        data
+    }
+
+    /// Costs independent of residue identity: 0 for a match, `mismatch`
+    /// for any two different residues and `gap` for a residue against a gap
+    pub fn identity(mismatch: i32, gap: i32) -> Self {
+        let mut data = ScoringScheme::new(Matrix::Identity, gap, gap);
+        for (r, row) in Arc::make_mut(&mut data.matrix).iter_mut().enumerate() {
+            for (l, cost) in row.iter_mut().enumerate() {
+                *cost = if r == l { 0 } else { mismatch };
+            }
+        }
+        data.set_gaps(gap, gap);
+
+        data
+    }
+}
+
+/// Largest scaled score `ScoringScheme::from_matrix_file` accepts, so
+/// path costs stay far from overflowing
+const MAX_SCALED_SCORE: f64 = 1e6;
+
+/// Costs read from a file
+impl ScoringScheme {
+    /// Substitution scores in NCBI layout: a header line of residues, then
+    /// one line per residue starting with it, `#` comments ignored. Scores
+    /// may be fractional: each is multiplied by `scale` and rounded to the
+    /// nearest integer, halves away from zero, then turned into costs like
+    /// BLOSUM62's, the highest score costing 0. A `-` row and column give
+    /// the gap scores; without them a gap scores as the lowest entry. Only
+    /// the residues of the header and row labels are accepted in sequences;
+    /// pairs the file leaves out cost as the lowest entry.
+    pub fn from_matrix_file(filename: &str, scale: f64) -> Result<Self, String> {
+        let text = std::fs::read_to_string(filename)
+            .map_err(|e| format!("Can't read matrix file {}: {}", filename, e))?;
+        Self::parse_matrix(&text, scale).map_err(|e| format!("Matrix file {}: {}", filename, e))
+    }
+
+    fn parse_matrix(text: &str, scale: f64) -> Result<Self, String> {
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(format!("scale must be positive, got {}", scale));
+        }
+        let residue = |token: &str| match token.as_bytes() {
+            &[c] => Ok(c.to_ascii_uppercase()),
+            _ => Err(format!("expected a single residue, got {:?}", token)),
+        };
+
+        let mut lines = text.lines().enumerate()
+            .map(|(num, line)| (num + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let (_, header) = lines.next().ok_or("no header line")?;
+        let columns = header.split_whitespace().map(residue).collect::<Result<Vec<u8>, _>>()?;
+
+        let mut scores = Vec::new();
+        for (num, line) in lines {
+            let mut tokens = line.split_whitespace();
+            let row = residue(tokens.next().unwrap_or_default()).map_err(|e| format!("line {}: {}", num, e))?;
+            let values: Vec<&str> = tokens.collect();
+            if values.len() != columns.len() {
+                return Err(format!("line {}: {} scores for {} columns", num, values.len(), columns.len()));
+            }
+            for (&column, value) in columns.iter().zip(values) {
+                let score: f64 = value.parse().map_err(|_| format!("line {}: invalid score {:?}", num, value))?;
+                let scaled = (score * scale).round();
+                if !scaled.is_finite() || scaled.abs() > MAX_SCALED_SCORE {
+                    return Err(format!("line {}: score {} is out of range once scaled", num, value));
+                }
+                scores.push((row, column, scaled as i32));
+            }
+        }
+        let best = scores.iter().map(|&(_, _, score)| score).max().ok_or("no scores")?;
+        let worst = scores.iter().map(|&(_, _, score)| score).min().unwrap_or(best);
+
+        let mut defined = [false; 256];
+        for &(r, l, _) in &scores {
+            defined[r as usize] = true;
+            defined[l as usize] = true;
+        }
+        defined[GAP as usize] = false;
+
+        let mut data = ScoringScheme::unselected();
+        data.kind = SchemeKind::File(Arc::new(defined));
+        data.matrix = Arc::new([[best - worst; 256]; 256]);
+        data.set_gaps(best - worst, best - worst);
+        let matrix = Arc::make_mut(&mut data.matrix);
+        for (r, l, score) in scores {
+            matrix[r as usize][l as usize] = best - score;
+        }
+
+        data.validate()?;
+        Ok(data)
+    }
+}
+
+impl Cost {
+    /// Make `scheme` the active one, returning it for callers that also
+    /// want to pass it along
+    fn install(scheme: ScoringScheme) -> ScoringScheme {
+        *COST_DATA.write() = scheme.clone();
+        scheme
+    }
+
+    /// Activate `ScoringScheme::pam250`
+    pub fn set_cost_pam250() -> ScoringScheme {
+        Self::install(ScoringScheme::pam250())
+    }
+
+    /// Activate `ScoringScheme::nucleotide`
+    pub fn set_cost_nuc() -> ScoringScheme {
+        Self::install(ScoringScheme::nucleotide())
+    }
+
+    /// Activate `ScoringScheme::blosum62`
+    pub fn set_cost_blosum62() -> ScoringScheme {
+        Self::install(ScoringScheme::blosum62())
+    }
+
+    /// Activate `ScoringScheme::identity`
+    pub fn set_cost_identity(mismatch: i32, gap: i32) -> ScoringScheme {
+        Self::install(ScoringScheme::identity(mismatch, gap))
+    }
+
+    /// Activate one of the built-in matrices with its default gap costs
+    pub fn set_matrix(matrix: Matrix) -> ScoringScheme {
+        Self::install(ScoringScheme::for_matrix(matrix))
+    }
+
+    /// Activate `ScoringScheme::from_matrix_file`
+    pub fn set_matrix_file(filename: &str, scale: f64) -> Result<ScoringScheme, String> {
+        Ok(Self::install(ScoringScheme::from_matrix_file(filename, scale)?))
     }
 
     pub fn cost(r: u8, l: u8) -> i32 {
-        let matrix = COST_MATRIX.lock().unwrap();
-        matrix[r as usize][l as usize]
+        COST_DATA.read().cost(r, l)
     }
 }
 
@@ -335,15 +840,245 @@ mod tests {
 
     #[test]
     fn test_cost_initialization() {
+        let _guard = crate::test_lock();
         Cost::set_cost_nuc();
         assert_eq!(Cost::cost(b'A', b'A'), 0);
-        assert_eq!(Cost::get_gap_cost(), 5);
+        assert_eq!(Cost::cost(b'A', b'C'), 1);
+        assert_eq!(Cost::get_gap_cost(), 2);
+    }
+
+    #[test]
+    fn test_gap_row() {
+        let _guard = crate::test_lock();
+        for (matrix, gap) in [(Matrix::Nucleotide, 2), (Matrix::Pam250, 30), (Matrix::Blosum62, 19), (Matrix::Identity, 2)] {
+            Cost::set_matrix(matrix);
+            for &c in b"ACGTWY*" {
+                assert_eq!(Cost::cost(GAP, c), gap);
+                assert_eq!(Cost::cost(c, GAP), gap);
+            }
+            assert_eq!(Cost::get_gap_cost(), gap);
+            assert_eq!(Cost::get_gap_gap(), gap);
+            assert_eq!(Cost::cost(GAP, GAP), Cost::get_gap_gap());
+        }
+
+        // Overriding the gap cost keeps the gap-gap cost
+        Cost::set_cost_nuc();
+        Cost::set_gap_cost(7);
+        assert_eq!((Cost::cost(b'T', GAP), Cost::cost(GAP, GAP)), (7, 2));
+
+        // Stop codons take the worst residue cost, not the gap cost
+        Cost::set_cost_pam250();
+        Cost::score_stop_codons();
+        assert!(Cost::cost(b'*', b'A') < Cost::get_gap_cost());
+        assert_eq!(Cost::cost(b'*', GAP), Cost::get_gap_cost());
+    }
+
+    #[test]
+    fn test_nuc_ambiguity_codes() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        // R is A or G: (0 + 1) / 2 = 0.5 rounds up
+        assert_eq!(Cost::cost(b'R', b'A'), 1);
+        assert_eq!(Cost::cost(b'A', b'R'), 1);
+        assert_eq!(Cost::cost(b'R', b'C'), 1);
+        // Y is C or T, never A or G
+        assert_eq!(Cost::cost(b'R', b'Y'), 1);
+        // N against C: 3 of 4 mismatch
+        assert_eq!(Cost::cost(b'N', b'C'), 1);
+        // V (A, C or G) against A: 2 of 3 mismatch
+        assert_eq!(Cost::cost(b'V', b'A'), 1);
+        assert_eq!(expected_nuc_cost(b"ACGT", b"ACG"), 1);
+        // Every code pair costs its mean mismatch, rounded half up
+        for &(r, r_bases) in &IUPAC_CODES {
+            for &(l, l_bases) in &IUPAC_CODES {
+                let pairs = r_bases.iter().flat_map(|x| l_bases.iter().map(move |y| (x, y)));
+                let mean = pairs.clone().filter(|(x, y)| x != y).count() as f64 / pairs.count() as f64;
+                assert_eq!(Cost::cost(r, l), (mean + 0.5).floor() as i32, "{} against {}", r as char, l as char);
+            }
+        }
+        assert_eq!(Cost::cost(b'A', b'C'), 1);
+        assert_eq!(Cost::cost(b'T', b'U'), 0);
+
+        assert!(b"ACGTURYSWKMBDHVN".iter().all(|&c| Cost::is_valid_residue(c)));
+        assert!(!Cost::is_valid_residue(b'X'));
+        Cost::set_cost_pam250();
+        assert!(Cost::is_valid_residue(b'X'));
+    }
+
+    #[test]
+    fn test_format_matrix() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        let dump = Cost::matrix_snapshot().format(b"CW");
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "      C   W");
+        assert_eq!(lines[1], "  C   5  25");
+        assert_eq!(lines[2], "  W  25   0");
+        assert_eq!(lines[3], "Gap cost: 30");
+        assert_eq!(lines[4], "Gap-gap cost: 30");
+    }
+
+    #[test]
+    fn test_matrix_file_scale() {
+        let _guard = crate::test_lock();
+        let path = std::env::temp_dir().join(format!("cost_matrix_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "# half-bit scores\n    A     C     -\nA  1.5  -0.24 -2.06\nc -0.24  2.0  -2.06\n- -2.06 -2.06  0\n").unwrap();
+
+        // x10: 15, -2 (-2.4), 20 and -21 (-20.6) for a gap, 0 for two gaps
+        let scheme = Cost::set_matrix_file(path, 10.0).unwrap();
+        assert!(Cost::is_selected());
+        assert_eq!((Cost::cost(b'A', b'A'), Cost::cost(b'A', b'C'), Cost::cost(b'C', b'C')), (5, 22, 0));
+        assert_eq!((scheme.gap_cost(), scheme.gap_gap()), (41, 20));
+        // Unscaled, 1.5 rounds away from zero to 2 and the rest to 0, 2 and -2
+        let scheme = ScoringScheme::from_matrix_file(path, 1.0).unwrap();
+        assert_eq!((scheme.cost(b'A', b'A'), scheme.cost(b'A', b'C'), scheme.gap_cost()), (0, 2, 4));
+
+        // Without a gap column a gap scores as the lowest entry
+        std::fs::write(path, "   A  C\nA  4 -1\nC -1  9\n").unwrap();
+        assert_eq!(ScoringScheme::from_matrix_file(path, 1.0).unwrap().gap_cost(), 10);
+
+        std::fs::write(path, "   A  C\nA  4\n").unwrap();
+        assert!(ScoringScheme::from_matrix_file(path, 1.0).err().unwrap().contains("line 2: 1 scores for 2 columns"));
+        assert!(ScoringScheme::from_matrix_file(path, 0.0).err().unwrap().contains("scale must be positive"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_matrix_file_residues() {
+        let _guard = crate::test_lock();
+        let path = std::env::temp_dir().join(format!("cost_residues_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        // No row for T, so T only gets its column
+        std::fs::write(path, "   A  C  G  T\nA  5 -4 -4 -4\nC -4  5 -4 -4\nG -4 -4  5 -4\n").unwrap();
+        let scheme = Cost::set_matrix_file(path, 1.0).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(b"ACGT".iter().all(|&c| Cost::is_valid_residue(c)));
+        assert!(!Cost::is_valid_residue(b'N'));
+        assert!(!Cost::is_valid_residue(GAP));
+        // Pairs the file leaves out cost as much as its lowest score
+        assert_eq!((scheme.cost(b'N', b'N'), scheme.cost(b'A', b'N'), scheme.cost(b'T', b'T')), (9, 9, 9));
+        assert_eq!(scheme.cost(b'A', b'T'), 9);
+
+        crate::sequences::Sequences::clear();
+        crate::sequences::Sequences::set_seq("ACGTACGT".to_string()).unwrap();
+        crate::sequences::Sequences::set_seq("NNNNNNNN".to_string()).unwrap();
+        assert_eq!(crate::heuristic_hpair::HeuristicHPair::init(), Err(MsaError::InvalidResidue(1, 'N')));
+    }
+
+    #[test]
+    fn test_validate_costs() {
+        let _guard = crate::test_lock();
+        for matrix in [Matrix::Nucleotide, Matrix::Pam250, Matrix::Blosum62, Matrix::Identity] {
+            assert_eq!(Cost::set_matrix(matrix).validate(), Ok(()), "{:?}", matrix);
+            Cost::score_stop_codons();
+            assert_eq!(Cost::check_selected(), Ok(()));
+        }
+
+        let negative_gap = ScoringScheme::nucleotide().with_gap_cost(-3);
+        assert_eq!(negative_gap.validate(), Err("gap costs must not be negative, got -3 and 2 for two gaps".to_string()));
+        Cost::restore(&negative_gap);
+        assert!(matches!(Cost::check_selected(), Err(MsaError::InvalidCosts(_))));
+        let mut negative_mismatch = ScoringScheme::pam250();
+        Arc::make_mut(&mut negative_mismatch.matrix)[b'C' as usize][b'W' as usize] = -2;
+        assert_eq!(negative_mismatch.validate(), Err("cost of 'C' against 'W' is negative (-2)".to_string()));
+
+        // A scores higher against C than against itself
+        let path = std::env::temp_dir().join(format!("cost_validate_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "   A  C  X\nA  2  3 -1\nC  3  5 -1\nX -1 -1 -2\n").unwrap();
+        assert!(ScoringScheme::from_matrix_file(path, 1.0).err().unwrap().ends_with("'A' costs 3 against itself but 2 against 'C'"));
+        // The unknown residue may score lower against itself
+        std::fs::write(path, "   A  C  X\nA  4  3 -1\nC  3  5 -1\nX -1 -1 -2\n").unwrap();
+        assert!(ScoringScheme::from_matrix_file(path, 1.0).is_ok());
+        std::fs::remove_file(path).unwrap();
     }
 
     #[test]
     fn test_pam250() {
+        let _guard = crate::test_lock();
         Cost::set_cost_pam250();
-        assert_eq!(Cost::cost(b'A', b'A'), 0);
-        assert_eq!(Cost::get_gap_cost(), 8);
+        assert_eq!(Cost::cost(b'A', b'A'), 15);
+        assert_eq!(Cost::cost(b'W', b'W'), 0);
+        assert_eq!(Cost::get_gap_cost(), 30);
+    }
+
+    #[test]
+    fn test_blosum62() {
+        let _guard = crate::test_lock();
+        Cost::set_matrix("BLOSUM62".parse().unwrap());
+        assert_eq!(Cost::cost(b'W', b'W'), 0);
+        assert_eq!(Cost::cost(b'A', b'A'), 7);
+        assert_eq!(Cost::cost(b'W', b'F'), Cost::cost(b'F', b'W'));
+        assert_eq!(Cost::get_gap_cost(), 19);
+        assert!("blosum".parse::<Matrix>().is_err());
+    }
+
+    #[test]
+    fn test_identity_costs() {
+        use crate::astar;
+        use crate::heuristic_hpair::HeuristicHPair;
+        use crate::msa_options::AStarOpt;
+        use crate::sequences::Sequences;
+
+        let _guard = crate::test_lock();
+        let align = |mismatch, gap| {
+            Cost::set_cost_identity(mismatch, gap);
+            Sequences::clear();
+            Sequences::set_seq("AC".to_string()).unwrap();
+            Sequences::set_seq("AG".to_string()).unwrap();
+            HeuristicHPair::init().unwrap();
+            astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap()
+        };
+
+        // A mismatch is cheaper than the two gaps that avoid it
+        let alignment = align(1, 2);
+        assert_eq!(alignment.seqs(), ["AC", "AG"]);
+        assert_eq!(alignment.search_scores().unwrap().g, 1);
+
+        let alignment = align(5, 2);
+        assert_eq!(alignment.seqs()[0].len(), 3);
+        assert_eq!(alignment.search_scores().unwrap().g, 4);
+
+        assert_eq!("identity".parse::<Matrix>().unwrap(), Matrix::Identity);
+        Cost::set_matrix(Matrix::Identity);
+        assert_eq!(Cost::cost(b'W', b'W'), 0);
+        assert_eq!(Cost::cost(b'W', b'F'), DEFAULT_IDENTITY_MISMATCH);
+        assert_eq!(Cost::get_gap_cost(), DEFAULT_IDENTITY_GAP);
+    }
+
+    #[test]
+    fn test_concurrent_matrix_switch() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        let done = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while !done.load(Ordering::Relaxed) {
+                        let snapshot = Cost::matrix_snapshot();
+                        match snapshot.gap_cost() {
+                            2 => assert_eq!(snapshot.cost(b'A', b'C'), 1),
+                            30 => assert_eq!(snapshot.cost(b'A', b'C'), 19),
+                            gap => panic!("unexpected gap cost {}", gap),
+                        }
+                        assert_eq!(snapshot.gap_cost(), snapshot.gap_gap());
+                    }
+                });
+            }
+
+            for i in 0..500 {
+                if i % 2 == 0 {
+                    Cost::set_cost_pam250();
+                } else {
+                    Cost::set_cost_nuc();
+                }
+            }
+            done.store(true, Ordering::Relaxed);
+        });
     }
 }
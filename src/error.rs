@@ -0,0 +1,49 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Errors reported by the alignment library
+ */
+
+use std::fmt;
+use crate::MAX_SEARCH_SEQUENCES;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MsaError {
+    /// Fewer sequences loaded than an alignment needs
+    TooFewSequences(usize),
+    /// More sequences loaded than the exact search supports
+    TooManySequences(usize),
+    /// The search started before any cost matrix was selected
+    NoCostMatrix,
+    /// Sequence index and a residue the active matrix can't score
+    InvalidResidue(usize, char),
+    /// The active costs fail `ScoringScheme::validate`, with its message
+    InvalidCosts(String),
+}
+
+impl fmt::Display for MsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MsaError::TooFewSequences(n) => write!(f, "Need at least 2 sequences, got {}", n),
+            MsaError::TooManySequences(n) => write!(
+                f, "Got {} sequences, the exact search supports at most {}", n, MAX_SEARCH_SEQUENCES
+            ),
+            MsaError::NoCostMatrix => write!(
+                f, "No cost matrix selected, call Cost::set_matrix or a Cost::set_cost_* function first"
+            ),
+            MsaError::InvalidResidue(i, c) => write!(
+                f, "Sequence {} contains '{}', which the active cost matrix can't score", i, c
+            ),
+            MsaError::InvalidCosts(e) => write!(f, "Invalid cost matrix: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MsaError {}
+
+impl From<MsaError> for String {
+    fn from(e: MsaError) -> Self {
+        e.to_string()
+    }
+}
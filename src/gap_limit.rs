@@ -0,0 +1,195 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief A-Star with a cap on the length of every gap run, for models where
+ * longer gaps are implausible. A search state is the lattice coordinate plus
+ * the gap run each sequence currently ends on.
+ */
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use ahash::AHashMap;
+use crate::alignment::{Alignment, SearchScores};
+use crate::astar;
+use crate::backtrace;
+use crate::coord::Coord;
+use crate::heuristic_hpair::SearchCosts;
+use crate::interrupt;
+use crate::msa_options::AStarOpt;
+use crate::node::Node;
+use crate::sequences::Sequences;
+use crate::time_counter::TimeCounter;
+
+/// Lattice coordinate and the length of the gap run ending there in each
+/// sequence
+type State<const N: usize> = (Coord<N>, [u16; N]);
+
+/// A reached state, its cost and heuristic, and the entry it came from
+struct Entry<const N: usize> {
+    state: State<N>,
+    g: i32,
+    h: i32,
+    parent: usize,
+}
+
+/// Gap runs after the move `neigh_num` (a bit per advanced sequence), or
+/// `None` if one would grow past `max_gap`
+fn next_runs<const N: usize>(runs: &[u16; N], neigh_num: u32, max_gap: usize) -> Option<[u16; N]> {
+    let mut next = *runs;
+    for (dim, run) in next.iter_mut().enumerate() {
+        if neigh_num & (1 << dim) != 0 {
+            *run = 0;
+        } else {
+            *run = run.saturating_add(1);
+            if *run as usize > max_gap {
+                return None;
+            }
+        }
+    }
+    Some(next)
+}
+
+/// Optimal alignment of the loaded sequences with no gap run longer than
+/// `max_gap`. The pairwise heuristic ignores the cap, so it stays a lower
+/// bound; the extra state only multiplies the nodes a coordinate can have.
+pub fn a_star_gap_limited<const N: usize>(max_gap: usize, options: &AStarOpt) -> Result<Alignment, String> {
+    let _timer = TimeCounter::new("\nPhase 2: Gap-limited A-Star running time:");
+
+    let origin: Coord<N> = Sequences::get_initial_coord();
+    let goal: Coord<N> = Sequences::get_final_coord();
+    let SearchCosts { scheme, heuristic } = SearchCosts::for_options(options)?;
+    let initial_h = heuristic.calculate_h(&origin);
+    astar::check_max_cost(initial_h, options)?;
+
+    let mut entries = vec![Entry { state: (origin, [0; N]), g: 0, h: initial_h, parent: 0 }];
+    let mut best: AHashMap<State<N>, usize> = AHashMap::new();
+    best.insert(entries[0].state, 0);
+    // Lowest f first, then the earliest reached
+    let mut open_list = BinaryHeap::new();
+    open_list.push(Reverse((initial_h, 0usize)));
+    let mut nodes_expanded = 0usize;
+    let mut final_entry = None;
+
+    while let Some(Reverse((_, index))) = open_list.pop() {
+        if interrupt::is_cancelled() {
+            return Err("Search interrupted".to_string());
+        }
+        let Entry { state: (pos, runs), g, h, .. } = entries[index];
+        // A cheaper way to this state was queued after it
+        if best[&(pos, runs)] != index {
+            continue;
+        }
+        if pos == goal {
+            final_entry = Some(index);
+            break;
+        }
+        nodes_expanded += 1;
+
+        for (neigh_num, cost, new_pos) in Node::with_values(g, pos, 0).neighbor_moves(&scheme) {
+            let Some(new_runs) = next_runs(&runs, neigh_num, max_gap) else {
+                continue;
+            };
+            let new_g = g + cost;
+            let new_h = h + heuristic.delta_h(&pos, &new_pos);
+            if !astar::within_max_cost(new_g + new_h, options) {
+                continue;
+            }
+            let state = (new_pos, new_runs);
+            if best.get(&state).is_some_and(|&other| entries[other].g <= new_g) {
+                continue;
+            }
+            best.insert(state, entries.len());
+            open_list.push(Reverse((new_g + new_h, entries.len())));
+            entries.push(Entry { state, g: new_g, h: new_h, parent: index });
+        }
+    }
+
+    crate::info!("Nodes expanded: {}", nodes_expanded);
+    crate::info!("States reached: {} for {} coordinates", best.len(),
+                 best.keys().map(|(pos, _)| pos).collect::<ahash::AHashSet<_>>().len());
+    let Some(mut index) = final_entry else {
+        return Err(match options.max_cost {
+            Some(_) => astar::no_solution(options),
+            None => format!("No alignment keeps every gap run within {}", max_gap),
+        });
+    };
+
+    let final_g = entries[index].g;
+    let mut path = vec![Node::with_values(final_g, goal, 0)];
+    while index != 0 {
+        index = entries[index].parent;
+        path.push(Node::with_values(entries[index].g, entries[index].state.0, 0));
+    }
+    path.reverse();
+
+    let verify = cfg!(debug_assertions) || options.verify_reconstruction;
+    // By input position, so taken before the order is restored
+    let pairwise_scores = heuristic.pairwise_scores();
+    let search_scores = SearchScores { f: final_g, g: final_g, h: 0, initial_h };
+    let alignment = backtrace::path_alignment(&path, &scheme, verify, options)?
+        .with_pairwise_scores(pairwise_scores)
+        .with_search_scores(search_scores)
+        .with_nodes_expanded(nodes_expanded);
+    backtrace::report_alignment(&alignment, options)?;
+    astar::report_expansion_ratio(&alignment);
+    Ok(alignment)
+}
+
+/// `a_star_gap_limited` for the number of loaded sequences
+pub fn run_gap_limited(max_gap: usize, options: &AStarOpt) -> Result<Alignment, String> {
+    dispatch_by_n!(Sequences::get_seq_num(), N => a_star_gap_limited::<N>(max_gap, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::GAP;
+    use crate::heuristic_hpair::HeuristicHPair;
+
+    fn longest_gap_run(row: &str) -> usize {
+        row.as_bytes().split(|&c| c != GAP).map(<[u8]>::len).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn test_gap_runs_within_limit() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VLSPAAWGKVGAH", "VLSPADKTNVKAWGKVGAH"]);
+        let unlimited = astar::run_astar_uncached(&AStarOpt::builder().build()).unwrap();
+        let unlimited_g = unlimited.search_scores().unwrap().g;
+        let unlimited_run = unlimited.seqs().iter().map(|row| longest_gap_run(row)).max().unwrap();
+        assert!(unlimited_run >= 4);
+
+        // A cap no run reaches finds the same optimal cost
+        let same = run_gap_limited(usize::MAX, &AStarOpt::builder().build()).unwrap();
+        assert_eq!(same.search_scores().unwrap().g, unlimited_g);
+        assert_eq!(same.sp_score(), unlimited.sp_score());
+
+        // The second sequence is 7 shorter, so runs of 3 must be spread out
+        let limited = run_gap_limited(3, &AStarOpt::builder().build()).unwrap();
+        assert!(limited.seqs().iter().all(|row| longest_gap_run(row) <= 3), "{:?}", limited.seqs());
+        assert!(limited.search_scores().unwrap().g >= unlimited_g);
+
+        // Nothing fits when the length difference can't be split into runs
+        let err = run_gap_limited(0, &AStarOpt::builder().build()).unwrap_err();
+        assert_eq!(err, "No alignment keeps every gap run within 0");
+    }
+
+    #[test]
+    fn test_first_seq_with_gap_limit() {
+        let _guard = crate::test_lock();
+        let inputs = ["VLSPADKTNVKAAWGKVGAH", "VLSPAAWGKVGAH", "GLSDGEWQLVLNVWGKVEAD"];
+        crate::test_support::pam_fixture(&inputs);
+        let baseline = run_gap_limited(4, &AStarOpt::builder().build()).unwrap();
+
+        Sequences::move_to_front(2).unwrap();
+        HeuristicHPair::init().unwrap();
+        let alignment = run_gap_limited(4, &AStarOpt::builder().build()).unwrap();
+
+        for (row, input) in alignment.seqs().iter().zip(inputs) {
+            assert_eq!(row.replace('-', ""), input);
+        }
+        assert_eq!(alignment.pairwise_scores(), baseline.pairwise_scores());
+        assert_eq!(alignment.search_scores().unwrap().g, baseline.search_scores().unwrap().g);
+    }
+}
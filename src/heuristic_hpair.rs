@@ -8,79 +8,310 @@
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use rayon::prelude::*;
-use std::time::Instant;
+use std::sync::Arc;
 
 use crate::coord::Coord;
+use crate::cost::{Cost, ScoringScheme};
+use crate::error::MsaError;
+use crate::msa_options::AStarOpt;
 use crate::pair_align::PairAlign;
 use crate::sequences::Sequences;
+use crate::time_counter;
 
 static HEURISTIC: Lazy<RwLock<HeuristicData>> = Lazy::new(|| {
     RwLock::new(HeuristicData::new())
 });
 
 struct HeuristicData {
-    aligns: Vec<PairAlign>,
+    /// Shared so a prepared problem can reinstall it without recomputing
+    aligns: Arc<Vec<PairAlign>>,
+    /// Costs the pairs were aligned with, `None` before `init`
+    scheme: Option<ScoringScheme>,
 }
 
 impl HeuristicData {
     fn new() -> Self {
         HeuristicData {
-            aligns: Vec::new(),
+            aligns: Arc::default(),
+            scheme: None,
         }
     }
 }
 
 pub struct HeuristicHPair;
 
-impl HeuristicHPair {
-    pub fn init() {
-        let start = Instant::now();
+/// Pairwise alignments of the loaded sequences under one scheme, the
+/// heuristic a search reads. Clones share the alignments.
+#[derive(Clone, Default)]
+pub struct PairHeuristic {
+    aligns: Arc<Vec<PairAlign>>,
+}
+
+impl PairHeuristic {
+    /// Align `pairs` of the loaded sequences under `scheme`, without
+    /// touching the heuristic `HeuristicHPair` holds
+    pub fn align(scheme: &ScoringScheme, pairs: &[(usize, usize)], parallel: bool) -> Result<Self, MsaError> {
         let seq_num = Sequences::get_seq_num();
-        
-        println!("Starting pairwise alignments...");
-        
-        // Create list of pairs to align
-        let mut pairs = Vec::new();
-        for i in 0..seq_num - 1 {
-            for j in i + 1..seq_num {
-                pairs.push((i, j));
-            }
+        if seq_num < 2 {
+            return Err(MsaError::TooFewSequences(seq_num));
         }
-        
-        // Parallel computation of all pairwise alignments
-        let aligns: Vec<PairAlign> = pairs.par_iter()
-            .map(|&(i, j)| {
-                let s1 = Sequences::get_seq(i);
-                let s2 = Sequences::get_seq(j);
-                PairAlign::new((i, j), &s1, &s2)
+        scheme.check_selected()?;
+        for i in 0..seq_num {
+            check_residues(scheme, i)?;
+        }
+        let aligns: Vec<PairAlign> = if parallel {
+            pairs.par_iter().map(|&(i, j)| align_pair(scheme, i, j)).collect()
+        } else {
+            pairs.iter().map(|&(i, j)| align_pair(scheme, i, j)).collect()
+        };
+        Ok(PairHeuristic { aligns: Arc::new(aligns) })
+    }
+
+    /// Sum of the optimal pairwise costs of the remaining suffixes. Each
+    /// pairwise score already pays for the `|remaining_i - remaining_j|`
+    /// gaps the length difference forces, so no separate length term can
+    /// make this bound any tighter.
+    pub fn calculate_h<const N: usize>(&self, c: &Coord<N>) -> i32 {
+        sum_h(&self.aligns, c)
+    }
+
+    /// Change in `h` when moving from `parent` to `neigh`; only the pairs
+    /// touching an advanced dimension are looked up
+    pub fn delta_h<const N: usize>(&self, parent: &Coord<N>, neigh: &Coord<N>) -> i32 {
+        sum_delta_h(&self.aligns, parent, neigh)
+    }
+
+    /// Optimal pairwise cost of every aligned pair, with pairs given by
+    /// input position
+    pub fn pairwise_scores(&self) -> Vec<((usize, usize), i32)> {
+        let mut scores: Vec<((usize, usize), i32)> = self.aligns.iter()
+            .map(|align| {
+                let (i, j) = align.get_pair();
+                let (i, j) = (Sequences::input_index(i), Sequences::input_index(j));
+                ((i.min(j), i.max(j)), align.get_final_score())
             })
             .collect();
+        scores.sort_unstable();
+        scores
+    }
+
+    pub fn aligns(&self) -> &[PairAlign] {
+        &self.aligns
+    }
+}
+
+/// Move costs and heuristic of one search
+#[derive(Clone)]
+pub struct SearchCosts {
+    pub scheme: ScoringScheme,
+    pub heuristic: PairHeuristic,
+}
+
+impl SearchCosts {
+    /// The costs of `options` (see `AStarOpt::costs`) with the heuristic
+    /// `HeuristicHPair` holds if it was aligned under them, otherwise with
+    /// the pairs aligned anew: every pair, or those against sequence 0 for
+    /// `to_reference`
+    pub fn for_options(options: &AStarOpt) -> Result<Self, MsaError> {
+        let installed = {
+            let data = HEURISTIC.read();
+            let matches = options.scheme.is_none() || data.scheme == options.scheme;
+            matches.then(|| PairHeuristic { aligns: data.aligns.clone() })
+        };
+        let scheme = options.costs();
+        let heuristic = match installed {
+            Some(heuristic) => heuristic,
+            None if options.to_reference => PairHeuristic::align(&scheme, &reference_pairs(), true)?,
+            None => PairHeuristic::align(&scheme, &all_pairs(), true)?,
+        };
+        Ok(SearchCosts { scheme, heuristic })
+    }
+}
+
+impl HeuristicHPair {
+    pub fn init() -> Result<(), MsaError> {
+        Self::init_with(true)
+    }
+
+    /// `init`, aligning the pairs on the rayon pool if `parallel`, or one
+    /// after the other on the calling thread for timings free of scheduling
+    /// noise
+    pub fn init_with(parallel: bool) -> Result<(), MsaError> {
+        Self::init_scheme(&Cost::matrix_snapshot(), &all_pairs(), parallel)
+    }
+
+    /// `init_with` for `--to-reference`: only sequence 0 against each other
+    /// sequence, the N - 1 pairs `center_star::star_alignment` reads for center 0.
+    /// Too weak a heuristic for the exact search, which `refresh` replaces.
+    pub fn init_reference_with(parallel: bool) -> Result<(), MsaError> {
+        Self::init_scheme(&Cost::matrix_snapshot(), &reference_pairs(), parallel)
+    }
+
+    /// Align `pairs` under `scheme` and make them the heuristic searches
+    /// without a scheme of their own read
+    pub fn init_scheme(scheme: &ScoringScheme, pairs: &[(usize, usize)], parallel: bool) -> Result<(), MsaError> {
+        let start = time_counter::phase_start();
+        crate::info!("Starting pairwise alignments...");
+        let heuristic = PairHeuristic::align(scheme, pairs, parallel)?;
+        Sequences::take_edited();
         
         let mut data = HEURISTIC.write();
-        data.aligns = aligns;
+        data.aligns = heuristic.aligns;
+        data.scheme = Some(scheme.clone());
         
-        let duration = start.elapsed();
-        println!("Pairwise alignments completed in {:.3}s", duration.as_secs_f64());
+        let duration = time_counter::record_phase("Phase 1: Pairwise alignments", start);
+        crate::info!("Pairwise alignments completed in {:.3}s", duration.as_secs_f64());
+        Ok(())
+    }
+
+    /// The heuristic `init` computed, as a value
+    pub fn installed() -> PairHeuristic {
+        PairHeuristic { aligns: HEURISTIC.read().aligns.clone() }
     }
 
+    /// `PairHeuristic::calculate_h` of the installed heuristic
     pub fn calculate_h<const N: usize>(c: &Coord<N>) -> i32 {
+        sum_h(&HEURISTIC.read().aligns, c)
+    }
+
+    /// Cost of aligning the suffixes `i[ci..]` and `j[cj..]` of loaded
+    /// sequences `i` and `j`, the term the pair adds to `calculate_h`.
+    /// `None` if the pair wasn't aligned by `init`.
+    pub fn get_pair_score(i: usize, j: usize, ci: usize, cj: usize) -> Option<i32> {
         let data = HEURISTIC.read();
-        let mut h = 0;
-        
-        for align in &data.aligns {
-            let (i, j) = align.get_pair();
-            let pos_i = c.get(i) as usize;
-            let pos_j = c.get(j) as usize;
-            h += align.get_score(pos_i, pos_j);
+        data.aligns.iter().find_map(|align| match align.get_pair() {
+            pair if pair == (i, j) => Some(align.get_score(ci, cj)),
+            pair if pair == (j, i) => Some(align.get_score(cj, ci)),
+            _ => None,
+        })
+    }
+
+    /// `PairHeuristic::delta_h` of the installed heuristic
+    pub fn delta_h<const N: usize>(parent: &Coord<N>, neigh: &Coord<N>) -> i32 {
+        sum_delta_h(&HEURISTIC.read().aligns, parent, neigh)
+    }
+
+    /// Re-align only the pairs of the sequences edited since the last `init`
+    /// or `refresh` (see `Sequences::replace_seq`), keeping the other pairs.
+    /// Falls back to `init` if the heuristic doesn't match the loaded
+    /// sequences or the active costs. Returns the number of pairs aligned.
+    pub fn refresh() -> Result<usize, MsaError> {
+        let seq_num = Sequences::get_seq_num();
+        let pair_num = seq_num * seq_num.saturating_sub(1) / 2;
+        let scheme = Cost::matrix_snapshot();
+        let stale_costs = HEURISTIC.read().scheme.as_ref() != Some(&scheme);
+        if stale_costs || HEURISTIC.read().aligns.len() != pair_num || seq_num < 2 {
+            Self::init()?;
+            return Ok(pair_num);
         }
-        
-        h
+
+        // Left marked if a residue is rejected, for the next attempt
+        let edited = Sequences::edited();
+        for &i in &edited {
+            check_residues(&scheme, i)?;
+        }
+        Sequences::take_edited();
+
+        let mut data = HEURISTIC.write();
+        let aligns = Arc::make_mut(&mut data.aligns);
+        let stale: Vec<usize> = (0..aligns.len())
+            .filter(|&k| {
+                let (i, j) = aligns[k].get_pair();
+                edited.binary_search(&i).is_ok() || edited.binary_search(&j).is_ok()
+            })
+            .collect();
+        let fresh: Vec<PairAlign> = stale.par_iter()
+            .map(|&k| {
+                let (i, j) = aligns[k].get_pair();
+                align_pair(&scheme, i, j)
+            })
+            .collect();
+        for (k, align) in stale.iter().zip(fresh) {
+            aligns[*k] = align;
+        }
+        Ok(stale.len())
     }
 
-    pub fn destroy_instance() {
+    /// Run `f` over the pairwise alignments computed by `init`
+    pub fn with_aligns<R>(f: impl FnOnce(&[PairAlign]) -> R) -> R {
+        let data = HEURISTIC.read();
+        f(&data.aligns)
+    }
+
+    /// `PairHeuristic::pairwise_scores` of the installed heuristic
+    pub fn pairwise_scores() -> Vec<((usize, usize), i32)> {
+        Self::installed().pairwise_scores()
+    }
+
+    /// Handle on the current pairwise alignments, to `install` later
+    pub fn shared_aligns() -> Arc<Vec<PairAlign>> {
+        HEURISTIC.read().aligns.clone()
+    }
+
+    /// Make `aligns`, from `shared_aligns`, the active heuristic again.
+    /// They must have been aligned with the active costs.
+    pub fn install(aligns: Arc<Vec<PairAlign>>) {
         let mut data = HEURISTIC.write();
-        data.aligns.clear();
+        data.aligns = aligns;
+        data.scheme = Some(Cost::matrix_snapshot());
+    }
+
+    pub fn destroy_instance() {
+        *HEURISTIC.write() = HeuristicData::new();
+    }
+}
+
+fn sum_h<const N: usize>(aligns: &[PairAlign], c: &Coord<N>) -> i32 {
+    aligns.iter()
+        .map(|align| {
+            let (i, j) = align.get_pair();
+            align.get_score(c.get(i) as usize, c.get(j) as usize)
+        })
+        .sum()
+}
+
+fn sum_delta_h<const N: usize>(aligns: &[PairAlign], parent: &Coord<N>, neigh: &Coord<N>) -> i32 {
+    let mut delta = 0;
+
+    for align in aligns {
+        let (i, j) = align.get_pair();
+        if parent.get(i) == neigh.get(i) && parent.get(j) == neigh.get(j) {
+            continue;
+        }
+        delta += align.get_score(neigh.get(i) as usize, neigh.get(j) as usize)
+            - align.get_score(parent.get(i) as usize, parent.get(j) as usize);
     }
+
+    delta
+}
+
+/// Every pair of loaded sequences
+fn all_pairs() -> Vec<(usize, usize)> {
+    let seq_num = Sequences::get_seq_num();
+    (0..seq_num).flat_map(|i| (i + 1..seq_num).map(move |j| (i, j))).collect()
+}
+
+/// Sequence 0 against each other loaded sequence
+fn reference_pairs() -> Vec<(usize, usize)> {
+    (1..Sequences::get_seq_num()).map(|j| (0, j)).collect()
+}
+
+/// Fail on a residue of sequence `index` `scheme` doesn't score
+fn check_residues(scheme: &ScoringScheme, index: usize) -> Result<(), MsaError> {
+    match Sequences::get_seq_slice(index).iter().find(|&&c| !scheme.is_valid_residue(c)) {
+        Some(&c) => Err(MsaError::InvalidResidue(index, c as char)),
+        None => Ok(()),
+    }
+}
+
+/// Optimal alignment of sequences `i` and `j` with their gap costs and
+/// structure labels, under `scheme`
+fn align_pair(scheme: &ScoringScheme, i: usize, j: usize) -> PairAlign {
+    let (gaps_i, gaps_j) = (Sequences::get_gap_costs(i), Sequences::get_gap_costs(j));
+    let (ss_i, ss_j) = (Sequences::get_ss_labels(i), Sequences::get_ss_labels(j));
+    PairAlign::with_scheme(
+        scheme, (i, j), &Sequences::get_seq_slice(i), &Sequences::get_seq_slice(j), gaps_i, gaps_j, ss_i, ss_j
+    )
 }
 
 #[cfg(test)]
@@ -90,16 +321,235 @@ mod tests {
 
     #[test]
     fn test_heuristic_init() {
+        let _guard = crate::test_lock();
         Cost::set_cost_nuc();
         Sequences::clear();
         Sequences::set_seq("ACGT".to_string()).unwrap();
         Sequences::set_seq("AGCT".to_string()).unwrap();
         Sequences::set_seq("ACCT".to_string()).unwrap();
         
-        HeuristicHPair::init();
+        HeuristicHPair::init().unwrap();
         
         let coord: Coord<3> = Coord::new(0);
         let h = HeuristicHPair::calculate_h(&coord);
         assert!(h >= 0);
     }
+
+    #[test]
+    fn test_pairwise_scores_reported() {
+        use crate::astar;
+        use crate::msa_options::AStarOpt;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        for seq in ["ACGT", "AGGT", "ACT", "CGTA"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        HeuristicHPair::init().unwrap();
+
+        let options = AStarOpt::builder().report_pairs(true).build();
+        let alignment = astar::run_astar_for_sequences(&options).unwrap();
+        let scores = alignment.pairwise_scores();
+        assert_eq!(scores.len(), 4 * 3 / 2);
+        assert!(scores.iter().all(|&((i, j), score)| i < j && score >= 0));
+        // The pairwise optima are a lower bound of the alignment's cost
+        assert!(scores.iter().map(|(_, score)| score).sum::<i32>() <= alignment.sp_score());
+    }
+
+    #[test]
+    fn test_heuristic_init_single_sequence() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        assert_eq!(HeuristicHPair::init(), Err(MsaError::TooFewSequences(0)));
+
+        Sequences::set_seq("ACGT".to_string()).unwrap();
+        assert_eq!(HeuristicHPair::init(), Err(MsaError::TooFewSequences(1)));
+    }
+
+    #[test]
+    fn test_search_requires_cost_matrix() {
+        let _guard = crate::test_lock();
+        Cost::clear();
+        let seqs = vec!["ACGT".to_string(), "AGT".to_string()];
+        let options = crate::msa_options::PAStarOpt::builder().threads(1).build().unwrap();
+        let err = crate::align::align(&seqs, &options).unwrap_err();
+        assert!(err.contains("No cost matrix selected"));
+
+        Cost::set_cost_nuc();
+        assert!(crate::align::align(&seqs, &options).is_ok());
+
+        // Ambiguity codes are fine for DNA, other letters are not
+        let ambiguous = vec!["ACNGT".to_string(), "ARGT".to_string()];
+        assert!(crate::align::align(&ambiguous, &options).is_ok());
+        let protein = vec!["ACGT".to_string(), "PEL".to_string()];
+        assert_eq!(crate::align::align(&protein, &options), Err(MsaError::InvalidResidue(1, 'P').to_string()));
+    }
+
+    #[test]
+    fn test_delta_h_matches_calculate_h() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD", "AGEYGAEALERMF"]);
+
+        let final_coord: Coord<4> = Sequences::get_final_coord();
+        let mut state = 42u64;
+        let mut random = |bound: u32| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((state >> 33) as u32) % bound
+        };
+
+        for _ in 0..1000 {
+            let mut parent: Coord<4> = Coord::new(0);
+            for dim in 0..4 {
+                parent.set(dim, random(final_coord.get(dim) as u32) as u16);
+            }
+            let neigh_num = 1 + random(15) as usize;
+            let mut neigh = parent;
+            for dim in 0..4 {
+                if neigh_num & (1 << dim) != 0 {
+                    neigh.set(dim, neigh.get(dim) + 1);
+                }
+            }
+
+            let expected = HeuristicHPair::calculate_h(&neigh);
+            let h = HeuristicHPair::calculate_h(&parent) + HeuristicHPair::delta_h(&parent, &neigh);
+            assert_eq!(h, expected);
+        }
+    }
+
+    #[test]
+    fn test_pair_scores_sum_to_h() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD", "AGEYGAEALERMF"]);
+
+        for coord in [[0, 0, 0, 0], [3, 7, 1, 12], [20, 18, 20, 13], [5, 0, 19, 2]] {
+            let coord: Coord<4> = Coord::from_array(coord);
+            let mut h = 0;
+            for i in 0..4 {
+                for j in i + 1..4 {
+                    let (ci, cj) = (coord.get(i) as usize, coord.get(j) as usize);
+                    let score = HeuristicHPair::get_pair_score(i, j, ci, cj).unwrap();
+                    assert_eq!(HeuristicHPair::get_pair_score(j, i, cj, ci), Some(score));
+                    h += score;
+                }
+            }
+            assert_eq!(h, HeuristicHPair::calculate_h(&coord));
+        }
+        assert_eq!(HeuristicHPair::get_pair_score(0, 4, 0, 0), None);
+        assert_eq!(HeuristicHPair::get_pair_score(2, 2, 0, 0), None);
+    }
+
+    #[test]
+    fn test_scores_cover_forced_gaps() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        Sequences::clear();
+        Sequences::set_seq("VLSPADKTNVKAAWGKVGAHAGEYGAEALERMFLSFPTTKTYFPHFDLSHGSAQV".to_string()).unwrap();
+        Sequences::set_seq("VHLTPEEKS".to_string()).unwrap();
+        Sequences::set_seq("GLSDGEWQLVLNVWGKVEADIPGHGQEVLIRLFKGHPETLEKF".to_string()).unwrap();
+        // Uneven gap costs, the forced gaps take the cheapest remaining ones
+        let gaps = (0..9).map(|i| 3 + (i % 4) * 5).collect();
+        Sequences::set_gap_profile(1, gaps).unwrap();
+        HeuristicHPair::init().unwrap();
+
+        HeuristicHPair::with_aligns(|aligns| {
+            for align in aligns {
+                let (a, b) = align.get_pair();
+                let (len_a, len_b) = (Sequences::get_seq_len(a), Sequences::get_seq_len(b));
+                let (gaps_a, gaps_b) = (Sequences::get_gap_costs(a), Sequences::get_gap_costs(b));
+                for i in 0..=len_a {
+                    for j in 0..=len_b {
+                        let (rest_a, rest_b) = (len_a - i, len_b - j);
+                        // Gaps go in the sequence with fewer residues left
+                        let (shorter_gaps, diff) = if rest_a < rest_b {
+                            (&gaps_a[i..], rest_b - rest_a)
+                        } else {
+                            (&gaps_b[j..], rest_a - rest_b)
+                        };
+                        let forced = diff as i32 * shorter_gaps.iter().min().unwrap();
+                        assert!(align.get_score(i, j) >= forced, "pair {:?} at ({}, {})", (a, b), i, j);
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_refresh_realigns_edited_pairs() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD", "AGEYGAEALERMF"]);
+        assert_eq!(HeuristicHPair::refresh().unwrap(), 0);
+
+        Sequences::replace_seq(2, "GLSDGEWQLVLNVWGKVEADIPGHGQ".to_string()).unwrap();
+        assert_eq!(HeuristicHPair::refresh().unwrap(), 3);
+        let refreshed = HeuristicHPair::pairwise_scores();
+        let origin: Coord<4> = Coord::new(0);
+        let h = HeuristicHPair::calculate_h(&origin);
+
+        HeuristicHPair::init().unwrap();
+        assert_eq!(refreshed, HeuristicHPair::pairwise_scores());
+        assert_eq!(h, HeuristicHPair::calculate_h(&origin));
+
+        // New costs realign every pair, not only the edited ones
+        Cost::set_cost_blosum62();
+        Sequences::replace_seq(3, "AGEYGAEALERMFLSF".to_string()).unwrap();
+        assert_eq!(HeuristicHPair::refresh().unwrap(), 6);
+        let blosum = HeuristicHPair::pairwise_scores();
+        HeuristicHPair::init().unwrap();
+        assert_eq!(blosum, HeuristicHPair::pairwise_scores());
+        Cost::set_gap_cost(20);
+        assert_eq!(HeuristicHPair::refresh().unwrap(), 6);
+        assert_eq!(HeuristicHPair::refresh().unwrap(), 0);
+
+        // A rejected edit stays pending
+        Cost::set_cost_nuc();
+        Sequences::replace_seq(0, "ACGT#".to_string()).unwrap();
+        assert_eq!(HeuristicHPair::refresh(), Err(MsaError::InvalidResidue(0, '#')));
+        assert_eq!(Sequences::edited(), [0]);
+    }
+
+    #[test]
+    fn test_sequential_init_matches_parallel() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        Sequences::clear();
+        for seq in ["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD", "AGEYGAEALERMF"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+
+        HeuristicHPair::init_with(true).unwrap();
+        let parallel = HeuristicHPair::shared_aligns();
+        HeuristicHPair::init_with(false).unwrap();
+        assert!(*parallel == *HeuristicHPair::shared_aligns());
+    }
+
+    /// Load `seqs` under `load_matrix`, give other test threads time to
+    /// run, then check the globals and the heuristic built from them are
+    /// still this test's. Without `test_lock` the two callers below would
+    /// overwrite each other's matrix and sequences.
+    fn check_isolated(load_matrix: fn() -> ScoringScheme, seqs: [&str; 2]) {
+        let _guard = crate::test_lock();
+        load_matrix();
+        Sequences::clear();
+        for seq in seqs {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        let expected = PairAlign::new((0, 1), seqs[0].as_bytes(), seqs[1].as_bytes()).get_final_score();
+        for _ in 0..20 {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            HeuristicHPair::init().unwrap();
+            assert_eq!(Sequences::get_seq(0), seqs[0].as_bytes());
+            assert_eq!(HeuristicHPair::pairwise_scores(), [((0, 1), expected)]);
+        }
+    }
+
+    #[test]
+    fn test_lock_isolates_nucleotide_state() {
+        check_isolated(Cost::set_cost_nuc, ["ACGTACGT", "ACGTTACGT"]);
+    }
+
+    #[test]
+    fn test_lock_isolates_protein_state() {
+        check_isolated(Cost::set_cost_pam250, ["MKVLWAA", "MKVWA"]);
+    }
 }
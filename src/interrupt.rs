@@ -0,0 +1,41 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Cancellation flag for the searches and the Ctrl-C handler that
+ * sets it
+ */
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Ask running searches to stop at their next iteration
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// Clear a previous cancellation before starting a new search
+pub fn reset() {
+    CANCELLED.store(false, Ordering::SeqCst);
+    INTERRUPTS.store(0, Ordering::SeqCst);
+}
+
+/// Install a Ctrl-C handler: the first Ctrl-C cancels the search so the
+/// partial alignment can be saved, a second one quits immediately
+pub fn install_handler() -> Result<(), String> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTS.fetch_add(1, Ordering::SeqCst) == 0 {
+            eprintln!("\nInterrupted, saving the partial alignment (Ctrl-C again to quit)");
+            cancel();
+        } else {
+            std::process::exit(130);
+        }
+    })
+    .map_err(|e| format!("Can't install the Ctrl-C handler: {}", e))
+}
@@ -3,9 +3,44 @@
  * \copyright MIT License
  */
 
+/// Run `$body` with the const `$n` bound to `$seq_num`, for every sequence
+/// count the exact searches are compiled for, or return an `Err` naming the
+/// supported range. This list is the single dispatch table for A-Star,
+/// PA-Star and the hash autotuner; supporting another N is one more literal
+/// here (and a larger `MAX_SEARCH_SEQUENCES`).
+macro_rules! dispatch_by_n {
+    ($seq_num:expr, $n:ident => $body:expr) => {
+        dispatch_by_n!(@arms $seq_num, $n => $body; 2 3 4 5 6 7 8)
+    };
+    (@arms $seq_num:expr, $n:ident => $body:expr; $($value:literal)*) => {
+        match $seq_num {
+            $($value => {
+                const $n: usize = $value;
+                $body
+            })*
+            n => Err(format!(
+                "Unsupported number of sequences: {}. Supported: {}-{}",
+                n,
+                $crate::build_info::SEARCH_SEQUENCES.start(),
+                $crate::build_info::SEARCH_SEQUENCES.end()
+            )),
+        }
+    };
+}
+
+/// `println!` for informational output: printed unless `--quiet` and
+/// copied to the `--log-file`, see `logger`
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::logger::write(format_args!($($arg)*))
+    };
+}
+
 pub mod coord;
 pub mod coord_hash;
 pub mod cost;
+pub mod error;
 pub mod node;
 pub mod sequences;
 pub mod read_fasta;
@@ -13,22 +48,62 @@ pub mod pair_align;
 pub mod heuristic_hpair;
 pub mod astar;
 pub mod pastar;
+pub mod autotune;
 pub mod backtrace;
+pub mod alignment;
+pub mod alignment_diff;
+pub mod center_star;
+pub mod gap_limit;
+pub mod align;
+pub mod cli;
+pub mod profile;
+pub mod output_sink;
+pub mod color;
+pub mod column_stream;
+pub mod result_cache;
 pub mod time_counter;
+pub mod stats_csv;
+pub mod translate;
+pub mod build_info;
+pub mod interrupt;
+pub mod logger;
 pub mod msa_options;
 pub mod priority_list;
 pub mod priority_types;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(test)]
+mod test_support;
 
 pub const VERSION: &str = "2.0.0";
 
 // Maximum number of sequences helper
 pub const MAX_SEQUENCES: usize = 64;
 
+// Largest number of sequences the exact search is compiled for
+pub const MAX_SEARCH_SEQUENCES: usize = 8;
+
 // Hash shift default value
 pub const HASH_SHIFT: usize = 12;
 
+/// Serializes tests that replace the global cost matrix or sequences, or
+/// read or write any other process-wide state (heuristic, phase timings,
+/// logger). `cargo test` runs tests on parallel threads sharing the
+/// statics, so every such test holds this guard for its whole body.
+#[cfg(test)]
+pub(crate) fn test_lock() -> parking_lot::MutexGuard<'static, ()> {
+    static LOCK: parking_lot::Mutex<()> = parking_lot::const_mutex(());
+    LOCK.lock()
+}
+
 // Re-export commonly used types
+pub use align::{align, align_batch, prepare, AlignParams, PreparedProblem};
+pub use alignment_diff::{diff_alignments, AlignmentDiff};
+pub use alignment::{AlignMode, Alignment, FLevelStats, IdentityMode, OutputFormat, SearchScores};
 pub use coord::Coord;
-pub use cost::Cost;
+pub use coord_hash::{HashRouter, NodeRouter};
+pub use cost::{Cost, Matrix};
+pub use error::MsaError;
 pub use node::Node;
+pub use output_sink::OutputSink;
 pub use sequences::Sequences;
@@ -0,0 +1,125 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Informational output, printed to stdout unless quiet and copied
+ * with timestamps to the `--log-file` of the run
+ */
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use parking_lot::Mutex;
+
+struct LogFile {
+    out: BufWriter<File>,
+    filename: String,
+    opened: Instant,
+}
+
+static LOG_FILE: Mutex<Option<LogFile>> = parking_lot::const_mutex(None);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Stop (or resume) printing informational output to stdout. The log file,
+/// if any, still gets all of it.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Copy all later informational output to `filename`, replacing the file.
+/// Lines are prefixed with the seconds since it was opened, after a header
+/// with the Unix time of the start and the command line.
+pub fn open_log_file(filename: &str) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Error writing log file {}: {}", filename, e);
+    let mut out = BufWriter::new(File::create(filename).map_err(error)?);
+    let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let command: Vec<String> = std::env::args().collect();
+    writeln!(out, "# Log started at Unix time {}.{:03}", start.as_secs(), start.subsec_millis())
+        .and_then(|_| writeln!(out, "# Command line: {}", command.join(" ")))
+        .and_then(|_| out.flush())
+        .map_err(error)?;
+    *LOG_FILE.lock() = Some(LogFile { out, filename: filename.to_string(), opened: Instant::now() });
+    Ok(())
+}
+
+/// Flush and close the log file
+pub fn close_log_file() {
+    if let Some(mut log) = LOG_FILE.lock().take() {
+        let _ = log.out.flush();
+    }
+}
+
+/// Print `args` and a newline to stdout unless quiet, and to the log file.
+/// Use through the `info!` macro.
+pub fn write(args: fmt::Arguments) {
+    let text = args.to_string();
+    write_styled(&text, &text);
+}
+
+/// Like `write`, printing `styled`, the same text with terminal escapes,
+/// to stdout instead. The log file gets the plain `text`.
+pub fn write_styled(text: &str, styled: &str) {
+    if !is_quiet() {
+        println!("{}", styled);
+    }
+    let mut log = LOG_FILE.lock();
+    if let Some(file) = log.as_mut() {
+        let elapsed = file.opened.elapsed().as_secs_f64();
+        let written = text.lines()
+            .filter(|line| !line.is_empty())
+            .try_for_each(|line| writeln!(file.out, "[{:10.3}] {}", elapsed, line))
+            .and_then(|_| file.out.flush());
+        if let Err(e) = written {
+            // One warning, then carry on without the log
+            eprintln!("Warning: error writing log file {}: {}", file.filename, e);
+            *log = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar;
+    use crate::cost::Cost;
+    use crate::heuristic_hpair::HeuristicHPair;
+    use crate::msa_options::AStarOpt;
+    use crate::sequences::Sequences;
+
+    #[test]
+    fn test_log_file_gets_quiet_output() {
+        let _guard = crate::test_lock();
+        let path = std::env::temp_dir().join(format!("logger_{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        for seq in ["ACGTACGT", "ACGACGT", "ACTTACGT"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+
+        open_log_file(path).unwrap();
+        set_quiet(true);
+        crate::info!("\nPhase 1: {}", "heuristic");
+        HeuristicHPair::init().unwrap();
+        let alignment = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+        set_quiet(false);
+        close_log_file();
+        crate::info!("after closing");
+
+        let text = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("# Log started at Unix time "));
+        assert!(lines[1].starts_with("# Command line: "));
+        assert!(lines.iter().any(|line| line.starts_with('[') && line.ends_with("] Phase 1: heuristic")));
+        assert!(text.contains("] Final Score: "));
+        assert!(text.contains(&format!("] Sum-of-pairs score: {}\n", alignment.sp_score())));
+        assert!(!text.contains("after closing"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -8,70 +8,55 @@
 use clap::Parser;
 use astar_msa_rust::{
     astar,
-    cost::Cost,
-    heuristic_hpair::HeuristicHPair,
+    build_info,
+    cli::{self, CliArgs},
+    info,
     msa_options::{AStarOptions, AStarOpt},
-    read_fasta::read_fasta_file,
-    sequences::Sequences,
+    profile,
+    time_counter,
     VERSION,
 };
 
 fn main() {
-    let args = AStarOptions::parse();
-    
-    println!("MSA A-Star version {}", VERSION);
-    println!("Input file: {}", args.input_file);
-    
-    // Set cost matrix
-    if args.nucleotide {
-        println!("Using nucleotide cost matrix");
-        Cost::set_cost_nuc();
-    } else {
-        println!("Using PAM250 cost matrix");
-        Cost::set_cost_pam250();
-    }
-    
-    // Read FASTA file
-    if let Err(e) = read_fasta_file(&args.input_file) {
-        eprintln!("Error reading FASTA file: {}", e);
-        std::process::exit(1);
+    if build_info::verbose_version_requested(std::env::args()) {
+        print!("{}", build_info::build_info());
+        return;
     }
     
-    let seq_num = Sequences::get_seq_num();
-    println!("Number of sequences: {}", seq_num);
+    let args = AStarOptions::parse();
+    let cli_args = CliArgs::from(&args);
+    cli::start(&cli_args, "MSA A-Star");
     
-    if seq_num < 2 {
-        eprintln!("Error: Need at least 2 sequences");
-        std::process::exit(1);
+    // Scoring an existing alignment skips the search
+    if let Some(file) = &cli_args.score_only {
+        cli::score_only(file, &AStarOpt::from(args));
     }
     
-    // Print sequence information
-    for i in 0..seq_num {
-        println!("Sequence {}: {} (length: {})",
-            i,
-            Sequences::get_seq_name(i),
-            Sequences::get_seq_len(i)
-        );
+    // Profile alignment skips the exact search
+    if let Some(files) = &cli_args.profile {
+        match profile::run_profile_alignment(&files[0], &files[1], &AStarOpt::from(args)) {
+            Ok(_) => {
+                info!("\nAlignment completed successfully!");
+                if cli_args.json {
+                    println!("{}", time_counter::timings_json());
+                }
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Error during profile alignment: {}", e);
+                std::process::exit(1);
+            }
+        }
     }
     
-    // Initialize heuristic
-    println!("\nPhase 1: Initializing heuristic...");
-    HeuristicHPair::init();
+    let translation = cli::load(&cli_args);
     
     // Run A-Star
-    println!("\nPerforming search with Serial A-Star ({})", VERSION);
+    info!("\nPerforming search with Serial A-Star ({})", VERSION);
     let options = AStarOpt::from(args);
-    
-    match astar::run_astar_for_sequences(&options) {
-        Ok(()) => {
-            println!("\nAlignment completed successfully!");
-            if options.force_quit {
-                std::process::exit(0);
-            }
-        }
-        Err(e) => {
-            eprintln!("Error during alignment: {}", e);
-            std::process::exit(1);
-        }
-    }
+    let result = cli::run_search(&cli_args, &options, |options, cached| match cached {
+        true => astar::run_astar_for_sequences(options),
+        false => astar::run_astar_uncached(options),
+    });
+    cli::finish(&cli_args, result, translation, &options, None);
 }
@@ -6,51 +6,526 @@
  */
 
 use clap::Parser;
-use crate::coord_hash::HashType;
+use crate::alignment::{AlignMode, IdentityMode, OutputFormat};
+use crate::color::ColorMode;
+use crate::coord_hash::{choose_default_hash, HashType};
+use crate::cost::{Cost, Matrix, ScoringScheme, DEFAULT_SS_BONUS, DEFAULT_SS_PENALTY};
+use crate::priority_types::TiePolicy;
+use crate::sequences::{Region, StopHandling};
+use crate::translate::GeneticCode;
 use crate::HASH_SHIFT;
 
+/// Residues per line in FASTA output
+pub const DEFAULT_FASTA_WRAP: usize = 60;
+
+/// A2M match columns have fewer gaps than this fraction
+pub const DEFAULT_MATCH_GAP_FRACTION: f64 = 0.5;
+
+/// Lowest frequency of a consensus residue among its column's residues
+pub const DEFAULT_CONSENSUS_THRESHOLD: f64 = 0.5;
+
 #[derive(Parser, Debug)]
-#[command(author, version, about = "PA-Star: Parallel A-Star for Multiple Sequence Alignment", long_about = None)]
+#[command(author, version, about = "PA-Star: Parallel A-Star for Multiple Sequence Alignment", long_about = None, after_help = "Use --version --verbose for build features and supported sequence counts")]
 pub struct AStarOptions {
     /// Input FASTA file
-    #[arg(value_name = "FILE")]
-    pub input_file: String,
+    #[arg(value_name = "FILE", required_unless_present_any = ["seq", "profile", "score_only", "supported_n"], conflicts_with_all = ["seq", "profile", "score_only"])]
+    pub input_file: Option<String>,
+
+    /// Sequence to align given inline (repeatable, replaces the input file)
+    #[arg(long = "seq", value_name = "STRING", conflicts_with = "profile")]
+    pub seq: Vec<String>,
+
+    /// Align two aligned FASTA files to each other, keeping their columns fixed
+    #[arg(long, num_args = 2, value_names = ["A", "B"])]
+    pub profile: Option<Vec<String>>,
+
+    /// Report the score of an existing aligned FASTA file without searching
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["seq", "profile"])]
+    pub score_only: Option<String>,
+
+    /// Name of the matching --seq (repeatable, default: "Sequence <n>")
+    #[arg(long = "name", value_name = "STRING", requires = "seq")]
+    pub name: Vec<String>,
 
     /// Output FASTA file with alignment
     #[arg(short = 'f', long, value_name = "FILE")]
     pub output_file: Option<String>,
 
+    /// On the first Ctrl-C, stop and save the best alignment reachable so far
+    /// (may be suboptimal) to this FASTA file; a second Ctrl-C quits
+    #[arg(long, value_name = "FILE")]
+    pub save_on_interrupt: Option<String>,
+
+    /// Wrap FASTA output sequence lines at this many columns (0 = no wrap)
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_FASTA_WRAP)]
+    pub wrap: usize,
+
+    /// Output file format: fasta or a2m (match columns uppercase, inserts lowercase with '.' gaps)
+    #[arg(long, value_name = "FORMAT", default_value = "fasta")]
+    pub format: OutputFormat,
+
+    /// A2M match columns have a gap fraction below this, the others are inserts
+    #[arg(long, value_name = "F", default_value_t = DEFAULT_MATCH_GAP_FRACTION)]
+    pub match_gap_fraction: f64,
+
+    /// Guarantee byte-identical output for any thread count: fail instead of
+    /// falling back to scheduling-dependent stored parents
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Stop codons ('*'): strip (drop trailing, reject others), score or error
+    #[arg(long, value_name = "MODE", default_value = "strip")]
+    pub stop_handling: StopHandling,
+
+    /// Translate the input coding sequences to protein and align those
+    #[arg(long, conflicts_with = "nucleotide")]
+    pub translate: bool,
+
+    /// Reading frame of --translate: the base (1 to 3) the first codon starts at
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=3), requires = "translate")]
+    pub frame: u8,
+
+    /// Genetic code of --translate by NCBI table: 1 (standard), 2 (vertebrate
+    /// mitochondrial), 4 (mold mitochondrial) or 11 (bacterial)
+    #[arg(long, value_name = "CODE", default_value = "1", requires = "translate")]
+    pub genetic_code: GeneticCode,
+
+    /// Also write the alignment of the original codons to this FASTA file,
+    /// each gap becoming three
+    #[arg(long, value_name = "FILE", requires = "translate", conflicts_with = "region")]
+    pub back_translate: Option<String>,
+
+    /// Align only residues START..END (0-based, END exclusive) of sequence
+    /// INDEX (repeatable)
+    #[arg(long = "region", value_name = "INDEX:START-END")]
+    pub region: Vec<Region>,
+
+    /// Per-residue gap costs of named sequences, in place of the matrix's
+    /// gap cost (FASTA-style records of whitespace separated costs)
+    #[arg(long = "gap-profile", value_name = "FILE")]
+    pub gap_profile: Option<String>,
+
+    /// Secondary structure labels (H, E, C) of named sequences, in
+    /// FASTA-style records; residue pairs with the same label get a bonus
+    #[arg(long = "ss", value_name = "FILE")]
+    pub ss: Option<String>,
+
+    /// Cost taken off residue pairs with the same structure label
+    #[arg(long, value_name = "N", requires = "ss", default_value_t = DEFAULT_SS_BONUS,
+          value_parser = clap::value_parser!(i32).range(0..))]
+    pub ss_bonus: i32,
+
+    /// Cost added to residue pairs with different structure labels
+    #[arg(long, value_name = "N", requires = "ss", default_value_t = DEFAULT_SS_PENALTY,
+          value_parser = clap::value_parser!(i32).range(0..))]
+    pub ss_penalty: i32,
+
+    /// Print the cost matrix for the input residues and the gap costs
+    #[arg(long)]
+    pub print_matrix: bool,
+
+    /// Print the phase timings as JSON (nanoseconds) once the run finishes
+    #[arg(long)]
+    pub json: bool,
+
+    /// Print the sequence counts the exact search supports and exit
+    #[arg(long)]
+    pub supported_n: bool,
+
+    /// Aligned FASTA file of an earlier run to compare the result against
+    #[arg(long, value_name = "FILE")]
+    pub diff: Option<String>,
+
+    /// Reject FASTA sequence lines with characters other than residues, gaps and `*`
+    #[arg(long)]
+    pub strict_fasta: bool,
+
+    /// Write a samtools-style index of the input file to `<FILE>.fai`
+    #[arg(long, requires = "input_file")]
+    pub fai: bool,
+
+    /// Align the phase 1 pairs one after the other on the main thread
+    #[arg(long)]
+    pub no_heuristic_parallel: bool,
+
+    /// Don't print progress and statistics to stdout
+    #[arg(short = 'q', long)]
+    pub quiet: bool,
+
+    /// Copy progress and statistics, with timestamps, to this file
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<String>,
+
+    /// Align each sequence with its own reverse (not complemented) and print the costs, then exit
+    #[arg(long)]
+    pub self_reverse: bool,
+
+    /// Run the search this many times after phase 1 and print the min,
+    /// median and max wall time (output from the first run only)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat: Option<u32>,
+
+    /// Append a row of search statistics (input, costs, threads, hash,
+    /// list sizes, scores, phase times) to this CSV file, with a header
+    /// line when it's new
+    #[arg(long, value_name = "FILE")]
+    pub stats_csv: Option<String>,
+
+    /// Alignment mode: global, or local (best scoring sub-alignment, two sequences only)
+    #[arg(long, value_name = "MODE", default_value = "global")]
+    pub mode: AlignMode,
+
+    /// After the search, check the heuristic never exceeded the true remaining cost along the path
+    #[arg(long)]
+    pub audit_heuristic: bool,
+
+    /// Align every sequence to sequence 0 pairwise and stack them, instead of the full MSA search
+    #[arg(long)]
+    pub to_reference: bool,
+
+    /// Partial expansion: only queue successors whose f is within MARGIN of the
+    /// expanded node's, and requeue the node for the rest
+    #[arg(long, value_name = "MARGIN", value_parser = clap::value_parser!(i32).range(0..))]
+    pub partial_expansion: Option<i32>,
+
+    /// Reuse alignments of identical inputs and scoring from this directory,
+    /// saving new ones there
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<String>,
+
+    /// Write the majority-rule consensus of the alignment, without gaps,
+    /// to this FASTA file
+    #[arg(long, value_name = "FILE")]
+    pub consensus: Option<String>,
+
+    /// Consensus columns whose most frequent residue has a lower frequency
+    /// than this are written as X
+    #[arg(long, value_name = "F", default_value_t = DEFAULT_CONSENSUS_THRESHOLD)]
+    pub consensus_threshold: f64,
+
+    /// Append the alignment to this multi-record FASTA file, after a
+    /// `; group <n>` separator line
+    #[arg(long, value_name = "FILE")]
+    pub append_output: Option<String>,
+
+    /// Order of open nodes with equal f: coord, high-g or fifo
+    #[arg(long, value_name = "POLICY", default_value = "coord")]
+    pub tie_policy: TiePolicy,
+
+    /// Write each residue's aligned column to this TSV file
+    #[arg(long, value_name = "FILE")]
+    pub coord_map: Option<String>,
+
+    /// Give up when no alignment can cost at most N
+    #[arg(long, value_name = "N")]
+    pub max_cost: Option<i32>,
+
+    /// Forbid gap runs longer than K in any sequence
+    #[arg(long, value_name = "K")]
+    pub max_gap: Option<usize>,
+
+    /// Print only the scores and timings: no alignment body, no output files
+    #[arg(long)]
+    pub summary_only: bool,
+
+    /// Write the alignment to this file column by column from the search path (CLUSTAL for .aln, FASTA otherwise)
+    #[arg(long, value_name = "FILE")]
+    pub stream_output: Option<String>,
+
+    /// Color the alignment printed to the terminal: auto, always or never
+    #[arg(long, value_name = "WHEN", default_value = "auto")]
+    pub color: ColorMode,
+
     /// Use nucleotide cost matrix (default: PAM250 for proteins)
     #[arg(short = 'n', long)]
     pub nucleotide: bool,
 
+    /// Cost matrix: pam250, blosum62, nuc or identity
+    #[arg(long, value_name = "NAME", conflicts_with = "nucleotide")]
+    pub matrix: Option<Matrix>,
+
+    /// Mismatch cost of the identity matrix, gaps cost twice as much
+    /// (default: 1)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(i32).range(0..))]
+    pub mismatch: Option<i32>,
+
+    /// Read the costs from this file of substitution scores in NCBI layout
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["nucleotide", "matrix"])]
+    pub matrix_file: Option<String>,
+
+    /// Multiply the scores of --matrix-file by this before rounding them to
+    /// integers (default: 1)
+    #[arg(long, value_name = "F", requires = "matrix_file")]
+    pub matrix_scale: Option<f64>,
+
+    /// Skip the exact search and build an approximate center-star alignment
+    #[arg(long)]
+    pub approx: bool,
+
+    /// Use the center-star approximation when the lattice has more nodes than this
+    #[arg(long, value_name = "SIZE")]
+    pub auto_approx: Option<u128>,
+
+    /// Similarity convention: strict (gap vs residue is a mismatch) or lenient (residue pairs only)
+    #[arg(long, value_name = "MODE", default_value = "strict")]
+    pub identity_mode: IdentityMode,
+
+    /// Print the optimal pairwise cost of each sequence pair used by the heuristic
+    #[arg(long)]
+    pub report_pairs: bool,
+
+    /// Check that every aligned sequence degaps back to its input (always on in debug builds)
+    #[arg(long)]
+    pub verify_reconstruction: bool,
+
     /// Force quit after alignment (skip cleanup)
     #[arg(long, default_value_t = true)]
     pub force_quit: bool,
 }
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "PA-Star: Parallel A-Star for Multiple Sequence Alignment", long_about = None)]
+#[command(author, version, about = "PA-Star: Parallel A-Star for Multiple Sequence Alignment", long_about = None, after_help = "Use --version --verbose for build features and supported sequence counts")]
 pub struct PAStarOptions {
     /// Input FASTA file
-    #[arg(value_name = "FILE")]
-    pub input_file: String,
+    #[arg(value_name = "FILE", required_unless_present_any = ["seq", "score_only", "supported_n"], conflicts_with_all = ["seq", "score_only"])]
+    pub input_file: Option<String>,
+
+    /// Sequence to align given inline (repeatable, replaces the input file)
+    #[arg(long = "seq", value_name = "STRING", conflicts_with = "score_only")]
+    pub seq: Vec<String>,
+
+    /// Report the score of an existing aligned FASTA file without searching
+    #[arg(long, value_name = "FILE")]
+    pub score_only: Option<String>,
+
+    /// Name of the matching --seq (repeatable, default: "Sequence <n>")
+    #[arg(long = "name", value_name = "STRING", requires = "seq")]
+    pub name: Vec<String>,
+
+    /// Input sequence to place in dimension 0, which the partial hashes
+    /// (pzorder, psum) ignore; the output keeps the input order
+    #[arg(long, value_name = "INDEX")]
+    pub first_seq: Option<usize>,
 
     /// Output FASTA file with alignment
     #[arg(short = 'f', long, value_name = "FILE")]
     pub output_file: Option<String>,
 
+    /// On the first Ctrl-C, stop and save the best alignment reachable so far
+    /// (may be suboptimal) to this FASTA file; a second Ctrl-C quits
+    #[arg(long, value_name = "FILE")]
+    pub save_on_interrupt: Option<String>,
+
+    /// Wrap FASTA output sequence lines at this many columns (0 = no wrap)
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_FASTA_WRAP)]
+    pub wrap: usize,
+
+    /// Output file format: fasta or a2m (match columns uppercase, inserts lowercase with '.' gaps)
+    #[arg(long, value_name = "FORMAT", default_value = "fasta")]
+    pub format: OutputFormat,
+
+    /// A2M match columns have a gap fraction below this, the others are inserts
+    #[arg(long, value_name = "F", default_value_t = DEFAULT_MATCH_GAP_FRACTION)]
+    pub match_gap_fraction: f64,
+
+    /// Guarantee byte-identical output for any thread count: fail instead of
+    /// falling back to scheduling-dependent stored parents
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Stop codons ('*'): strip (drop trailing, reject others), score or error
+    #[arg(long, value_name = "MODE", default_value = "strip")]
+    pub stop_handling: StopHandling,
+
+    /// Translate the input coding sequences to protein and align those
+    #[arg(long, conflicts_with = "nucleotide")]
+    pub translate: bool,
+
+    /// Reading frame of --translate: the base (1 to 3) the first codon starts at
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=3), requires = "translate")]
+    pub frame: u8,
+
+    /// Genetic code of --translate by NCBI table: 1 (standard), 2 (vertebrate
+    /// mitochondrial), 4 (mold mitochondrial) or 11 (bacterial)
+    #[arg(long, value_name = "CODE", default_value = "1", requires = "translate")]
+    pub genetic_code: GeneticCode,
+
+    /// Also write the alignment of the original codons to this FASTA file,
+    /// each gap becoming three
+    #[arg(long, value_name = "FILE", requires = "translate", conflicts_with = "region")]
+    pub back_translate: Option<String>,
+
+    /// Align only residues START..END (0-based, END exclusive) of sequence
+    /// INDEX (repeatable)
+    #[arg(long = "region", value_name = "INDEX:START-END")]
+    pub region: Vec<Region>,
+
+    /// Per-residue gap costs of named sequences, in place of the matrix's
+    /// gap cost (FASTA-style records of whitespace separated costs)
+    #[arg(long = "gap-profile", value_name = "FILE")]
+    pub gap_profile: Option<String>,
+
+    /// Secondary structure labels (H, E, C) of named sequences, in
+    /// FASTA-style records; residue pairs with the same label get a bonus
+    #[arg(long = "ss", value_name = "FILE")]
+    pub ss: Option<String>,
+
+    /// Cost taken off residue pairs with the same structure label
+    #[arg(long, value_name = "N", requires = "ss", default_value_t = DEFAULT_SS_BONUS,
+          value_parser = clap::value_parser!(i32).range(0..))]
+    pub ss_bonus: i32,
+
+    /// Cost added to residue pairs with different structure labels
+    #[arg(long, value_name = "N", requires = "ss", default_value_t = DEFAULT_SS_PENALTY,
+          value_parser = clap::value_parser!(i32).range(0..))]
+    pub ss_penalty: i32,
+
+    /// Print the cost matrix for the input residues and the gap costs
+    #[arg(long)]
+    pub print_matrix: bool,
+
+    /// Print the phase timings as JSON (nanoseconds) once the run finishes
+    #[arg(long)]
+    pub json: bool,
+
+    /// Print the sequence counts the exact search supports and exit
+    #[arg(long)]
+    pub supported_n: bool,
+
+    /// Aligned FASTA file of an earlier run to compare the result against
+    #[arg(long, value_name = "FILE")]
+    pub diff: Option<String>,
+
+    /// Reject FASTA sequence lines with characters other than residues, gaps and `*`
+    #[arg(long)]
+    pub strict_fasta: bool,
+
+    /// Write a samtools-style index of the input file to `<FILE>.fai`
+    #[arg(long, requires = "input_file")]
+    pub fai: bool,
+
+    /// Align the phase 1 pairs one after the other on the main thread
+    #[arg(long)]
+    pub no_heuristic_parallel: bool,
+
+    /// Don't print progress and statistics to stdout
+    #[arg(short = 'q', long)]
+    pub quiet: bool,
+
+    /// Copy progress and statistics, with timestamps, to this file
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<String>,
+
+    /// Align each sequence with its own reverse (not complemented) and print the costs, then exit
+    #[arg(long)]
+    pub self_reverse: bool,
+
+    /// Run the search this many times after phase 1 and print the min,
+    /// median and max wall time (output from the first run only)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat: Option<u32>,
+
+    /// Append a row of search statistics (input, costs, threads, hash,
+    /// list sizes, scores, phase times) to this CSV file, with a header
+    /// line when it's new
+    #[arg(long, value_name = "FILE")]
+    pub stats_csv: Option<String>,
+
+    /// Alignment mode: global, or local (best scoring sub-alignment, two sequences only)
+    #[arg(long, value_name = "MODE", default_value = "global")]
+    pub mode: AlignMode,
+
+    /// After the search, check the heuristic never exceeded the true remaining cost along the path
+    #[arg(long)]
+    pub audit_heuristic: bool,
+
+    /// Align every sequence to sequence 0 pairwise and stack them, instead of the full MSA search
+    #[arg(long)]
+    pub to_reference: bool,
+
+    /// Partial expansion: only queue successors whose f is within MARGIN of the
+    /// expanded node's, and requeue the node for the rest
+    #[arg(long, value_name = "MARGIN", value_parser = clap::value_parser!(i32).range(0..))]
+    pub partial_expansion: Option<i32>,
+
+    /// Reuse alignments of identical inputs and scoring from this directory,
+    /// saving new ones there
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<String>,
+
+    /// Write the majority-rule consensus of the alignment, without gaps,
+    /// to this FASTA file
+    #[arg(long, value_name = "FILE")]
+    pub consensus: Option<String>,
+
+    /// Consensus columns whose most frequent residue has a lower frequency
+    /// than this are written as X
+    #[arg(long, value_name = "F", default_value_t = DEFAULT_CONSENSUS_THRESHOLD)]
+    pub consensus_threshold: f64,
+
+    /// Append the alignment to this multi-record FASTA file, after a
+    /// `; group <n>` separator line
+    #[arg(long, value_name = "FILE")]
+    pub append_output: Option<String>,
+
+    /// Order of open nodes with equal f: coord, high-g or fifo
+    #[arg(long, value_name = "POLICY", default_value = "coord")]
+    pub tie_policy: TiePolicy,
+
+    /// Write each residue's aligned column to this TSV file
+    #[arg(long, value_name = "FILE")]
+    pub coord_map: Option<String>,
+
+    /// Give up when no alignment can cost at most N
+    #[arg(long, value_name = "N")]
+    pub max_cost: Option<i32>,
+
+    /// Forbid gap runs longer than K in any sequence
+    #[arg(long, value_name = "K")]
+    pub max_gap: Option<usize>,
+
+    /// Print only the scores and timings: no alignment body, no output files
+    #[arg(long)]
+    pub summary_only: bool,
+
+    /// Write the alignment to this file column by column from the search path (CLUSTAL for .aln, FASTA otherwise)
+    #[arg(long, value_name = "FILE")]
+    pub stream_output: Option<String>,
+
+    /// Color the alignment printed to the terminal: auto, always or never
+    #[arg(long, value_name = "WHEN", default_value = "auto")]
+    pub color: ColorMode,
+
     /// Use nucleotide cost matrix (default: PAM250 for proteins)
     #[arg(short = 'n', long)]
     pub nucleotide: bool,
 
-    /// Number of threads to use (default: number of CPUs)
+    /// Cost matrix: pam250, blosum62, nuc or identity
+    #[arg(long, value_name = "NAME", conflicts_with = "nucleotide")]
+    pub matrix: Option<Matrix>,
+
+    /// Mismatch cost of the identity matrix, gaps cost twice as much
+    /// (default: 1)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(i32).range(0..))]
+    pub mismatch: Option<i32>,
+
+    /// Read the costs from this file of substitution scores in NCBI layout
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["nucleotide", "matrix"])]
+    pub matrix_file: Option<String>,
+
+    /// Multiply the scores of --matrix-file by this before rounding them to
+    /// integers (default: 1)
+    #[arg(long, value_name = "F", requires = "matrix_file")]
+    pub matrix_scale: Option<f64>,
+
+    /// Number of threads to use (default: number of CPUs, 1 runs the serial A-Star)
     #[arg(short = 't', long)]
     pub threads: Option<usize>,
 
-    /// Hash type: fzorder, pzorder, fsum, psum
-    #[arg(long, default_value = "fzorder")]
-    pub hash_type: String,
+    /// Hash type: fzorder, pzorder, fsum, psum (default: fsum when
+    /// `threads << hash-shift` is at most 32, fzorder otherwise)
+    #[arg(long, value_name = "TYPE")]
+    pub hash_type: Option<HashType>,
 
     /// Hash shift value
     #[arg(long, default_value_t = HASH_SHIFT)]
@@ -64,6 +539,22 @@ pub struct PAStarOptions {
     #[arg(long, value_delimiter = ',')]
     pub affinity: Option<Vec<usize>>,
 
+    /// Prune closed entries that cannot beat the best goal every K expansions (0: never)
+    #[arg(long, value_name = "K", default_value_t = 0)]
+    pub compact_every: usize,
+
+    /// Initial open and closed list capacity per thread (default: derived from the lattice size)
+    #[arg(long, value_name = "NODES")]
+    pub presize: Option<usize>,
+
+    /// Try every hash type and a few shifts on short searches, then run with the fastest
+    #[arg(long)]
+    pub autotune_hash: bool,
+
+    /// Node budget of each --autotune-hash trial
+    #[arg(long, value_name = "N", requires = "autotune_hash")]
+    pub max_nodes: Option<usize>,
+
     /// Hybrid CPU configuration: p-cores-num
     #[arg(long)]
     pub p_cores_num: Option<usize>,
@@ -80,16 +571,68 @@ pub struct PAStarOptions {
     #[arg(long)]
     pub e_cores_size: Option<usize>,
 
+    /// Skip the exact search and build an approximate center-star alignment
+    #[arg(long)]
+    pub approx: bool,
+
+    /// Use the center-star approximation when the lattice has more nodes than this
+    #[arg(long, value_name = "SIZE")]
+    pub auto_approx: Option<u128>,
+
+    /// Similarity convention: strict (gap vs residue is a mismatch) or lenient (residue pairs only)
+    #[arg(long, value_name = "MODE", default_value = "strict")]
+    pub identity_mode: IdentityMode,
+
+    /// Print the optimal pairwise cost of each sequence pair used by the heuristic
+    #[arg(long)]
+    pub report_pairs: bool,
+
+    /// Check that every aligned sequence degaps back to its input (always on in debug builds)
+    #[arg(long)]
+    pub verify_reconstruction: bool,
+
     /// Force quit after alignment (skip cleanup)
     #[arg(long, default_value_t = true)]
     pub force_quit: bool,
 }
 
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AStarOpt {
     pub force_quit: bool,
     pub output_file: Option<String>,
+    pub approx: bool,
+    pub auto_approx: Option<u128>,
+    pub verify_reconstruction: bool,
+    pub identity_mode: IdentityMode,
+    pub report_pairs: bool,
+    pub wrap: usize,
+    pub save_on_interrupt: Option<String>,
+    pub format: OutputFormat,
+    pub match_gap_fraction: f64,
+    pub deterministic: bool,
+    pub mode: AlignMode,
+    pub audit_heuristic: bool,
+    pub to_reference: bool,
+    pub partial_expansion: Option<i32>,
+    pub cache_dir: Option<String>,
+    pub consensus: Option<String>,
+    pub consensus_threshold: f64,
+    pub append_output: Option<String>,
+    pub tie_policy: TiePolicy,
+    pub coord_map: Option<String>,
+    pub max_cost: Option<i32>,
+    pub max_gap: Option<usize>,
+    pub summary_only: bool,
+    pub stream_output: Option<String>,
+    pub color: ColorMode,
+    /// Costs of the search, `None` for the active ones (see `costs`)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub scheme: Option<ScoringScheme>,
 }
 
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PAStarOpt {
     pub common: AStarOpt,
     pub hash_type: HashType,
@@ -98,9 +641,13 @@ pub struct PAStarOpt {
     pub no_affinity: bool,
     pub thread_affinity: Vec<usize>,
     pub hybrid_conf: HybridCpu,
+    pub compact_every: usize,
+    pub presize: Option<usize>,
+    pub max_nodes: Option<usize>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HybridCpu {
     pub p_cores_num: usize,
     pub p_cores_size: usize,
@@ -108,22 +655,615 @@ pub struct HybridCpu {
     pub e_cores_size: usize,
 }
 
-impl Default for HybridCpu {
+impl AStarOpt {
+    /// Start building serial A-Star options without going through the CLI
+    ///
+    /// ```
+    /// use astar_msa_rust::msa_options::AStarOpt;
+    ///
+    /// let opt = AStarOpt::builder()
+    ///     .output_file("aligned.fasta")
+    ///     .force_quit(false)
+    ///     .build();
+    /// assert_eq!(opt.output_file.as_deref(), Some("aligned.fasta"));
+    /// ```
+    pub fn builder() -> AStarOptBuilder {
+        AStarOptBuilder::default()
+    }
+
+    /// The scheme set with `AStarOptBuilder::scheme`, or the active costs
+    pub fn costs(&self) -> ScoringScheme {
+        self.scheme.clone().unwrap_or_else(Cost::matrix_snapshot)
+    }
+}
+
+impl PAStarOpt {
+    /// Start building parallel A-Star options without going through the CLI
+    ///
+    /// Defaults: one thread per CPU, full Z-order hash, `HASH_SHIFT` shift,
+    /// thread affinity enabled and no hybrid CPU configuration.
+    ///
+    /// ```
+    /// use astar_msa_rust::coord_hash::HashType;
+    /// use astar_msa_rust::msa_options::PAStarOpt;
+    ///
+    /// let opt = PAStarOpt::builder()
+    ///     .threads(4)
+    ///     .hash_type(HashType::PSum)
+    ///     .hash_shift(8)
+    ///     .no_affinity(true)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(opt.threads_num, 4);
+    /// assert_eq!(opt.hash_type, HashType::PSum);
+    /// ```
+    pub fn builder() -> PAStarOptBuilder {
+        PAStarOptBuilder::default()
+    }
+
+    /// Check the thread count and hybrid CPU layout, which `build` and
+    /// `msa_pastar` both need before a search can start
+    pub fn validate(&self) -> Result<(), String> {
+        if self.threads_num == 0 {
+            return Err("Number of threads must be at least 1".to_string());
+        }
+
+        let hybrid = &self.hybrid_conf;
+        if hybrid.p_cores_num + hybrid.e_cores_num > self.threads_num {
+            return Err(format!(
+                "Hybrid configuration uses {} cores but only {} threads were requested",
+                hybrid.p_cores_num + hybrid.e_cores_num,
+                self.threads_num
+            ));
+        }
+        if (hybrid.p_cores_num > 0 && hybrid.p_cores_size == 0)
+            || (hybrid.e_cores_num > 0 && hybrid.e_cores_size == 0) {
+            return Err("Hybrid core groups must have a non-zero size".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Fluent builder for `AStarOpt`
+#[derive(Clone, Debug)]
+pub struct AStarOptBuilder {
+    force_quit: bool,
+    output_file: Option<String>,
+    approx: bool,
+    auto_approx: Option<u128>,
+    verify_reconstruction: bool,
+    identity_mode: IdentityMode,
+    report_pairs: bool,
+    wrap: usize,
+    save_on_interrupt: Option<String>,
+    format: OutputFormat,
+    match_gap_fraction: f64,
+    deterministic: bool,
+    mode: AlignMode,
+    audit_heuristic: bool,
+    to_reference: bool,
+    partial_expansion: Option<i32>,
+    cache_dir: Option<String>,
+    consensus: Option<String>,
+    consensus_threshold: f64,
+    append_output: Option<String>,
+    tie_policy: TiePolicy,
+    coord_map: Option<String>,
+    max_cost: Option<i32>,
+    max_gap: Option<usize>,
+    summary_only: bool,
+    stream_output: Option<String>,
+    color: ColorMode,
+    scheme: Option<ScoringScheme>,
+}
+
+impl Default for AStarOptBuilder {
     fn default() -> Self {
-        HybridCpu {
-            p_cores_num: 0,
-            p_cores_size: 0,
-            e_cores_num: 0,
-            e_cores_size: 0,
+        AStarOptBuilder {
+            force_quit: true,
+            output_file: None,
+            approx: false,
+            auto_approx: None,
+            verify_reconstruction: false,
+            identity_mode: IdentityMode::default(),
+            report_pairs: false,
+            wrap: DEFAULT_FASTA_WRAP,
+            save_on_interrupt: None,
+            format: OutputFormat::default(),
+            match_gap_fraction: DEFAULT_MATCH_GAP_FRACTION,
+            deterministic: false,
+            mode: AlignMode::default(),
+            audit_heuristic: false,
+            to_reference: false,
+            partial_expansion: None,
+            cache_dir: None,
+            consensus: None,
+            consensus_threshold: DEFAULT_CONSENSUS_THRESHOLD,
+            append_output: None,
+            tie_policy: TiePolicy::default(),
+            coord_map: None,
+            max_cost: None,
+            max_gap: None,
+            summary_only: false,
+            stream_output: None,
+            color: ColorMode::default(),
+            scheme: None,
         }
     }
 }
 
+impl AStarOptBuilder {
+    pub fn output_file(mut self, file: impl Into<String>) -> Self {
+        self.output_file = Some(file.into());
+        self
+    }
+
+    pub fn force_quit(mut self, force_quit: bool) -> Self {
+        self.force_quit = force_quit;
+        self
+    }
+
+    /// Always use the center-star approximation instead of the exact search
+    pub fn approx(mut self, approx: bool) -> Self {
+        self.approx = approx;
+        self
+    }
+
+    /// Use the center-star approximation above this lattice size
+    pub fn auto_approx(mut self, lattice_size: u128) -> Self {
+        self.auto_approx = Some(lattice_size);
+        self
+    }
+
+    /// Check the reconstructed alignment against the inputs in release builds too
+    pub fn verify_reconstruction(mut self, verify: bool) -> Self {
+        self.verify_reconstruction = verify;
+        self
+    }
+
+    pub fn identity_mode(mut self, mode: IdentityMode) -> Self {
+        self.identity_mode = mode;
+        self
+    }
+
+    pub fn report_pairs(mut self, report: bool) -> Self {
+        self.report_pairs = report;
+        self
+    }
+
+    /// FASTA output line width, 0 for one line per sequence
+    pub fn wrap(mut self, wrap: usize) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Where to save the partial alignment when the search is cancelled
+    pub fn save_on_interrupt(mut self, filename: &str) -> Self {
+        self.save_on_interrupt = Some(filename.to_string());
+        self
+    }
+
+    /// Output file format
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Columns with a gap fraction below this are A2M match states
+    pub fn match_gap_fraction(mut self, fraction: f64) -> Self {
+        self.match_gap_fraction = fraction;
+        self
+    }
+
+    /// Fail rather than return an alignment that may depend on thread scheduling
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Global or local alignment
+    pub fn mode(mut self, mode: AlignMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Check the heuristic against the true remaining cost along the found path
+    pub fn audit_heuristic(mut self, audit_heuristic: bool) -> Self {
+        self.audit_heuristic = audit_heuristic;
+        self
+    }
+
+    /// Stack pairwise alignments against sequence 0 instead of searching
+    pub fn to_reference(mut self, to_reference: bool) -> Self {
+        self.to_reference = to_reference;
+        self
+    }
+
+    /// Queue only successors within `margin` of the node's f, deferring the rest
+    pub fn partial_expansion(mut self, margin: i32) -> Self {
+        self.partial_expansion = Some(margin);
+        self
+    }
+
+    /// Directory of cached alignments, keyed on the sequences and scoring
+    pub fn cache_dir(mut self, dir: &str) -> Self {
+        self.cache_dir = Some(dir.to_string());
+        self
+    }
+
+    /// Write the ungapped consensus (see `Alignment::consensus`) to `filename`
+    pub fn consensus(mut self, filename: &str) -> Self {
+        self.consensus = Some(filename.to_string());
+        self
+    }
+
+    pub fn consensus_threshold(mut self, threshold: f64) -> Self {
+        self.consensus_threshold = threshold;
+        self
+    }
+
+    /// Append each alignment to `filename` (see `output_sink::AppendFastaSink`)
+    pub fn append_output(mut self, filename: &str) -> Self {
+        self.append_output = Some(filename.to_string());
+        self
+    }
+
+    /// Break ties among open nodes of equal f by `policy`
+    pub fn tie_policy(mut self, policy: TiePolicy) -> Self {
+        self.tie_policy = policy;
+        self
+    }
+
+    /// Write the residue to column map (see `Alignment::coord_map`) to `filename`
+    pub fn coord_map(mut self, filename: &str) -> Self {
+        self.coord_map = Some(filename.to_string());
+        self
+    }
+
+    /// Reject the input unless an alignment costs at most `cost`
+    pub fn max_cost(mut self, cost: i32) -> Self {
+        self.max_cost = Some(cost);
+        self
+    }
+
+    /// Forbid gap runs longer than `max_gap` in any sequence
+    pub fn max_gap(mut self, max_gap: usize) -> Self {
+        self.max_gap = Some(max_gap);
+        self
+    }
+
+    /// Report only the scores and timings, without the alignment body or output files
+    pub fn summary_only(mut self, summary_only: bool) -> Self {
+        self.summary_only = summary_only;
+        self
+    }
+
+    /// Stream the alignment to `filename` as it's read off the search path (see `column_stream`)
+    pub fn stream_output(mut self, filename: &str) -> Self {
+        self.stream_output = Some(filename.to_string());
+        self
+    }
+
+    /// When to color the alignment printed to the terminal
+    pub fn color(mut self, color: ColorMode) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Search with `scheme` instead of the active costs, so searches with
+    /// different schemes can run at the same time
+    pub fn scheme(mut self, scheme: ScoringScheme) -> Self {
+        self.scheme = Some(scheme);
+        self
+    }
+
+    pub fn build(self) -> AStarOpt {
+        AStarOpt {
+            force_quit: self.force_quit,
+            output_file: self.output_file,
+            approx: self.approx,
+            auto_approx: self.auto_approx,
+            verify_reconstruction: self.verify_reconstruction,
+            identity_mode: self.identity_mode,
+            report_pairs: self.report_pairs,
+            wrap: self.wrap,
+            save_on_interrupt: self.save_on_interrupt,
+            format: self.format,
+            match_gap_fraction: self.match_gap_fraction,
+            deterministic: self.deterministic,
+            mode: self.mode,
+            audit_heuristic: self.audit_heuristic,
+            to_reference: self.to_reference,
+            partial_expansion: self.partial_expansion,
+            cache_dir: self.cache_dir,
+            consensus: self.consensus,
+            consensus_threshold: self.consensus_threshold,
+            append_output: self.append_output,
+            tie_policy: self.tie_policy,
+            coord_map: self.coord_map,
+            max_cost: self.max_cost,
+            max_gap: self.max_gap,
+            summary_only: self.summary_only,
+            stream_output: self.stream_output,
+            color: self.color,
+            scheme: self.scheme,
+        }
+    }
+}
+
+/// Fluent builder for `PAStarOpt`
+#[derive(Clone, Debug)]
+pub struct PAStarOptBuilder {
+    common: AStarOptBuilder,
+    threads_num: usize,
+    hash_type: HashType,
+    hash_shift: usize,
+    no_affinity: bool,
+    thread_affinity: Vec<usize>,
+    hybrid_conf: HybridCpu,
+    compact_every: usize,
+    presize: Option<usize>,
+    max_nodes: Option<usize>,
+}
+
+impl Default for PAStarOptBuilder {
+    fn default() -> Self {
+        PAStarOptBuilder {
+            common: AStarOptBuilder::default(),
+            threads_num: num_cpus::get(),
+            hash_type: HashType::FZorder,
+            hash_shift: HASH_SHIFT,
+            no_affinity: false,
+            thread_affinity: Vec::new(),
+            hybrid_conf: HybridCpu::default(),
+            compact_every: 0,
+            presize: None,
+            max_nodes: None,
+        }
+    }
+}
+
+impl PAStarOptBuilder {
+    pub fn threads(mut self, threads_num: usize) -> Self {
+        self.threads_num = threads_num;
+        self
+    }
+
+    pub fn hash_type(mut self, hash_type: HashType) -> Self {
+        self.hash_type = hash_type;
+        self
+    }
+
+    pub fn hash_shift(mut self, hash_shift: usize) -> Self {
+        self.hash_shift = hash_shift;
+        self
+    }
+
+    pub fn no_affinity(mut self, no_affinity: bool) -> Self {
+        self.no_affinity = no_affinity;
+        self
+    }
+
+    pub fn thread_affinity(mut self, cores: Vec<usize>) -> Self {
+        self.thread_affinity = cores;
+        self
+    }
+
+    pub fn hybrid_conf(mut self, hybrid_conf: HybridCpu) -> Self {
+        self.hybrid_conf = hybrid_conf;
+        self
+    }
+
+    /// Compact the closed lists every `expansions` nodes, 0 disables it
+    pub fn compact_every(mut self, expansions: usize) -> Self {
+        self.compact_every = expansions;
+        self
+    }
+
+    /// Reserve room for `nodes` entries in each thread's open and closed
+    /// lists instead of deriving it from the lattice size
+    pub fn presize(mut self, nodes: usize) -> Self {
+        self.presize = Some(nodes);
+        self
+    }
+
+    /// Stop the search with an error after `nodes` expansions
+    pub fn max_nodes(mut self, nodes: usize) -> Self {
+        self.max_nodes = Some(nodes);
+        self
+    }
+
+    pub fn output_file(mut self, file: impl Into<String>) -> Self {
+        self.common = self.common.output_file(file);
+        self
+    }
+
+    pub fn force_quit(mut self, force_quit: bool) -> Self {
+        self.common = self.common.force_quit(force_quit);
+        self
+    }
+
+    pub fn approx(mut self, approx: bool) -> Self {
+        self.common = self.common.approx(approx);
+        self
+    }
+
+    pub fn auto_approx(mut self, lattice_size: u128) -> Self {
+        self.common = self.common.auto_approx(lattice_size);
+        self
+    }
+
+    pub fn verify_reconstruction(mut self, verify: bool) -> Self {
+        self.common = self.common.verify_reconstruction(verify);
+        self
+    }
+
+    pub fn identity_mode(mut self, mode: IdentityMode) -> Self {
+        self.common = self.common.identity_mode(mode);
+        self
+    }
+
+    pub fn report_pairs(mut self, report: bool) -> Self {
+        self.common = self.common.report_pairs(report);
+        self
+    }
+
+    pub fn wrap(mut self, wrap: usize) -> Self {
+        self.common = self.common.wrap(wrap);
+        self
+    }
+
+    pub fn save_on_interrupt(mut self, filename: &str) -> Self {
+        self.common = self.common.save_on_interrupt(filename);
+        self
+    }
+
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.common = self.common.format(format);
+        self
+    }
+
+    pub fn match_gap_fraction(mut self, fraction: f64) -> Self {
+        self.common = self.common.match_gap_fraction(fraction);
+        self
+    }
+
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.common = self.common.deterministic(deterministic);
+        self
+    }
+
+    pub fn mode(mut self, mode: AlignMode) -> Self {
+        self.common = self.common.mode(mode);
+        self
+    }
+
+    pub fn audit_heuristic(mut self, audit_heuristic: bool) -> Self {
+        self.common = self.common.audit_heuristic(audit_heuristic);
+        self
+    }
+
+    pub fn to_reference(mut self, to_reference: bool) -> Self {
+        self.common = self.common.to_reference(to_reference);
+        self
+    }
+
+    pub fn partial_expansion(mut self, margin: i32) -> Self {
+        self.common = self.common.partial_expansion(margin);
+        self
+    }
+
+    pub fn cache_dir(mut self, dir: &str) -> Self {
+        self.common = self.common.cache_dir(dir);
+        self
+    }
+
+    pub fn consensus(mut self, filename: &str) -> Self {
+        self.common = self.common.consensus(filename);
+        self
+    }
+
+    pub fn consensus_threshold(mut self, threshold: f64) -> Self {
+        self.common = self.common.consensus_threshold(threshold);
+        self
+    }
+
+    pub fn append_output(mut self, filename: &str) -> Self {
+        self.common = self.common.append_output(filename);
+        self
+    }
+
+    pub fn tie_policy(mut self, policy: TiePolicy) -> Self {
+        self.common = self.common.tie_policy(policy);
+        self
+    }
+
+    pub fn coord_map(mut self, filename: &str) -> Self {
+        self.common = self.common.coord_map(filename);
+        self
+    }
+
+    pub fn max_cost(mut self, cost: i32) -> Self {
+        self.common = self.common.max_cost(cost);
+        self
+    }
+
+    pub fn max_gap(mut self, max_gap: usize) -> Self {
+        self.common = self.common.max_gap(max_gap);
+        self
+    }
+
+    pub fn summary_only(mut self, summary_only: bool) -> Self {
+        self.common = self.common.summary_only(summary_only);
+        self
+    }
+
+    pub fn stream_output(mut self, filename: &str) -> Self {
+        self.common = self.common.stream_output(filename);
+        self
+    }
+
+    pub fn color(mut self, color: ColorMode) -> Self {
+        self.common = self.common.color(color);
+        self
+    }
+
+    /// Search with `scheme` instead of the active costs
+    pub fn scheme(mut self, scheme: ScoringScheme) -> Self {
+        self.common = self.common.scheme(scheme);
+        self
+    }
+
+    /// Validate the configuration and produce the options
+    pub fn build(self) -> Result<PAStarOpt, String> {
+        let opt = PAStarOpt {
+            common: self.common.build(),
+            hash_type: self.hash_type,
+            hash_shift: self.hash_shift,
+            threads_num: self.threads_num,
+            no_affinity: self.no_affinity,
+            thread_affinity: self.thread_affinity,
+            hybrid_conf: self.hybrid_conf,
+            compact_every: self.compact_every,
+            presize: self.presize,
+            max_nodes: self.max_nodes,
+        };
+        opt.validate()?;
+        Ok(opt)
+    }
+}
+
 impl From<AStarOptions> for AStarOpt {
     fn from(opts: AStarOptions) -> Self {
         AStarOpt {
             force_quit: opts.force_quit,
             output_file: opts.output_file,
+            approx: opts.approx,
+            auto_approx: opts.auto_approx,
+            verify_reconstruction: opts.verify_reconstruction,
+            identity_mode: opts.identity_mode,
+            report_pairs: opts.report_pairs,
+            wrap: opts.wrap,
+            save_on_interrupt: opts.save_on_interrupt,
+            format: opts.format,
+            match_gap_fraction: opts.match_gap_fraction,
+            deterministic: opts.deterministic,
+            mode: opts.mode,
+            audit_heuristic: opts.audit_heuristic,
+            to_reference: opts.to_reference,
+            partial_expansion: opts.partial_expansion,
+            cache_dir: opts.cache_dir,
+            consensus: opts.consensus,
+            consensus_threshold: opts.consensus_threshold,
+            append_output: opts.append_output,
+            tie_policy: opts.tie_policy,
+            coord_map: opts.coord_map,
+            max_cost: opts.max_cost,
+            max_gap: opts.max_gap,
+            summary_only: opts.summary_only,
+            stream_output: opts.stream_output,
+            color: opts.color,
+            scheme: None,
         }
     }
 }
@@ -132,8 +1272,8 @@ impl From<PAStarOptions> for PAStarOpt {
     fn from(opts: PAStarOptions) -> Self {
         let threads_num = opts.threads.unwrap_or_else(num_cpus::get);
         
-        let hash_type = HashType::from_str(&opts.hash_type)
-            .unwrap_or(HashType::FZorder);
+        let hash_type = opts.hash_type
+            .unwrap_or_else(|| choose_default_hash(threads_num, opts.hash_shift));
         
         let hybrid_conf = HybridCpu {
             p_cores_num: opts.p_cores_num.unwrap_or(0),
@@ -148,6 +1288,32 @@ impl From<PAStarOptions> for PAStarOpt {
             common: AStarOpt {
                 force_quit: opts.force_quit,
                 output_file: opts.output_file,
+                approx: opts.approx,
+                auto_approx: opts.auto_approx,
+                verify_reconstruction: opts.verify_reconstruction,
+                identity_mode: opts.identity_mode,
+                report_pairs: opts.report_pairs,
+                wrap: opts.wrap,
+                save_on_interrupt: opts.save_on_interrupt,
+                format: opts.format,
+                match_gap_fraction: opts.match_gap_fraction,
+                deterministic: opts.deterministic,
+                mode: opts.mode,
+                audit_heuristic: opts.audit_heuristic,
+                to_reference: opts.to_reference,
+                partial_expansion: opts.partial_expansion,
+                cache_dir: opts.cache_dir,
+                consensus: opts.consensus,
+                consensus_threshold: opts.consensus_threshold,
+                append_output: opts.append_output,
+                tie_policy: opts.tie_policy,
+                coord_map: opts.coord_map,
+                max_cost: opts.max_cost,
+                max_gap: opts.max_gap,
+                summary_only: opts.summary_only,
+                stream_output: opts.stream_output,
+                color: opts.color,
+                scheme: None,
             },
             hash_type,
             hash_shift: opts.hash_shift,
@@ -155,6 +1321,63 @@ impl From<PAStarOptions> for PAStarOpt {
             no_affinity: opts.no_affinity,
             thread_affinity,
             hybrid_conf,
+            compact_every: opts.compact_every,
+            presize: opts.presize,
+            max_nodes: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pastar_builder_defaults() {
+        let opt = PAStarOpt::builder().build().unwrap();
+        assert_eq!(opt.threads_num, num_cpus::get());
+        assert_eq!(opt.hash_type, HashType::FZorder);
+        assert_eq!(opt.hash_shift, HASH_SHIFT);
+        assert!(opt.common.output_file.is_none());
+    }
+
+    #[test]
+    fn test_pastar_builder_validation() {
+        assert!(PAStarOpt::builder().threads(0).build().is_err());
+
+        let hybrid = HybridCpu {
+            p_cores_num: 4,
+            p_cores_size: 2,
+            e_cores_num: 4,
+            e_cores_size: 1,
+        };
+        assert!(PAStarOpt::builder().threads(4).hybrid_conf(hybrid.clone()).build().is_err());
+        assert!(PAStarOpt::builder().threads(8).hybrid_conf(hybrid).build().is_ok());
+    }
+
+    #[test]
+    fn test_command_line_options_validated() {
+        use clap::Parser;
+        let validate = |extra: &[&str]| {
+            let args = ["msa_pastar", "--seq", "ACGT", "--seq", "AGT"].iter().chain(extra);
+            PAStarOpt::from(PAStarOptions::try_parse_from(args).unwrap()).validate()
+        };
+
+        assert!(validate(&["-t", "2"]).is_ok());
+        assert!(validate(&["-t", "0"]).is_err());
+        assert!(validate(&["-t", "2", "--p-cores-num", "4", "--p-cores-size", "1"]).is_err());
+        assert!(validate(&["-t", "4", "--p-cores-num", "2", "--p-cores-size", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_hash_type_parsed_by_name() {
+        use clap::Parser;
+        let parse = |name: &str| {
+            PAStarOptions::try_parse_from(["msa_pastar", "--seq", "ACGT", "--hash-type", name])
+        };
+
+        assert_eq!(PAStarOpt::from(parse("psum").unwrap()).hash_type, HashType::PSum);
+        assert_eq!(PAStarOpt::from(parse("Full-Sum").unwrap()).hash_type, HashType::FSum);
+        assert!(parse("fzordr").is_err());
+    }
+}
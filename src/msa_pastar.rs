@@ -7,68 +7,69 @@
 
 use clap::Parser;
 use astar_msa_rust::{
-    pastar,
-    cost::Cost,
-    heuristic_hpair::HeuristicHPair,
+    autotune,
+    build_info,
+    cli::{self, CliArgs},
+    coord_hash::CoordHash,
+    info,
     msa_options::{PAStarOptions, PAStarOpt},
-    read_fasta::read_fasta_file,
-    sequences::Sequences,
+    pastar,
     VERSION,
 };
 
 fn main() {
-    let args = PAStarOptions::parse();
-    
-    println!("MSA PA-Star version {}", VERSION);
-    println!("Input file: {}", args.input_file);
-    
-    // Set cost matrix
-    if args.nucleotide {
-        println!("Using nucleotide cost matrix");
-        Cost::set_cost_nuc();
-    } else {
-        println!("Using PAM250 cost matrix");
-        Cost::set_cost_pam250();
+    if build_info::verbose_version_requested(std::env::args()) {
+        print!("{}", build_info::build_info());
+        return;
     }
     
-    // Read FASTA file
-    if let Err(e) = read_fasta_file(&args.input_file) {
-        eprintln!("Error reading FASTA file: {}", e);
-        std::process::exit(1);
-    }
+    let args = PAStarOptions::parse();
+    let cli_args = CliArgs::from(&args);
+    cli::start(&cli_args, "MSA PA-Star");
     
-    let seq_num = Sequences::get_seq_num();
-    println!("Number of sequences: {}", seq_num);
+    let auto_hash = args.hash_type.is_none();
+    let autotune = args.autotune_hash.then(|| args.max_nodes.unwrap_or(autotune::DEFAULT_TRIAL_NODES));
+    let mut options = PAStarOpt::from(args);
     
-    if seq_num < 2 {
-        eprintln!("Error: Need at least 2 sequences");
-        std::process::exit(1);
+    // Scoring an existing alignment skips the search
+    if let Some(file) = &cli_args.score_only {
+        cli::score_only(file, &options.common);
     }
     
-    // Print sequence information
-    for i in 0..seq_num {
-        println!("Sequence {}: {} (length: {})",
-            i,
-            Sequences::get_seq_name(i),
-            Sequences::get_seq_len(i)
-        );
+    if let Err(e) = options.validate() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
     
-    // Initialize heuristic
-    println!("\nPhase 1: Initializing heuristic...");
-    HeuristicHPair::init();
+    let translation = cli::load(&cli_args);
     
     // Run PA-Star
-    println!("\nPerforming search with Parallel A-Star ({})", VERSION);
-    let options = PAStarOpt::from(args);
-    
-    match pastar::run_pastar_for_sequences(options) {
-        Ok(()) => {
-            println!("\nAlignment completed successfully!");
-        }
-        Err(e) => {
-            eprintln!("Error during alignment: {}", e);
-            std::process::exit(1);
+    info!("\nPerforming search with Parallel A-Star ({})", VERSION);
+    if let Some(max_nodes) = autotune {
+        info!("\nAutotuning hash with {} node trials", max_nodes);
+        match autotune::autotune_hash(&options, max_nodes) {
+            Ok(trials) => {
+                autotune::print_ranking(&trials);
+                options.hash_type = trials[0].hash_type;
+                options.hash_shift = trials[0].hash_shift;
+                info!("Using {} hash with shift {}\n",
+                         CoordHash::get_hash_name(options.hash_type), options.hash_shift);
+            }
+            Err(e) => {
+                eprintln!("Error during hash autotune: {}", e);
+                std::process::exit(1);
+            }
         }
+    } else if auto_hash {
+        info!("Hash type chosen automatically: {}", CoordHash::get_hash_name(options.hash_type));
     }
+    
+    let result = cli::run_search(&cli_args, &options.common, |common, cached| {
+        let run_options = PAStarOpt { common: common.clone(), ..options.clone() };
+        match cached {
+            true => pastar::run_pastar_for_sequences(run_options),
+            false => pastar::run_pastar_uncached(run_options),
+        }
+    });
+    cli::finish(&cli_args, result, translation, &options.common, Some(&options));
 }
@@ -8,10 +8,11 @@
 
 use std::fmt;
 use crate::coord::Coord;
-use crate::cost::Cost;
+use crate::cost::{ScoringScheme, GAP};
 use crate::sequences::Sequences;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node<const N: usize> {
     pub pos: Coord<N>,
     f: i32,  // priority (g + h)
@@ -59,6 +60,13 @@ impl<const N: usize> Node<N> {
         self.parenti
     }
 
+    /// Order between two nodes for the same coordinate: lower g wins, and
+    /// equal g keeps the smaller parent index, so the reconstructed path
+    /// doesn't depend on expansion order
+    pub fn better_than(&self, other: &Self) -> bool {
+        (self.g, self.parenti) < (other.g, other.parenti)
+    }
+
     pub fn get_parent(&self) -> Coord<N> {
         // parenti is a bitmap indicating which dimensions were incremented
         let mut parent_pos = self.pos;
@@ -85,98 +93,98 @@ impl<const N: usize> Node<N> {
         self.parenti = parenti;
     }
 
-    /// Check if coordinate is within boundaries
-    fn border_check(&self, c: &Coord<N>) -> bool {
-        for i in 0..N {
-            if c.get(i) > Sequences::get_seq_len(i) as u16 {
-                return false;
-            }
-        }
-        true
-    }
-
     /// Calculate pairwise alignment cost
-    fn pair_cost(&self, neigh_num: usize, s1: usize, s2: usize) -> i32 {
+    fn pair_cost(&self, scheme: &ScoringScheme, neigh_num: usize, s1: usize, s2: usize) -> i32 {
         let pos1 = self.pos.get(s1) as usize;
         let pos2 = self.pos.get(s2) as usize;
 
         // Check which dimension is being incremented
         let inc_s1 = (neigh_num & (1 << s1)) != 0;
         let inc_s2 = (neigh_num & (1 << s2)) != 0;
+        if (inc_s1 && pos1 >= Sequences::get_seq_len(s1)) || (inc_s2 && pos2 >= Sequences::get_seq_len(s2)) {
+            return i32::MAX;
+        }
+
+        // What each sequence puts in the new column: its next residue or a gap
+        let c1 = if inc_s1 { Sequences::get_seq_char(s1, pos1) } else { GAP };
+        let c2 = if inc_s2 { Sequences::get_seq_char(s2, pos2) } else { GAP };
+        let cost = scheme.cost(c1, c2);
 
         match (inc_s1, inc_s2) {
             (true, true) => {
-                // Both sequences advance - match/mismatch
-                if pos1 < Sequences::get_seq_len(s1) && pos2 < Sequences::get_seq_len(s2) {
-                    let c1 = Sequences::get_seq_char(s1, pos1);
-                    let c2 = Sequences::get_seq_char(s2, pos2);
-                    Cost::cost(c1, c2)
-                } else {
-                    i32::MAX
-                }
-            }
-            (true, false) => {
-                // Only s1 advances - gap in s2
-                Cost::get_gap_cost()
-            }
-            (false, true) => {
-                // Only s2 advances - gap in s1
-                Cost::get_gap_cost()
-            }
-            (false, false) => {
-                // Neither advances - gap in both (shouldn't happen in practice)
-                Cost::get_gap_gap()
+                let l1 = Sequences::get_ss_label(s1, pos1);
+                let l2 = Sequences::get_ss_label(s2, pos2);
+                scheme.structure().apply(cost, l1, l2)
             }
+            // A gap profile replaces the matrix's cost of a gap in its sequence
+            (true, false) => Sequences::get_gap_profile_cost(s2, pos2).unwrap_or(cost),
+            (false, true) => Sequences::get_gap_profile_cost(s1, pos1).unwrap_or(cost),
+            (false, false) => cost,
         }
     }
 
-    /// Get all valid neighbors of this node
-    pub fn get_neighbors(&self) -> Vec<Node<N>> {
-        let mut neighbors = Vec::new();
+    /// Valid moves out of this node as `(neigh_num, cost, new_pos)` under
+    /// `scheme`, without building the neighbor nodes
+    pub fn neighbor_moves<'a>(&'a self, scheme: &'a ScoringScheme) -> impl Iterator<Item = (u32, i32, Coord<N>)> + 'a {
+        let final_coord = Sequences::get_final_coord::<N>();
         
-        // Generate all 2^N - 1 possible neighbors (excluding staying in place)
-        for neigh_num in 1..(1 << N) {
-            let mut new_pos = self.pos;
-            let mut valid = true;
-
-            // Create new coordinate by incrementing selected dimensions
-            for dim in 0..N {
-                if (neigh_num & (1 << dim)) != 0 {
-                    new_pos.set(dim, new_pos.get(dim) + 1);
+        // All 2^N - 1 possible moves (excluding staying in place), in
+        // order. One scratch coordinate follows them: each move only flips
+        // the dimensions whose bit differs from the previous move's.
+        let mut new_pos = self.pos;
+        let mut prev = 0u32;
+        (1..(1u32 << N)).filter_map(move |neigh_num| {
+            let mut changed = prev ^ neigh_num;
+            while changed != 0 {
+                let dim = changed.trailing_zeros() as usize;
+                if neigh_num & (1 << dim) != 0 {
+                    new_pos.incr(dim);
+                } else {
+                    new_pos.decr(dim);
                 }
+                changed &= changed - 1;
             }
+            prev = neigh_num;
 
-            // Check boundaries
-            if !self.border_check(&new_pos) {
-                continue;
+            // Skip successors that overshoot the goal
+            if !new_pos.is_goal_reachable(&final_coord) {
+                return None;
             }
 
-            // Calculate cost for this neighbor
-            let mut cost = 0;
-            
-            // Sum costs for all sequence pairs
-            for s1 in 0..N {
-                for s2 in (s1 + 1)..N {
-                    let pair_cost = self.pair_cost(neigh_num, s1, s2);
-                    if pair_cost == i32::MAX {
-                        valid = false;
-                        break;
-                    }
-                    cost += pair_cost;
-                }
-                if !valid {
-                    break;
-                }
-            }
+            self.move_cost(scheme, neigh_num as usize).map(|cost| (neigh_num, cost, new_pos))
+        })
+    }
+
+    /// Get all valid neighbors of this node
+    pub fn get_neighbors(&self, scheme: &ScoringScheme) -> Vec<Node<N>> {
+        self.neighbor_moves(scheme)
+            .map(|(neigh_num, cost, new_pos)| Node::with_values(self.g + cost, new_pos, neigh_num as i32))
+            .collect()
+    }
 
-            if valid {
-                let new_g = self.g + cost;
-                let new_node = Node::with_values(new_g, new_pos, neigh_num as i32);
-                neighbors.push(new_node);
+    /// Sum-of-pairs cost under `scheme` of the move that advances the
+    /// dimensions set in `neigh_num`
+    pub fn move_cost(&self, scheme: &ScoringScheme, neigh_num: usize) -> Option<i32> {
+        let mut cost = 0;
+        
+        // Sum costs for all sequence pairs
+        for s1 in 0..N {
+            for s2 in (s1 + 1)..N {
+                let pair_cost = self.pair_cost(scheme, neigh_num, s1, s2);
+                if pair_cost == i32::MAX {
+                    return None;
+                }
+                cost += pair_cost;
             }
         }
+        
+        Some(cost)
+    }
+}
 
-        neighbors
+impl<const N: usize> Default for Node<N> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -212,4 +220,97 @@ mod tests {
         node.set_f(25);
         assert_eq!(node.get_h(), 15);
     }
+
+    #[test]
+    fn test_node_better_than() {
+        let pos = Coord::new(1);
+        let node: Node<2> = Node::with_values(10, pos, 2);
+        assert!(Node::with_values(9, pos, 3).better_than(&node));
+        assert!(Node::with_values(10, pos, 1).better_than(&node));
+        assert!(!Node::with_values(10, pos, 2).better_than(&node));
+        assert!(!Node::with_values(11, pos, 1).better_than(&node));
+    }
+
+    #[test]
+    fn test_neighbors_stay_within_goal() {
+        let _guard = crate::test_lock();
+        let scheme = ScoringScheme::nucleotide();
+        Sequences::clear();
+        Sequences::set_seq("ACG".to_string()).unwrap();
+        Sequences::set_seq("A".to_string()).unwrap();
+
+        let final_coord: Coord<2> = Sequences::get_final_coord();
+        let node: Node<2> = Node::with_values(0, Coord::from_array([1, 1]), 0);
+        let neighbors = node.get_neighbors(&scheme);
+
+        // Only the move along the first sequence is left
+        assert_eq!(neighbors.len(), 1);
+        assert!(neighbors.iter().all(|n| n.pos.is_goal_reachable(&final_coord)));
+    }
+
+    #[test]
+    fn test_neighbor_moves_match_neighbors() {
+        let _guard = crate::test_lock();
+        let scheme = ScoringScheme::pam250();
+        Sequences::clear();
+        for seq in ["MKV", "MV", "KVL"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+
+        for (pos, count) in [([0, 0, 0], 7), ([1, 1, 2], 7), ([3, 1, 2], 3), ([2, 2, 3], 1)] {
+            let node: Node<3> = Node::with_values(7, Coord::from_array(pos), 0);
+            let moves: Vec<_> = node.neighbor_moves(&scheme).collect();
+            let neighbors = node.get_neighbors(&scheme);
+            assert_eq!((moves.len(), neighbors.len()), (count, count));
+            for ((neigh_num, cost, new_pos), neighbor) in moves.into_iter().zip(&neighbors) {
+                assert_eq!(neighbor.get_parenti(), neigh_num as i32);
+                assert_eq!(neighbor.get_g(), 7 + cost);
+                assert_eq!(neighbor.pos, new_pos);
+                assert_eq!(node.move_cost(&scheme, neigh_num as usize), Some(cost));
+            }
+        }
+    }
+
+    #[test]
+    fn test_in_place_moves_match_copies() {
+        let _guard = crate::test_lock();
+        let scheme = ScoringScheme::pam250();
+        Sequences::clear();
+        for seq in ["MKV", "MV", "KVL", "W"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        let final_coord: Coord<4> = Sequences::get_final_coord();
+
+        // Every cell of the lattice, each move built from a fresh copy
+        for cell in 0..4 * 3 * 4 * 2 {
+            let pos = Coord::from_array([cell % 4, cell / 4 % 3, cell / 12 % 4, cell / 48].map(|c| c as u16));
+            let node: Node<4> = Node::with_values(0, pos, 0);
+            let expected: Vec<_> = (1..16u32)
+                .filter_map(|neigh_num| {
+                    let mut new_pos = pos;
+                    for dim in (0..4).filter(|dim| neigh_num & (1 << dim) != 0) {
+                        new_pos = new_pos.neigh(dim);
+                    }
+                    new_pos.is_goal_reachable(&final_coord).then_some(())?;
+                    node.move_cost(&scheme, neigh_num as usize).map(|cost| (neigh_num, cost, new_pos))
+                })
+                .collect();
+            assert_eq!(node.neighbor_moves(&scheme).collect::<Vec<_>>(), expected, "at {}", pos);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut node: Node<3> = Node::with_values(12, Coord::from_array([4, 0, 7]), 5);
+        node.set_f(20);
+
+        let json = serde_json::to_string(&node).unwrap();
+        let back: Node<3> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.pos, node.pos);
+        assert_eq!((back.get_f(), back.get_g(), back.get_parenti()), (20, 12, 5));
+
+        // The dimension count is checked
+        assert!(serde_json::from_str::<Node<2>>(&json).is_err());
+    }
 }
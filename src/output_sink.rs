@@ -0,0 +1,205 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Destinations for finished alignments, so callers decide where
+ * results go instead of the search writing files itself
+ */
+
+use std::io::Write;
+use crate::alignment::{Alignment, OutputFormat};
+use crate::backtrace::write_fasta;
+use crate::msa_options::AStarOpt;
+
+/// Receives each finished alignment. Sequence names are read from
+/// `Sequences`, which still holds the aligned input when `emit` runs.
+pub trait OutputSink {
+    fn emit(&mut self, alignment: &Alignment) -> Result<(), String>;
+}
+
+/// Collects the alignments in memory
+impl OutputSink for Vec<Alignment> {
+    fn emit(&mut self, alignment: &Alignment) -> Result<(), String> {
+        self.push(alignment.clone());
+        Ok(())
+    }
+}
+
+/// Writes every alignment to one file, created or emptied when the sink
+/// is made, each flushed as soon as it's written
+pub struct FastaFileSink {
+    out: std::io::BufWriter<std::fs::File>,
+    filename: String,
+    wrap: usize,
+    format: OutputFormat,
+    match_gap_fraction: f64,
+}
+
+impl FastaFileSink {
+    /// Create `filename`, written with the format and wrapping of `options`
+    pub fn new(filename: &str, options: &AStarOpt) -> Result<Self, String> {
+        let file = std::fs::File::create(filename)
+            .map_err(|e| format!("Error writing FASTA file {}: {}", filename, e))?;
+        Ok(FastaFileSink {
+            out: std::io::BufWriter::new(file),
+            filename: filename.to_string(),
+            wrap: options.wrap,
+            format: options.format,
+            match_gap_fraction: options.match_gap_fraction,
+        })
+    }
+}
+
+impl OutputSink for FastaFileSink {
+    fn emit(&mut self, alignment: &Alignment) -> Result<(), String> {
+        let rows = format_rows(alignment, self.format, self.match_gap_fraction);
+        write_fasta(&mut self.out, &rows, self.wrap)
+            .and_then(|_| self.out.flush())
+            .map_err(|e| format!("Error writing FASTA file {}: {}", self.filename, e))
+    }
+}
+
+/// Appends every alignment to one multi-record FASTA file kept open for
+/// the whole batch, each group after a `; group <n>` separator line and
+/// flushed as soon as it's written
+pub struct AppendFastaSink {
+    out: std::io::BufWriter<std::fs::File>,
+    filename: String,
+    group: usize,
+    wrap: usize,
+    format: OutputFormat,
+    match_gap_fraction: f64,
+}
+
+impl AppendFastaSink {
+    /// Open `filename` for appending, creating it if needed. Group numbers
+    /// continue after the groups already in the file.
+    pub fn open(filename: &str, options: &AStarOpt) -> Result<Self, String> {
+        let group = match std::fs::read_to_string(filename) {
+            Ok(text) => text.lines().filter(|line| line.starts_with(GROUP_SEPARATOR)).count(),
+            Err(_) => 0,
+        };
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)
+            .map_err(|e| format!("Can't open {} for appending: {}", filename, e))?;
+        Ok(AppendFastaSink {
+            out: std::io::BufWriter::new(file),
+            filename: filename.to_string(),
+            group,
+            wrap: options.wrap,
+            format: options.format,
+            match_gap_fraction: options.match_gap_fraction,
+        })
+    }
+}
+
+/// Start of the line before each group of `AppendFastaSink`
+const GROUP_SEPARATOR: &str = "; group ";
+
+impl OutputSink for AppendFastaSink {
+    fn emit(&mut self, alignment: &Alignment) -> Result<(), String> {
+        let rows = format_rows(alignment, self.format, self.match_gap_fraction);
+        writeln!(self.out, "{}{}", GROUP_SEPARATOR, self.group)
+            .and_then(|_| write_fasta(&mut self.out, &rows, self.wrap))
+            .and_then(|_| self.out.flush())
+            .map_err(|e| format!("Error appending to {}: {}", self.filename, e))?;
+        self.group += 1;
+        Ok(())
+    }
+}
+
+/// Prints each alignment to stdout as FASTA
+pub struct StdoutSink {
+    pub wrap: usize,
+}
+
+impl OutputSink for StdoutSink {
+    fn emit(&mut self, alignment: &Alignment) -> Result<(), String> {
+        let mut out = std::io::stdout().lock();
+        write_fasta(&mut out, alignment.seqs(), self.wrap)
+            .and_then(|_| out.flush())
+            .map_err(|e| format!("Error writing to stdout: {}", e))
+    }
+}
+
+/// Rows of `alignment` in the given output format
+pub fn format_rows(alignment: &Alignment, format: OutputFormat, match_gap_fraction: f64) -> Vec<String> {
+    match format {
+        OutputFormat::Fasta => alignment.seqs().to_vec(),
+        OutputFormat::A2m => alignment.to_a2m(match_gap_fraction),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::align::{align_batch, AlignParams};
+
+    #[test]
+    fn test_batch_into_memory_and_file() {
+        let _guard = crate::test_lock();
+        let inputs = vec![
+            vec!["ACGT".to_string(), "AGT".to_string()],
+            vec!["ACGTA".to_string(), "ACTA".to_string(), "AGTA".to_string()],
+        ];
+
+        let mut collected: Vec<Alignment> = Vec::new();
+        align_batch(&inputs, &AlignParams::nucleotide(), &mut collected).unwrap();
+        assert_eq!(collected.len(), 2);
+        assert_eq!(collected[0].seqs(), ["ACGT", "A-GT"]);
+        assert_eq!(collected[1].seq_num(), 3);
+
+        let path = std::env::temp_dir().join(format!("output_sink_{}.fasta", std::process::id()));
+        let options = AStarOpt::builder().wrap(0).build();
+        let mut sink = FastaFileSink::new(path.to_str().unwrap(), &options).unwrap();
+        align_batch(&inputs[..1], &AlignParams::nucleotide(), &mut sink).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), ">Sequence 1\nACGT\n>Sequence 2\nA-GT\n");
+
+        // Later alignments follow the first in the same file
+        sink.emit(&collected[0]).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), ">Sequence 1\nACGT\n>Sequence 2\nA-GT\n".repeat(2));
+        // A new sink starts the file over
+        drop(sink);
+        FastaFileSink::new(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_append_groups() {
+        let _guard = crate::test_lock();
+        let inputs = vec![
+            vec!["ACGT".to_string(), "AGT".to_string()],
+            vec!["TTGA".to_string(), "TGA".to_string()],
+        ];
+        let path = std::env::temp_dir().join(format!("output_sink_append_{}.fasta", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let options = AStarOpt::builder().wrap(0).build();
+
+        let mut sink = AppendFastaSink::open(path, &options).unwrap();
+        align_batch(&inputs, &AlignParams::nucleotide(), &mut sink).unwrap();
+        drop(sink);
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "; group 0\n>Sequence 1\nACGT\n>Sequence 2\nA-GT\n; group 1\n>Sequence 1\nTTGA\n>Sequence 2\nT-GA\n"
+        );
+
+        // A later run keeps numbering after the groups already there
+        let mut sink = AppendFastaSink::open(path, &options).unwrap();
+        align_batch(&inputs[..1], &AlignParams::nucleotide(), &mut sink).unwrap();
+        assert!(std::fs::read_to_string(path).unwrap().ends_with("; group 2\n>Sequence 1\nACGT\n>Sequence 2\nA-GT\n"));
+
+        // Appending through the options numbers the batch's groups in order
+        let options = crate::msa_options::PAStarOpt::builder().threads(1).wrap(0).append_output(path);
+        let mut collected: Vec<Alignment> = Vec::new();
+        align_batch(&inputs, &AlignParams::nucleotide().options(options), &mut collected).unwrap();
+        assert_eq!(collected.len(), 2);
+        assert!(std::fs::read_to_string(path).unwrap().ends_with(
+            "; group 3\n>Sequence 1\nACGT\n>Sequence 2\nA-GT\n; group 4\n>Sequence 1\nTTGA\n>Sequence 2\nT-GA\n"
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+}
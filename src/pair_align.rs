@@ -5,28 +5,112 @@
  * \brief Pairwise sequence alignment using dynamic programming
  */
 
-use crate::cost::Cost;
+use crate::alignment::Alignment;
+use crate::backtrace;
+use crate::cost::{Cost, ScoringScheme};
+use crate::msa_options::AStarOpt;
+use crate::sequences::{Region, Sequences};
+use crate::time_counter::TimeCounter;
 use std::cmp::min;
+use std::ops::Range;
 
 pub type Pair = (usize, usize);
 
+#[derive(Clone, PartialEq)]
 pub struct PairAlign {
     pair: Pair,
+    /// Rows follow the longer sequence, so the shorter one is contiguous
     matrix: Vec<Vec<i32>>,
+    /// Whether the sequences are stored swapped (`s1` is the shorter one).
+    /// Everything below is in stored order; the public methods take and
+    /// return the caller's.
+    transposed: bool,
+    /// Same sequence twice (see `is_symmetric`): the matrix equals its
+    /// transpose, so row `i` only holds columns `i..`
+    symmetric: bool,
     s1_len: usize,
     s2_len: usize,
+    /// Cost of a gap in each sequence after its first `i` residues
+    gaps1: Vec<i32>,
+    gaps2: Vec<i32>,
+    /// Structure label of each residue, empty if a sequence has none
+    ss1: Vec<u8>,
+    ss2: Vec<u8>,
+    /// Costs the matrix was filled with
+    scheme: ScoringScheme,
 }
 
 impl PairAlign {
     pub fn new(pair: Pair, s1: &[u8], s2: &[u8]) -> Self {
+        let gap = Cost::get_gap_cost();
+        Self::with_gap_costs(pair, s1, s2, vec![gap; s1.len() + 1], vec![gap; s2.len() + 1])
+    }
+
+    /// Align with a gap cost per position, `gaps1[i]` for a gap in `s1`
+    /// after its first `i` residues (see `Sequences::get_gap_costs`)
+    pub fn with_gap_costs(pair: Pair, s1: &[u8], s2: &[u8], gaps1: Vec<i32>, gaps2: Vec<i32>) -> Self {
+        Self::with_structure(pair, s1, s2, gaps1, gaps2, Vec::new(), Vec::new())
+    }
+
+    /// Like `with_gap_costs`, also scoring residue pairs by their structure
+    /// labels with `Cost::structure_scoring` (see `Sequences::get_ss_labels`)
+    pub fn with_structure(
+        pair: Pair,
+        s1: &[u8],
+        s2: &[u8],
+        gaps1: Vec<i32>,
+        gaps2: Vec<i32>,
+        ss1: Vec<u8>,
+        ss2: Vec<u8>,
+    ) -> Self {
+        Self::with_scheme(&Cost::matrix_snapshot(), pair, s1, s2, gaps1, gaps2, ss1, ss2)
+    }
+
+    /// Like `with_structure`, with the costs of `scheme` instead of the
+    /// active ones, so alignments under different schemes can run at once
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_scheme(
+        scheme: &ScoringScheme,
+        pair: Pair,
+        s1: &[u8],
+        s2: &[u8],
+        gaps1: Vec<i32>,
+        gaps2: Vec<i32>,
+        ss1: Vec<u8>,
+        ss2: Vec<u8>,
+    ) -> Self {
         let s1_len = s1.len();
         let s2_len = s2.len();
+        assert!(gaps1.len() == s1_len + 1 && gaps2.len() == s2_len + 1);
+        assert!(ss1.is_empty() || ss1.len() == s1_len);
+        assert!(ss2.is_empty() || ss2.len() == s2_len);
+        
+        let transposed = s1_len < s2_len;
+        let (s1, s2, gaps1, gaps2, ss1, ss2) = if transposed {
+            (s2, s1, gaps2, gaps1, ss2, ss1)
+        } else {
+            (s1, s2, gaps1, gaps2, ss1, ss2)
+        };
+        
+        let symmetric = is_symmetric(scheme, s1, s2, &gaps1, &gaps2, &ss1, &ss2);
+        let matrix = if symmetric {
+            (0..=s1.len()).map(|i| vec![0; s1.len() + 1 - i]).collect()
+        } else {
+            vec![vec![0; s2.len() + 1]; s1.len() + 1]
+        };
         
         let mut align = PairAlign {
             pair,
-            matrix: vec![vec![0; s2_len + 1]; s1_len + 1],
-            s1_len,
-            s2_len,
+            matrix,
+            transposed,
+            symmetric,
+            s1_len: s1.len(),
+            s2_len: s2.len(),
+            gaps1,
+            gaps2,
+            ss1,
+            ss2,
+            scheme: scheme.clone(),
         };
         
         align.align(s1, s2);
@@ -34,19 +118,79 @@ impl PairAlign {
     }
 
     fn align(&mut self, s1: &[u8], s2: &[u8]) {
-        // Initialize borders
+        if self.symmetric {
+            self.align_symmetric(s1);
+            return;
+        }
+        // Nothing but borders to fill
+        if self.s1_len == 0 || self.s2_len == 0 {
+            self.fill_borders();
+            return;
+        }
+
+        #[cfg(feature = "simd")]
+        self.align_antidiagonal(s1, s2);
+
+        #[cfg(not(feature = "simd"))]
+        self.align_scalar(s1, s2);
+    }
+
+    /// Matrix cost of stored residues `c1` of `s1` and `c2` of `s2`, looked
+    /// up in the caller's order
+    fn matrix_cost(&self, c1: u8, c2: u8) -> i32 {
+        if self.transposed { self.scheme.cost(c2, c1) } else { self.scheme.cost(c1, c2) }
+    }
+
+    /// Matrix `cost` of residues `i` and `j` adjusted by their structure
+    /// labels
+    fn residue_cost(&self, cost: i32, i: usize, j: usize) -> i32 {
+        if self.ss1.is_empty() || self.ss2.is_empty() {
+            return cost;
+        }
+        self.scheme.structure().apply(cost, self.ss1[i], self.ss2[j])
+    }
+
+    /// Fill the border rows of the DP matrix
+    fn fill_borders(&mut self) {
         self.matrix[self.s1_len][self.s2_len] = 0;
         
         // Fill last row
         for j in (0..self.s2_len).rev() {
-            self.matrix[self.s1_len][j] = self.matrix[self.s1_len][j + 1] + Cost::get_gap_cost();
+            self.matrix[self.s1_len][j] = self.matrix[self.s1_len][j + 1] + self.gaps1[self.s1_len];
         }
         
         // Fill last column
         for i in (0..self.s1_len).rev() {
-            self.matrix[i][self.s2_len] = self.matrix[i + 1][self.s2_len] + Cost::get_gap_cost();
+            self.matrix[i][self.s2_len] = self.matrix[i + 1][self.s2_len] + self.gaps2[self.s2_len];
         }
-        
+    }
+
+    /// Fill the upper triangle of a symmetric matrix, half the cells of a
+    /// full fill. Cell `(i, j)` lives at `matrix[i][j - i]`, and the one
+    /// cell below the diagonal the recurrence reads, `(i + 1, i)`, is its
+    /// mirror `(i, i + 1)`.
+    fn align_symmetric(&mut self, s: &[u8]) {
+        let n = self.s1_len;
+        let costs = self.scheme.clone();
+
+        self.matrix[n][0] = 0;
+        for i in (0..n).rev() {
+            self.matrix[i][n - i] = self.matrix[i + 1][n - i - 1] + self.gaps2[n];
+            for j in (i..n).rev() {
+                let up = if j > i { self.matrix[i + 1][j - i - 1] } else { self.matrix[i][1] };
+                let left = self.matrix[i][j + 1 - i];
+                let diag = self.matrix[i + 1][j - i];
+                let c2 = diag + self.residue_cost(costs.cost(s[i], s[j]), i, j);
+                self.matrix[i][j - i] = min(c2, min(up + self.gaps2[j], left + self.gaps1[i]));
+            }
+        }
+    }
+
+    /// Cell-by-cell fill, used when the `simd` feature is disabled
+    #[cfg_attr(feature = "simd", allow(dead_code))]
+    fn align_scalar(&mut self, s1: &[u8], s2: &[u8]) {
+        self.fill_borders();
+
         // Fill the rest of the matrix
         for i in (0..self.s1_len).rev() {
             for j in (0..self.s2_len).rev() {
@@ -55,25 +199,141 @@ impl PairAlign {
         }
     }
 
+    /// Anti-diagonal fill: every cell with the same `i + j` only depends on
+    /// the previous two diagonals, so each diagonal is processed in fixed
+    /// size lanes that the compiler can vectorize
+    #[cfg(feature = "simd")]
+    fn align_antidiagonal(&mut self, s1: &[u8], s2: &[u8]) {
+        const LANES: usize = 8;
+
+        self.fill_borders();
+        if self.s1_len == 0 || self.s2_len == 0 {
+            return;
+        }
+
+        let costs = &self.scheme;
+
+        // Substitution costs of every s1 residue against all of s2, so the
+        // inner loop does not go through the cost matrix lock
+        let mut profiles: Vec<Option<Vec<i32>>> = vec![None; 256];
+        for &c in s1 {
+            profiles[c as usize].get_or_insert_with(|| {
+                s2.iter()
+                    .map(|&d| if self.transposed { costs.cost(d, c) } else { costs.cost(c, d) })
+                    .collect()
+            });
+        }
+
+        let mut up = [0i32; LANES];
+        let mut left = [0i32; LANES];
+        let mut gap_up = [0i32; LANES];
+        let mut gap_left = [0i32; LANES];
+        let mut diag = [0i32; LANES];
+        let mut out = [0i32; LANES];
+
+        for d in (0..self.s1_len + self.s2_len - 1).rev() {
+            // Cells (i, d - i) inside the matrix, walked by increasing i
+            let i_min = d.saturating_sub(self.s2_len - 1);
+            let i_max = d.min(self.s1_len - 1);
+
+            let mut i0 = i_min;
+            while i0 <= i_max {
+                let lanes = (i_max - i0 + 1).min(LANES);
+
+                for k in 0..lanes {
+                    let i = i0 + k;
+                    let j = d - i;
+                    let profile = profiles[s1[i] as usize].as_ref().unwrap();
+                    up[k] = self.matrix[i + 1][j];
+                    left[k] = self.matrix[i][j + 1];
+                    gap_up[k] = self.gaps2[j];
+                    gap_left[k] = self.gaps1[i];
+                    diag[k] = self.matrix[i + 1][j + 1] + self.residue_cost(profile[j], i, j);
+                }
+
+                for k in 0..LANES {
+                    out[k] = min(diag[k], min(up[k] + gap_up[k], left[k] + gap_left[k]));
+                }
+
+                for (k, &value) in out.iter().enumerate().take(lanes) {
+                    let i = i0 + k;
+                    self.matrix[i][d - i] = value;
+                }
+
+                i0 += lanes;
+            }
+        }
+    }
+
     fn pair_cost(&mut self, i: usize, j: usize, s1: &[u8], s2: &[u8]) {
-        let c0 = self.matrix[i + 1][j] + Cost::get_gap_cost();
-        let c1 = self.matrix[i][j + 1] + Cost::get_gap_cost();
+        let c0 = self.matrix[i + 1][j] + self.gaps2[j];
+        let c1 = self.matrix[i][j + 1] + self.gaps1[i];
         let min_value = min(c0, c1);
         
-        let c2 = self.matrix[i + 1][j + 1] + Cost::cost(s1[i], s2[j]);
+        let c2 = self.matrix[i + 1][j + 1] + self.residue_cost(self.matrix_cost(s1[i], s2[j]), i, j);
         let min_value = min(c2, min_value);
         
         self.matrix[i][j] = min_value;
     }
 
+    /// Cost of aligning the caller's `s1[i..]` with `s2[j..]`
     pub fn get_score(&self, i: usize, j: usize) -> i32 {
+        let (i, j) = if self.transposed { (j, i) } else { (i, j) };
         if i <= self.s1_len && j <= self.s2_len {
-            self.matrix[i][j]
+            self.cell(i, j)
         } else {
             0
         }
     }
 
+    /// Matrix value at stored `(i, j)`, whatever the layout
+    fn cell(&self, i: usize, j: usize) -> i32 {
+        if self.symmetric {
+            let (i, j) = (i.min(j), i.max(j));
+            self.matrix[i][j - i]
+        } else {
+            self.matrix[i][j]
+        }
+    }
+
+    /// Rebuild one optimal alignment of `s1` and `s2` from the filled matrix,
+    /// as two equal length rows with `-` for gaps
+    pub fn traceback(&self, s1: &[u8], s2: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        if self.transposed {
+            let (row2, row1) = self.traceback_stored(s2, s1);
+            (row1, row2)
+        } else {
+            self.traceback_stored(s1, s2)
+        }
+    }
+
+    fn traceback_stored(&self, s1: &[u8], s2: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut row1 = Vec::with_capacity(self.s1_len + self.s2_len);
+        let mut row2 = Vec::with_capacity(self.s1_len + self.s2_len);
+        let (mut i, mut j) = (0, 0);
+
+        while i < self.s1_len || j < self.s2_len {
+            let score = self.cell(i, j);
+            if i < self.s1_len && j < self.s2_len
+                && score == self.cell(i + 1, j + 1) + self.residue_cost(self.matrix_cost(s1[i], s2[j]), i, j) {
+                row1.push(s1[i]);
+                row2.push(s2[j]);
+                i += 1;
+                j += 1;
+            } else if i < self.s1_len && score == self.cell(i + 1, j) + self.gaps2[j] {
+                row1.push(s1[i]);
+                row2.push(b'-');
+                i += 1;
+            } else {
+                row1.push(b'-');
+                row2.push(s2[j]);
+                j += 1;
+            }
+        }
+
+        (row1, row2)
+    }
+
     pub fn get_pair(&self) -> Pair {
         self.pair
     }
@@ -83,6 +343,135 @@ impl PairAlign {
     }
 }
 
+/// Whether aligning `s1` with `s2` gives a matrix equal to its transpose:
+/// the same sequence twice, with the same gap costs and labels, under costs
+/// symmetric on its residues
+fn is_symmetric(costs: &ScoringScheme, s1: &[u8], s2: &[u8], gaps1: &[i32], gaps2: &[i32], ss1: &[u8], ss2: &[u8]) -> bool {
+    if s1 != s2 || gaps1 != gaps2 || ss1 != ss2 {
+        return false;
+    }
+    let mut residues = s1.to_vec();
+    residues.sort_unstable();
+    residues.dedup();
+    residues.iter().all(|&a| residues.iter().all(|&b| costs.cost(a, b) == costs.cost(b, a)))
+}
+
+/// Best scoring sub-alignment of two sequences
+pub struct LocalAlign {
+    /// Aligned part of each sequence
+    pub range1: Range<usize>,
+    pub range2: Range<usize>,
+    /// Sum of `reward - cost` over residue pairs, minus the gap costs
+    pub score: i32,
+    pub row1: Vec<u8>,
+    pub row2: Vec<u8>,
+}
+
+/// Per pair reward of local alignment, one above the worst identity cost
+/// so every identity between the sequences scores positive
+pub fn local_reward(costs: &ScoringScheme, s1: &[u8], s2: &[u8]) -> i32 {
+    s1.iter().chain(s2).map(|&c| costs.cost(c, c)).max().unwrap_or(0) + 1
+}
+
+/// Smith-Waterman local alignment on `costs`: residue pairs score
+/// `local_reward - cost`, gaps `-gap_cost`, and the alignment may start and
+/// end anywhere. Among equal scores the shortest alignment is returned.
+pub fn local_align(costs: &ScoringScheme, s1: &[u8], s2: &[u8]) -> LocalAlign {
+    let gap = costs.gap_cost();
+    let reward = local_reward(costs, s1, s2);
+    let (len1, len2) = (s1.len(), s2.len());
+
+    // Negated score of the best alignment starting at (i, j), 0 when
+    // stopping right away is better
+    let mut matrix = vec![vec![0i32; len2 + 1]; len1 + 1];
+    let mut best = (0, len1, len2);
+    for i in (0..len1).rev() {
+        for j in (0..len2).rev() {
+            let diag = matrix[i + 1][j + 1] + costs.cost(s1[i], s2[j]) - reward;
+            let value = min(0, min(diag, min(matrix[i + 1][j], matrix[i][j + 1]) + gap));
+            matrix[i][j] = value;
+            if value < best.0 {
+                best = (value, i, j);
+            }
+        }
+    }
+
+    let (score, start1, start2) = best;
+    let (mut i, mut j) = (start1, start2);
+    let (mut row1, mut row2) = (Vec::new(), Vec::new());
+    while matrix[i][j] < 0 {
+        let value = matrix[i][j];
+        if value == matrix[i + 1][j + 1] + costs.cost(s1[i], s2[j]) - reward {
+            row1.push(s1[i]);
+            row2.push(s2[j]);
+            i += 1;
+            j += 1;
+        } else if value == matrix[i + 1][j] + gap {
+            row1.push(s1[i]);
+            row2.push(b'-');
+            i += 1;
+        } else {
+            row1.push(b'-');
+            row2.push(s2[j]);
+            j += 1;
+        }
+    }
+
+    LocalAlign {
+        range1: start1..i,
+        range2: start2..j,
+        score: -score,
+        row1,
+        row2,
+    }
+}
+
+/// Local alignment of the two loaded sequences. Each sequence is cut down
+/// to its aligned part, with the range appended to its name, so the report
+/// describes the sub-alignment.
+pub fn run_local_alignment(options: &AStarOpt) -> Result<Alignment, String> {
+    let _timer = TimeCounter::new("\nPhase 2: Local alignment running time:");
+
+    let seq_num = Sequences::get_seq_num();
+    if seq_num != 2 {
+        return Err(format!("Local mode aligns exactly 2 sequences, got {}", seq_num));
+    }
+
+    let local = local_align(&options.costs(), &Sequences::get_seq_slice(0), &Sequences::get_seq_slice(1));
+    if local.score == 0 {
+        return Err("No local alignment with a positive score".to_string());
+    }
+    crate::info!("Local alignment score: {}", local.score);
+
+    for (index, range) in [(0, &local.range1), (1, &local.range2)] {
+        Sequences::restrict_to_region(&Region { index, start: range.start, end: range.end })?;
+    }
+
+    let rows = [local.row1, local.row2]
+        .into_iter()
+        .map(|row| String::from_utf8_lossy(&row).to_string())
+        .collect();
+    let alignment = Alignment::new(Sequences::restore_input_order(rows));
+    backtrace::report_alignment(&alignment, options)?;
+    Ok(alignment)
+}
+
+/// Cost of aligning sequence `index` with its own reverse, and with itself
+/// for comparison: the two are equal for a palindrome, and close for a
+/// sequence with inverted repeats. The loaded sequences are left as they were.
+pub fn reverse_score(index: usize) -> Result<(i32, i32), String> {
+    let before = Sequences::snapshot();
+    let (forward, forward_gaps) = (Sequences::get_seq(index), Sequences::get_gap_costs(index));
+    Sequences::reverse(index)?;
+    let (backward, backward_gaps) = (Sequences::get_seq(index), Sequences::get_gap_costs(index));
+    Sequences::restore(&before);
+
+    let pair = (index, index);
+    let reverse = PairAlign::with_gap_costs(pair, &forward, &backward, forward_gaps.clone(), backward_gaps);
+    let itself = PairAlign::with_gap_costs(pair, &forward, &forward, forward_gaps.clone(), forward_gaps);
+    Ok((reverse.get_final_score(), itself.get_final_score()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,6 +479,7 @@ mod tests {
 
     #[test]
     fn test_pair_align() {
+        let _guard = crate::test_lock();
         Cost::set_cost_nuc();
         let s1 = b"ACGT";
         let s2 = b"AGCT";
@@ -98,4 +488,197 @@ mod tests {
         // Score should be calculated
         assert!(align.get_final_score() >= 0);
     }
+
+    #[test]
+    fn test_traceback() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        let s1 = b"ACGTAC";
+        let s2 = b"ACTAC";
+        let align = PairAlign::new((0, 1), s1, s2);
+
+        let (row1, row2) = align.traceback(s1, s2);
+        assert_eq!(row1, b"ACGTAC");
+        assert_eq!(row2, b"AC-TAC");
+    }
+
+    #[test]
+    fn test_local_align_shared_motif() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        let s1 = b"CCCCCGATTACACCCCC";
+        let s2 = b"TTTGATTACATTT";
+
+        let local = local_align(&Cost::matrix_snapshot(), s1, s2);
+        assert_eq!((local.range1, local.range2), (5..12, 3..10));
+        assert_eq!((local.row1.as_slice(), local.row2.as_slice()), (&b"GATTACA"[..], &b"GATTACA"[..]));
+        assert_eq!(local.score, 7);
+
+        // The binaries go through the search entry point
+        Sequences::clear();
+        for (name, seq) in [(">a", &s1[..]), (">b", &s2[..])] {
+            Sequences::set_name(name.to_string());
+            Sequences::set_seq_bytes(seq.to_vec()).unwrap();
+        }
+        let options = AStarOpt::builder().mode(crate::alignment::AlignMode::Local).build();
+        let alignment = crate::astar::run_astar_for_sequences(&options).unwrap();
+        assert_eq!(alignment.seqs(), ["GATTACA", "GATTACA"]);
+        assert_eq!(Sequences::get_seq_name(0), ">a/6-12");
+        assert_eq!(Sequences::get_seq_name(1), ">b/4-10");
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn test_antidiagonal_matches_scalar() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        Cost::set_structure_scoring(crate::cost::StructureScoring { bonus: 6, penalty: 4 });
+        let s1 = b"VLSPADKTNVKAAWGKVGAHAGEYGAEALERMFLSFPTTKTYFPHFDLSHGSAQV";
+        let s2 = b"VHLTPEEKSAVTALWGKVNVDEVGGEALGRLLVVYPWTQRFF";
+
+        for (a, b) in [(&s1[..], &s2[..]), (&s2[..], &s1[..]), (&s1[..3], &s2[..]), (&s1[..1], &s2[..1])] {
+            // Uneven gap costs, as from a gap profile
+            let gaps = |len: usize| (0..=len).map(|i| 4 + (i % 7) as i32 * 5).collect::<Vec<_>>();
+            // and structure labels
+            let labels = |len: usize| (0..len).map(|i| b"HHEECC"[i % 6]).collect::<Vec<_>>();
+            let mut scalar = PairAlign {
+                pair: (0, 1),
+                matrix: vec![vec![0; b.len() + 1]; a.len() + 1],
+                transposed: false,
+                symmetric: false,
+                s1_len: a.len(),
+                s2_len: b.len(),
+                gaps1: gaps(a.len()),
+                gaps2: gaps(b.len()),
+                ss1: labels(a.len()),
+                ss2: labels(b.len()),
+                scheme: Cost::matrix_snapshot(),
+            };
+            scalar.align_scalar(a, b);
+
+            let vector = PairAlign::with_structure(
+                (0, 1), a, b, gaps(a.len()), gaps(b.len()), labels(a.len()), labels(b.len())
+            );
+            for i in 0..=a.len() {
+                for j in 0..=b.len() {
+                    assert_eq!(scalar.get_score(i, j), vector.get_score(i, j));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_score_independent_of_order() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        let s1 = b"VLSPADKTNVKAAWGKVGAHAGEYGAEALERMFLSFPTTKTYFPHFDLSHGSAQV";
+        let s2 = b"VHLTPEEKSAVTALWG";
+
+        let long_first = PairAlign::new((0, 1), s1, s2);
+        let short_first = PairAlign::new((1, 0), s2, s1);
+        // Either way the shorter sequence is the contiguous dimension
+        assert_eq!(long_first.matrix[0].len(), s2.len() + 1);
+        assert_eq!(short_first.matrix[0].len(), s2.len() + 1);
+
+        assert_eq!(long_first.get_final_score(), short_first.get_final_score());
+        for i in 0..=s1.len() {
+            for j in 0..=s2.len() {
+                assert_eq!(long_first.get_score(i, j), short_first.get_score(j, i));
+            }
+        }
+        let (row1, row2) = short_first.traceback(s2, s1);
+        assert_eq!(row1.iter().filter(|&&c| c != b'-').count(), s2.len());
+        assert_eq!(row2.iter().filter(|&&c| c != b'-').count(), s1.len());
+    }
+
+    #[test]
+    fn test_identical_and_empty_fast_paths() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        let s = b"VLSPADKTNVKAAWGKVGAHAGEYGAEALERMFLSFPTTKTYFPHFDLSHGSAQV";
+        let gaps = |len: usize| (0..=len).map(|i| 4 + (i % 7) as i32 * 5).collect::<Vec<_>>();
+
+        for (a, b) in [(&s[..], &s[..]), (&s[..], &b""[..]), (&b""[..], &s[..5]), (&b""[..], &b""[..])] {
+            let fast = PairAlign::with_gap_costs((0, 1), a, b, gaps(a.len()), gaps(b.len()));
+            assert_eq!(fast.symmetric, a == b);
+            let mut full = PairAlign {
+                pair: (0, 1),
+                matrix: vec![vec![0; fast.s2_len + 1]; fast.s1_len + 1],
+                transposed: fast.transposed,
+                symmetric: false,
+                s1_len: fast.s1_len,
+                s2_len: fast.s2_len,
+                gaps1: fast.gaps1.clone(),
+                gaps2: fast.gaps2.clone(),
+                ss1: Vec::new(),
+                ss2: Vec::new(),
+                scheme: Cost::matrix_snapshot(),
+            };
+            let (s1, s2) = if fast.transposed { (b, a) } else { (a, b) };
+            full.align_scalar(s1, s2);
+            for i in 0..=a.len() {
+                for j in 0..=b.len() {
+                    assert_eq!(full.get_score(i, j), fast.get_score(i, j), "{} x {} at ({}, {})", a.len(), b.len(), i, j);
+                }
+            }
+            assert_eq!(full.traceback(a, b), fast.traceback(a, b));
+        }
+    }
+
+    #[test]
+    fn test_reverse_score() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        Sequences::set_seq("ACGTTGCA".to_string()).unwrap();
+        Sequences::set_seq("ACGTACGG".to_string()).unwrap();
+
+        // A palindrome is its own reverse, so it aligns without any cost
+        let (reverse, itself) = reverse_score(0).unwrap();
+        assert_eq!(reverse, itself);
+        assert_eq!(reverse, 0);
+        let (reverse, itself) = reverse_score(1).unwrap();
+        assert!(reverse > itself);
+        assert_eq!(Sequences::get_seq(1), b"ACGTACGG");
+        assert!(reverse_score(2).is_err());
+    }
+
+    #[test]
+    fn test_schemes_in_parallel() {
+        use crate::node::Node;
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        for seq in ["ACGTACGTTA", "AGGTACTTA", "ACGTCCGTA"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        let origin = Node::with_values(0, Sequences::get_initial_coord::<3>(), 0);
+
+        // Score of the first pair and the costs of every move from the
+        // origin under one scheme
+        let search = |scheme: &ScoringScheme| {
+            let s = |i| Sequences::get_seq_slice(i);
+            let score = PairAlign::with_scheme(
+                scheme, (0, 1), &s(0), &s(1), vec![scheme.gap_cost(); 11], vec![scheme.gap_cost(); 10],
+                Vec::new(), Vec::new(),
+            ).get_final_score();
+            let moves: Vec<i32> = origin.neighbor_moves(scheme).map(|(_, cost, _)| cost).collect();
+            (score, moves)
+        };
+        let (nucleotide, identity) = (ScoringScheme::nucleotide(), ScoringScheme::identity(3, 5));
+        let expected = (search(&nucleotide), search(&identity));
+        assert_ne!(expected.0, expected.1);
+
+        // Both run while the active scheme keeps changing under them
+        std::thread::scope(|scope| {
+            let runs = [&nucleotide, &identity].map(|scheme| scope.spawn(|| {
+                (0..200).map(|_| search(scheme)).collect::<Vec<_>>()
+            }));
+            for k in 0..200 {
+                if k % 2 == 0 { Cost::set_cost_pam250() } else { Cost::set_cost_blosum62() };
+            }
+            let [a, b] = runs.map(|run| run.join().unwrap());
+            assert!(a.iter().all(|result| *result == expected.0));
+            assert!(b.iter().all(|result| *result == expected.1));
+        });
+    }
 }
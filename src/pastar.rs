@@ -6,76 +6,170 @@
  */
 
 use ahash::AHashMap;
-use parking_lot::Mutex;
-use rayon::prelude::*;
+use parking_lot::{Condvar, Mutex};
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::time::Duration;
 
+use crate::alignment::{AlignMode, Alignment, ListStats};
+use crate::astar;
 use crate::coord::Coord;
+use crate::coord_hash::{hash_shift_warning, HashRouter, HashType, NodeRouter};
 use crate::node::Node;
 use crate::priority_list::PriorityList;
-use crate::heuristic_hpair::HeuristicHPair;
+use crate::heuristic_hpair::SearchCosts;
+use crate::interrupt;
 use crate::sequences::Sequences;
 use crate::time_counter::TimeCounter;
-use crate::backtrace;
+use crate::backtrace::{self, ClosedLookup};
+use crate::center_star;
+use crate::gap_limit;
+use crate::pair_align;
+use crate::result_cache;
 use crate::msa_options::PAStarOpt;
 
+/// Largest per-thread list capacity reserved up front when `--presize`
+/// isn't given. The search usually visits a small part of the lattice, so
+/// a bigger default would mostly reserve memory that is never used.
+const PRESIZE_LIMIT: usize = 1 << 16;
+
+/// Longest an idle worker sleeps before rechecking termination and
+/// cancellation on its own
+const IDLE_WAIT: Duration = Duration::from_millis(10);
+
 pub struct PAStar<const N: usize> {
     options: PAStarOpt,
     open_lists: Vec<Mutex<PriorityList<N>>>,
+    /// Signalled when a node is pushed to the matching open list
+    work_ready: Vec<Condvar>,
     closed_lists: Vec<Mutex<AHashMap<Coord<N>, Node<N>>>>,
-    thread_map: Vec<usize>,
-    map_size: usize,
+    /// Partially expanded nodes of each thread and the f up to which their
+    /// successors were queued
+    deferred: Vec<Mutex<AHashMap<Coord<N>, i32>>>,
+    /// Picks the thread whose open list receives each generated node
+    router: Box<dyn NodeRouter<N>>,
+    /// Move costs and heuristic, resolved from the options once
+    costs: SearchCosts,
     final_node: Arc<Mutex<Option<Node<N>>>>,
+    best_goal_g: AtomicI32,
     end_cond: Arc<AtomicBool>,
+    active_workers: AtomicUsize,
+    pushes: AtomicUsize,
+    /// Expansions of all threads, checked against `max_nodes`
+    expanded: AtomicUsize,
+    limit_reached: AtomicBool,
     nodes_processed: Vec<AtomicUsize>,
+    nodes_reopened: Vec<AtomicUsize>,
+    nodes_requeued: Vec<AtomicUsize>,
+    peak_closed: Vec<AtomicUsize>,
+    peak_open: Vec<AtomicUsize>,
+    idle_waits: Vec<AtomicUsize>,
 }
 
 impl<const N: usize> PAStar<N> {
-    pub fn new(node_zero: Node<N>, options: PAStarOpt) -> Self {
+    pub fn new(node_zero: Node<N>, options: PAStarOpt) -> Result<Self, String> {
         let threads_num = options.threads_num;
         
-        println!(
+        crate::info!(
             "Running PA-Star with: {} threads, {:?} hash, {} shift",
             threads_num,
             options.hash_type,
             options.hash_shift
         );
+        if N <= 2 && matches!(options.hash_type, HashType::PSum | HashType::PZorder) {
+            crate::info!("Two sequences: the partial hash falls back to the full one");
+        }
+        let max_hash = Sequences::get_final_coord::<N>().hash_value(options.hash_type);
+        if let Some(warning) = hash_shift_warning(max_hash, threads_num, options.hash_shift) {
+            eprintln!("Warning: {}", warning);
+        }
+
+        // Thread map for hybrid CPUs
+        let router = HashRouter {
+            hash_type: options.hash_type,
+            shift: options.hash_shift,
+            thread_map: Self::configure_thread_map(&options),
+        };
+        Self::with_router(node_zero, options, Box::new(router))
+    }
+
+    /// Like `new`, sending each generated node to the open list `router`
+    /// picks instead of hashing its coordinate. Fails if the heuristic
+    /// can't be aligned or already exceeds `max_cost` at the origin.
+    pub fn with_router(node_zero: Node<N>, options: PAStarOpt, router: Box<dyn NodeRouter<N>>) -> Result<Self, String> {
+        let threads_num = options.threads_num;
+        let costs = SearchCosts::for_options(&options.common)?;
+        
+        // Neighbors derive their heuristic from the parent's, so the start
+        // node needs its full estimate
+        let mut node_zero = node_zero;
+        node_zero.set_f(node_zero.get_g() + costs.heuristic.calculate_h(&node_zero.pos));
+        astar::check_max_cost(node_zero.get_f(), &options.common)?;
         
         let mut open_lists = Vec::with_capacity(threads_num);
         let mut closed_lists = Vec::with_capacity(threads_num);
         let mut nodes_processed = Vec::with_capacity(threads_num);
+        let mut nodes_reopened = Vec::with_capacity(threads_num);
+        let mut nodes_requeued = Vec::with_capacity(threads_num);
+        let mut deferred = Vec::with_capacity(threads_num);
+        let mut peak_closed = Vec::with_capacity(threads_num);
+        let mut peak_open = Vec::with_capacity(threads_num);
+        let mut idle_waits = Vec::with_capacity(threads_num);
+        let mut work_ready = Vec::with_capacity(threads_num);
         
+        let capacity = Self::list_capacity(&options);
         for _ in 0..threads_num {
-            open_lists.push(Mutex::new(PriorityList::new()));
-            closed_lists.push(Mutex::new(AHashMap::new()));
+            let open_list = PriorityList::with_capacity(capacity).with_tie_policy(options.common.tie_policy);
+            open_lists.push(Mutex::new(open_list));
+            work_ready.push(Condvar::new());
+            idle_waits.push(AtomicUsize::new(0));
+            closed_lists.push(Mutex::new(AHashMap::with_capacity(capacity)));
             nodes_processed.push(AtomicUsize::new(0));
+            nodes_reopened.push(AtomicUsize::new(0));
+            nodes_requeued.push(AtomicUsize::new(0));
+            deferred.push(Mutex::new(AHashMap::new()));
+            peak_closed.push(AtomicUsize::new(0));
+            peak_open.push(AtomicUsize::new(0));
         }
         
         // Add initial node to first thread
         open_lists[0].lock().push(node_zero);
+        peak_open[0].store(1, Ordering::Relaxed);
         
-        // Configure thread map for hybrid CPUs
-        let (thread_map, map_size) = Self::configure_thread_map(&options);
+        // Nodes over --max-cost are pruned like those past a found goal
+        let best_goal_g = options.common.max_cost.map_or(i32::MAX, |cost| cost.saturating_add(1));
         
-        PAStar {
+        Ok(PAStar {
             options,
             open_lists,
+            work_ready,
             closed_lists,
-            thread_map,
-            map_size,
+            deferred,
+            router,
+            costs,
             final_node: Arc::new(Mutex::new(None)),
+            best_goal_g: AtomicI32::new(best_goal_g),
             end_cond: Arc::new(AtomicBool::new(false)),
+            active_workers: AtomicUsize::new(0),
+            pushes: AtomicUsize::new(0),
+            expanded: AtomicUsize::new(0),
+            limit_reached: AtomicBool::new(false),
             nodes_processed,
-        }
+            nodes_reopened,
+            nodes_requeued,
+            peak_closed,
+            peak_open,
+            idle_waits,
+        })
     }
     
-    fn configure_thread_map(options: &PAStarOpt) -> (Vec<usize>, usize) {
+    fn configure_thread_map(options: &PAStarOpt) -> Vec<usize> {
         let hybrid = &options.hybrid_conf;
         
         if hybrid.p_cores_num == 0 && hybrid.e_cores_num == 0 {
             // No hybrid configuration - simple 1:1 mapping
-            return (Vec::new(), options.threads_num);
+            return Vec::new();
         }
         
         let map_size = hybrid.p_cores_num * hybrid.p_cores_size +
@@ -96,38 +190,62 @@ impl<const N: usize> PAStar<N> {
             }
         }
         
-        (thread_map, map_size)
+        thread_map
     }
     
     fn get_thread_id(&self, coord: &Coord<N>) -> usize {
-        if self.thread_map.is_empty() {
-            coord.get_id(
-                self.options.threads_num,
-                self.options.hash_type,
-                self.options.hash_shift,
-                &[]
-            )
-        } else {
-            coord.get_id(
-                self.map_size,
-                self.options.hash_type,
-                self.options.hash_shift,
-                &self.thread_map
-            )
-        }
+        self.router.route(coord, self.options.threads_num)
+    }
+    
+    /// Initial capacity of each thread's open and closed list: `--presize`
+    /// if given, otherwise the thread's share of the lattice up to
+    /// `PRESIZE_LIMIT`
+    fn list_capacity(options: &PAStarOpt) -> usize {
+        options.presize.unwrap_or_else(|| {
+            let share = Sequences::lattice_size().unwrap_or(u128::MAX) / options.threads_num.max(1) as u128;
+            share.min(PRESIZE_LIMIT as u128) as usize
+        })
+    }
+    
+    /// Run the workers until the search ends, without backtracking
+    pub fn search(&self, coord_final: Coord<N>) -> Result<(), String> {
+        // Parallel execution on a pool sized by --threads, independent of
+        // the global rayon pool and RAYON_NUM_THREADS
+        let pool = build_thread_pool(self.options.threads_num)?;
+        pool.scope(|scope| {
+            for tid in 0..self.options.threads_num {
+                scope.spawn(move |_| self.worker(tid, coord_final));
+            }
+        });
+        Ok(())
+    }
+    
+    /// Whether the search stopped on `max_nodes` before finishing
+    pub fn limit_reached(&self) -> bool {
+        self.limit_reached.load(Ordering::SeqCst)
+    }
+    
+    /// Nodes expanded by each thread
+    pub fn thread_loads(&self) -> Vec<usize> {
+        self.nodes_processed.iter().map(|n| n.load(Ordering::Relaxed)).collect()
     }
     
-    pub fn run(&self, coord_final: Coord<N>) -> Result<(), String> {
+    pub fn run(&self, coord_final: Coord<N>) -> Result<Alignment, String> {
         let _timer = TimeCounter::new("\nPhase 2: PA-Star running time:");
         
-        // Parallel execution
-        (0..self.options.threads_num).into_par_iter().for_each(|tid| {
-            self.worker(tid, coord_final);
-        });
+        self.search(coord_final)?;
+        if let Some(max_nodes) = self.options.max_nodes
+            && self.limit_reached() {
+            return Err(format!("Node limit of {} reached before the goal", max_nodes));
+        }
         
         // Get final node
         let final_node = self.final_node.lock().clone();
         
+        if interrupt::is_cancelled() {
+            return backtrace::save_partial(final_node.as_ref(), self.closed_lists.as_slice(), &self.options.common);
+        }
+        
         match final_node {
             Some(node) => {
                 // Print statistics
@@ -136,27 +254,52 @@ impl<const N: usize> PAStar<N> {
                     .map(|n| n.load(Ordering::Relaxed))
                     .sum();
                 
-                println!("Total nodes processed: {}", total_nodes);
+                let total_reopened: usize = self.nodes_reopened
+                    .iter()
+                    .map(|n| n.load(Ordering::Relaxed))
+                    .sum();
                 
-                // Merge closed lists for backtrace
-                let mut merged_closed = AHashMap::new();
-                for closed_list in &self.closed_lists {
-                    let list = closed_list.lock();
-                    for (coord, node) in list.iter() {
-                        merged_closed.entry(*coord)
-                            .and_modify(|e: &mut Node<N>| {
-                                if node.get_g() < e.get_g() {
-                                    *e = node.clone();
-                                }
-                            })
-                            .or_insert_with(|| node.clone());
-                    }
+                let peak_closed: usize = self.peak_closed
+                    .iter()
+                    .map(|n| n.load(Ordering::Relaxed))
+                    .sum();
+                
+                let peak_open: usize = self.peak_open
+                    .iter()
+                    .map(|n| n.load(Ordering::Relaxed))
+                    .sum();
+                
+                let closed: usize = self.closed_lists
+                    .iter()
+                    .map(|list| list.lock().len())
+                    .sum();
+                
+                let idle_waits: usize = self.idle_waits
+                    .iter()
+                    .map(|n| n.load(Ordering::Relaxed))
+                    .sum();
+                
+                crate::info!("Total nodes processed: {}", total_nodes);
+                crate::info!("Reopened nodes: {}", total_reopened);
+                if self.options.common.partial_expansion.is_some() {
+                    let total_requeued: usize = self.nodes_requeued
+                        .iter()
+                        .map(|n| n.load(Ordering::Relaxed))
+                        .sum();
+                    crate::info!("Requeued partial expansions: {}", total_requeued);
                 }
+                crate::info!("Peak closed list size: {}", peak_closed);
+                crate::info!("Peak open list size: {}", peak_open);
+                crate::info!("Idle waits: {}", idle_waits);
                 
-                backtrace::backtrace(&node, &merged_closed, &self.options.common.output_file);
-                Ok(())
+                backtrace::backtrace(&node, self.closed_lists.as_slice(), &self.costs, &self.options.common).map(|alignment| {
+                    let list_stats = ListStats { reopened: total_reopened, closed, peak_open };
+                    let alignment = alignment.with_nodes_expanded(total_nodes).with_list_stats(list_stats);
+                    astar::report_expansion_ratio(&alignment);
+                    alignment
+                })
             }
-            None => Err("No solution found".to_string()),
+            None => Err(astar::no_solution(&self.options.common)),
         }
     }
     
@@ -167,156 +310,622 @@ impl<const N: usize> PAStar<N> {
             let _ = core_affinity::set_for_current(core_affinity::CoreId { id: core_id });
         }
         
-        let mut empty_iterations = 0;
-        const MAX_EMPTY_ITERATIONS: usize = 100;
-        
-        while !self.end_cond.load(Ordering::Relaxed) {
-            // Try to dequeue a node
+        while !self.end_cond.load(Ordering::SeqCst) {
+            if interrupt::is_cancelled() {
+                self.finish();
+                break;
+            }
+            
+            // Try to dequeue a node. The worker is marked active while still
+            // holding the lock so the termination check never misses it
             let current = {
                 let mut open_list = self.open_lists[tid].lock();
-                open_list.pop()
+                let node = open_list.pop();
+                if node.is_some() {
+                    self.active_workers.fetch_add(1, Ordering::SeqCst);
+                }
+                node
             };
             
             let current = match current {
-                Some(node) => {
-                    empty_iterations = 0;
-                    node
-                },
+                Some(node) => node,
                 None => {
-                    // No work available
-                    empty_iterations += 1;
-                    
-                    if empty_iterations > MAX_EMPTY_ITERATIONS {
-                        // Check if all lists are truly empty
-                        if self.all_lists_empty() {
-                            break;
-                        }
+                    // No work available: the last worker to go idle sees
+                    // the search finished and wakes the others
+                    if self.search_finished() {
+                        self.finish();
+                        break;
                     }
                     
-                    // Small delay to avoid busy waiting
-                    std::thread::yield_now();
+                    // Sleep until a node is routed here. The end condition
+                    // is checked under the list lock `finish` also takes,
+                    // so its wake-up can't be missed
+                    let mut open_list = self.open_lists[tid].lock();
+                    if open_list.is_empty() && !self.end_cond.load(Ordering::SeqCst) {
+                        self.idle_waits[tid].fetch_add(1, Ordering::Relaxed);
+                        self.work_ready[tid].wait_for(&mut open_list, IDLE_WAIT);
+                    }
                     continue;
                 }
             };
             
-            // Check if already processed with better cost
-            {
-                let closed_list = self.closed_lists[tid].lock();
-                if let Some(existing) = closed_list.get(&current.pos) {
-                    if current.get_g() >= existing.get_g() {
-                        continue;
+            self.expand(tid, current, coord_final);
+            self.active_workers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+    
+    fn expand(&self, tid: usize, current: Node<N>, coord_final: Coord<N>) {
+        // Nothing at or above the best goal cost can lead to a cheaper goal
+        let bound = self.best_goal_g.load(Ordering::SeqCst);
+        if current.get_f() >= bound {
+            return;
+        }
+        
+        // Check if already processed with better cost. A partially
+        // expanded node comes back above its queued f.
+        let mut queued_up_to = None;
+        {
+            let mut closed_list = self.closed_lists[tid].lock();
+            if let Some(existing) = closed_list.get_mut(&current.pos)
+                && current.get_g() >= existing.get_g() {
+                match self.deferred[tid].lock().get(&current.pos) {
+                    Some(&up_to) if current.get_g() == existing.get_g() && current.get_f() > up_to => {
+                        queued_up_to = Some((up_to, existing.clone()));
+                    }
+                    _ => {
+                        if current.better_than(existing) {
+                            existing.set_parenti(current.get_parenti());
+                        }
+                        return;
                     }
                 }
             }
-            
-            // Check if final
-            if current.pos == coord_final {
-                let mut final_node = self.final_node.lock();
-                let should_update = match *final_node {
-                    Some(ref existing) => current.get_g() < existing.get_g(),
-                    None => true,
-                };
-                
-                if should_update {
-                    *final_node = Some(current.clone());
-                    self.end_cond.store(true, Ordering::Relaxed);
-                }
-                continue;
+        }
+        
+        // The closed entry keeps the node's own f, so its h stays exact
+        let (lower, expanded) = match queued_up_to {
+            Some((up_to, closed)) => {
+                self.nodes_requeued[tid].fetch_add(1, Ordering::Relaxed);
+                (Some(up_to), closed)
             }
-            
-            // Add to closed list
-            {
-                let mut closed_list = self.closed_lists[tid].lock();
-                closed_list.insert(current.pos, current.clone());
+            None if self.close(tid, &current, coord_final, bound) => (None, current.clone()),
+            None => return,
+        };
+        
+        // Generate neighbors
+        let mut neighbors = expanded.get_neighbors(&self.costs.scheme);
+        for neighbor in &mut neighbors {
+            // Calculate heuristic
+            let h = expanded.get_h() + self.costs.heuristic.delta_h(&expanded.pos, &neighbor.pos);
+            neighbor.set_f(neighbor.get_g() + h);
+        }
+        
+        if let Some(margin) = self.options.common.partial_expansion {
+            let upper = current.get_f() + margin.max(0);
+            let (queued, next_f) = astar::partial_successors(neighbors, lower, upper);
+            neighbors = queued;
+            if let Some(next_f) = next_f {
+                self.deferred[tid].lock().insert(current.pos, upper);
+                let mut requeued = expanded.clone();
+                requeued.set_f(next_f);
+                let mut open_list = self.open_lists[tid].lock();
+                open_list.push(requeued);
+                self.peak_open[tid].fetch_max(open_list.len(), Ordering::Relaxed);
+                self.pushes.fetch_add(1, Ordering::SeqCst);
+            } else {
+                self.deferred[tid].lock().remove(&current.pos);
+            }
+        }
+        
+        for neighbor in neighbors {
+            if neighbor.get_f() >= bound {
+                continue;
             }
             
-            self.nodes_processed[tid].fetch_add(1, Ordering::Relaxed);
-            
-            // Generate neighbors
-            let neighbors = current.get_neighbors();
+            // Determine which thread should handle this node
+            let target_tid = self.get_thread_id(&neighbor.pos);
             
-            for mut neighbor in neighbors {
-                // Calculate heuristic
-                let h = HeuristicHPair::calculate_h(&neighbor.pos);
-                neighbor.set_f(neighbor.get_g() + h);
-                
-                // Determine which thread should handle this node
-                let target_tid = self.get_thread_id(&neighbor.pos);
-                
-                // Check if already in target's closed list
-                let should_add = {
-                    let closed_list = self.closed_lists[target_tid].lock();
-                    if let Some(existing) = closed_list.get(&neighbor.pos) {
-                        neighbor.get_g() < existing.get_g()
-                    } else {
-                        true
+            // Check if already in target's closed list
+            let should_add = {
+                let mut closed_list = self.closed_lists[target_tid].lock();
+                if let Some(existing) = closed_list.get_mut(&neighbor.pos) {
+                    let better = neighbor.get_g() < existing.get_g();
+                    if better {
+                        self.nodes_reopened[tid].fetch_add(1, Ordering::Relaxed);
+                    } else if neighbor.better_than(existing) {
+                        existing.set_parenti(neighbor.get_parenti());
                     }
-                };
-                
-                if should_add {
-                    let mut open_list = self.open_lists[target_tid].lock();
-                    open_list.push(neighbor);
+                    better
+                } else {
+                    true
                 }
+            };
+            
+            if should_add {
+                let mut open_list = self.open_lists[target_tid].lock();
+                open_list.push(neighbor);
+                self.peak_open[target_tid].fetch_max(open_list.len(), Ordering::Relaxed);
+                self.pushes.fetch_add(1, Ordering::SeqCst);
+                self.work_ready[target_tid].notify_one();
             }
         }
     }
     
+    /// Move `current` to the closed list, or record it as the goal. False
+    /// when it has no successors to generate: it's the goal, or the node
+    /// limit was reached.
+    fn close(&self, tid: usize, current: &Node<N>, coord_final: Coord<N>, bound: i32) -> bool {
+        // Check if final. Other threads keep searching until no open node
+        // can beat this goal
+        if current.pos == coord_final {
+            let mut final_node = self.final_node.lock();
+            let should_update = match *final_node {
+                Some(ref existing) => current.better_than(existing),
+                None => true,
+            };
+            
+            if should_update {
+                self.best_goal_g.fetch_min(current.get_g(), Ordering::SeqCst);
+                *final_node = Some(current.clone());
+            }
+            return false;
+        }
+        
+        // Add to closed list
+        {
+            let mut closed_list = self.closed_lists[tid].lock();
+            closed_list.insert(current.pos, current.clone());
+            self.deferred[tid].lock().remove(&current.pos);
+            
+            let processed = self.nodes_processed[tid].fetch_add(1, Ordering::Relaxed) + 1;
+            let compact_every = self.options.compact_every;
+            if compact_every > 0 && processed.is_multiple_of(compact_every) && bound < i32::MAX {
+                // Entries whose f exceeds the best goal cannot be on an
+                // optimal path
+                closed_list.retain(|_, node| node.get_f() <= bound);
+            }
+            self.peak_closed[tid].fetch_max(closed_list.len(), Ordering::Relaxed);
+        }
+        
+        if let Some(max_nodes) = self.options.max_nodes
+            && self.expanded.fetch_add(1, Ordering::Relaxed) + 1 >= max_nodes {
+            self.limit_reached.store(true, Ordering::SeqCst);
+            self.finish();
+            return false;
+        }
+        true
+    }
+    
+    /// Stop the search and wake every idle worker
+    fn finish(&self) {
+        self.end_cond.store(true, Ordering::SeqCst);
+        for (open_list, work_ready) in self.open_lists.iter().zip(&self.work_ready) {
+            let _guard = open_list.lock();
+            work_ready.notify_all();
+        }
+    }
+    
+    /// True when every open list is empty and no worker is expanding a
+    /// node. The push counter catches nodes routed to an already checked
+    /// list while the check was running.
+    fn search_finished(&self) -> bool {
+        let pushes = self.pushes.load(Ordering::SeqCst);
+        self.all_lists_empty()
+            && self.active_workers.load(Ordering::SeqCst) == 0
+            && self.pushes.load(Ordering::SeqCst) == pushes
+    }
+    
     fn all_lists_empty(&self) -> bool {
         self.open_lists.iter().all(|list| list.lock().is_empty())
     }
 }
 
-pub fn run_pastar_for_sequences(options: PAStarOpt) -> Result<(), String> {
-    match Sequences::get_seq_num() {
-        2 => {
-            let pastar = PAStar::<2>::new(
-                Sequences::get_initial_node(),
-                options
-            );
-            pastar.run(Sequences::get_final_coord())
-        },
-        3 => {
-            let pastar = PAStar::<3>::new(
-                Sequences::get_initial_node(),
-                options
-            );
-            pastar.run(Sequences::get_final_coord())
-        },
-        4 => {
-            let pastar = PAStar::<4>::new(
-                Sequences::get_initial_node(),
-                options
-            );
-            pastar.run(Sequences::get_final_coord())
-        },
-        5 => {
-            let pastar = PAStar::<5>::new(
-                Sequences::get_initial_node(),
-                options
-            );
-            pastar.run(Sequences::get_final_coord())
-        },
-        6 => {
-            let pastar = PAStar::<6>::new(
-                Sequences::get_initial_node(),
-                options
-            );
-            pastar.run(Sequences::get_final_coord())
-        },
-        7 => {
-            let pastar = PAStar::<7>::new(
-                Sequences::get_initial_node(),
-                options
-            );
-            pastar.run(Sequences::get_final_coord())
-        },
-        8 => {
-            let pastar = PAStar::<8>::new(
-                Sequences::get_initial_node(),
-                options
-            );
-            pastar.run(Sequences::get_final_coord())
-        },
-        n => Err(format!("Unsupported number of sequences: {}. Supported: 2-8", n)),
+/// Backtrace looks nodes up in the per-thread closed lists directly, which
+/// avoids merging every closed node into one map after the search
+impl<const N: usize> ClosedLookup<N> for [Mutex<AHashMap<Coord<N>, Node<N>>>] {
+    fn closed_node(&self, pos: &Coord<N>) -> Option<Node<N>> {
+        // Copies in several lists resolve by cost, never by list order
+        self.iter()
+            .filter_map(|list| list.lock().get(pos).cloned())
+            .min_by_key(|node| (node.get_g(), node.get_parenti(), node.get_f()))
+    }
+
+    fn for_each_closed(&self, f: &mut dyn FnMut(&Node<N>)) {
+        for list in self {
+            list.lock().values().for_each(&mut *f);
+        }
+    }
+}
+
+/// Dedicated pool with exactly one thread per PA-Star worker
+fn build_thread_pool(threads_num: usize) -> Result<ThreadPool, String> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads_num)
+        .thread_name(|i| format!("pastar-worker-{}", i))
+        .build()
+        .map_err(|e| format!("Can't create thread pool: {}", e))
+}
+
+pub fn run_pastar_for_sequences(options: PAStarOpt) -> Result<Alignment, String> {
+    let common = options.common.clone();
+    result_cache::with_cache(&common, || run_pastar_uncached(options))
+}
+
+pub fn run_pastar_uncached(options: PAStarOpt) -> Result<Alignment, String> {
+    if options.common.mode == AlignMode::Local {
+        return pair_align::run_local_alignment(&options.common);
+    }
+    if options.common.to_reference {
+        return center_star::run_to_reference(&options.common);
+    }
+    if center_star::use_approximation(&options.common) {
+        return center_star::run_center_star(&options.common);
+    }
+
+    if let Some(max_gap) = options.common.max_gap {
+        crate::info!("Gap-limited search runs on a single thread");
+        return gap_limit::run_gap_limited(max_gap, &options.common);
+    }
+
+    // A single worker gains nothing from the parallel machinery
+    if options.threads_num == 1 {
+        crate::info!("Single thread requested, running Serial A-Star");
+        return astar::run_astar_uncached(&options.common);
+    }
+
+    dispatch_by_n!(Sequences::get_seq_num(), N => {
+        let pastar = PAStar::<N>::new(Sequences::get_initial_node(), options)?;
+        pastar.run(Sequences::get_final_coord())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord_hash::HashType;
+    use crate::cost::Cost;
+    use crate::heuristic_hpair::HeuristicHPair;
+    use crate::msa_options::AStarOpt;
+    use crate::priority_types::TiePolicy;
+
+    #[test]
+    fn test_closed_scan_independent_of_hash_order() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        for seq in ["ACGTAC", "ACTTAC", "AGGTAC"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        // Every coordinate up to sum 4, all tied on g for their sum, so the
+        // furthest closed node is only decided by scan order
+        let mut nodes: Vec<Node<3>> = Vec::new();
+        for x in 0..=3u16 {
+            for y in 0..=3u16 {
+                for z in 0..=3u16 {
+                    if x + y + z <= 4 {
+                        let parenti = [x, y, z].iter().position(|&c| c > 0).map_or(0, |dim| 1 << dim);
+                        nodes.push(Node::with_values((x + y + z) as i32, Coord::from_array([x, y, z]), parenti));
+                    }
+                }
+            }
+        }
+        let spread = |lists_num: usize, reversed: bool| {
+            let lists: Vec<Mutex<AHashMap<Coord<3>, Node<3>>>> = (0..lists_num).map(|_| Mutex::new(AHashMap::new())).collect();
+            let order: Vec<&Node<3>> = if reversed { nodes.iter().rev().collect() } else { nodes.iter().collect() };
+            for (k, node) in order.into_iter().enumerate() {
+                lists[k % lists_num].lock().insert(node.pos, node.clone());
+                // A worse copy in another list, as a stale duplicate
+                let mut worse = node.clone();
+                worse.set_g(node.get_g() + 1);
+                lists[(k + 1) % lists_num].lock().entry(node.pos).or_insert(worse);
+            }
+            lists
+        };
+        let (a, b) = (spread(2, false), spread(3, true));
+
+        let path = std::env::temp_dir().join(format!("pastar_closed_scan_{}.fasta", std::process::id()));
+        let path = path.to_str().unwrap();
+        let options = AStarOpt::builder().save_on_interrupt(path).build();
+        let saved_a = backtrace::save_partial(None, &a[..], &options).unwrap_err();
+        assert!(saved_a.starts_with(&format!("Search interrupted at {},", Coord::from_array([0u16, 1, 3]))), "{}", saved_a);
+        let file_a = std::fs::read_to_string(path).unwrap();
+        let saved_b = backtrace::save_partial(None, &b[..], &options).unwrap_err();
+        assert_eq!(saved_a, saved_b);
+        assert_eq!(file_a, std::fs::read_to_string(path).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_thread_pool_concurrency() {
+        use std::sync::Barrier;
+
+        let threads_num = 3;
+        let pool = build_thread_pool(threads_num).unwrap();
+        let active = AtomicUsize::new(0);
+        let max_active = AtomicUsize::new(0);
+        // Every worker must be running at once for the barrier to release
+        let barrier = Barrier::new(threads_num);
+
+        pool.scope(|scope| {
+            for _ in 0..threads_num {
+                scope.spawn(|_| {
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(now, Ordering::SeqCst);
+                    barrier.wait();
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        assert_eq!(pool.current_num_threads(), threads_num);
+        assert_eq!(max_active.load(Ordering::SeqCst), threads_num);
+    }
+
+    #[test]
+    fn test_parallel_cost_matches_serial() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]);
+
+        let serial = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+        for compact_every in [0, 1] {
+            let options = PAStarOpt::builder()
+                .threads(4)
+                .hash_shift(0)
+                .compact_every(compact_every)
+                .build()
+                .unwrap();
+            let parallel = run_pastar_for_sequences(options).unwrap();
+            assert_eq!(parallel.sp_score(), serial.sp_score());
+        }
+    }
+
+    #[test]
+    fn test_inflated_heuristic_reopens_nodes() {
+        let _guard = crate::test_lock();
+        let seqs = ["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD", "VLSEGEWQLVLHVWAKVEAD"];
+        crate::test_support::pam_fixture(&seqs);
+        let reopened = |threads: usize| {
+            let options = PAStarOpt::builder().threads(threads).build().unwrap();
+            run_pastar_for_sequences(options).unwrap().list_stats().unwrap().reopened
+        };
+        // One worker expands in f order, and the heuristic is consistent
+        assert_eq!(reopened(1), 0);
+
+        // Pairs aligned with mismatches at 40 and gaps at 10 over- or
+        // underestimate depending on the move, so h isn't consistent
+        Cost::set_cost_identity(40, 10);
+        HeuristicHPair::init().unwrap();
+        let inflated = HeuristicHPair::shared_aligns();
+        Cost::set_cost_pam250();
+        HeuristicHPair::install(inflated);
+
+        for threads in [1, 2] {
+            assert!(reopened(threads) > 0, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn test_custom_router() {
+        struct FirstThread;
+
+        impl<const N: usize> NodeRouter<N> for FirstThread {
+            fn route(&self, _coord: &Coord<N>, _num_threads: usize) -> usize {
+                0
+            }
+        }
+
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]);
+        let serial = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+
+        let options = PAStarOpt::builder().threads(4).no_affinity(true).build().unwrap();
+        let pastar = PAStar::<3>::with_router(Sequences::get_initial_node(), options, Box::new(FirstThread)).unwrap();
+        let parallel = pastar.run(Sequences::get_final_coord()).unwrap();
+        assert_eq!(parallel.sp_score(), serial.sp_score());
+
+        let loads = pastar.thread_loads();
+        assert!(loads[0] > 0);
+        assert_eq!(loads[1..], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_starved_worker_sleeps() {
+        use crate::msa_options::HybridCpu;
+        use std::time::Instant;
+
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]);
+        let serial = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+
+        // The thread map only covers threads 0 and 1, thread 2 never gets work
+        let options = PAStarOpt::builder()
+            .threads(3)
+            .no_affinity(true)
+            .hybrid_conf(HybridCpu { p_cores_num: 2, p_cores_size: 1, ..Default::default() })
+            .build()
+            .unwrap();
+        let pastar = PAStar::<3>::new(Sequences::get_initial_node(), options).unwrap();
+        let start = Instant::now();
+        let parallel = pastar.run(Sequences::get_final_coord()).unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(parallel.sp_score(), serial.sp_score());
+
+        // Sleeping, the starved worker only wakes up on the idle timeout
+        let timeouts = (elapsed.as_millis() / IDLE_WAIT.as_millis()) as usize;
+        assert!(pastar.idle_waits[2].load(Ordering::Relaxed) <= 2 * timeouts + 5);
+    }
+
+    #[test]
+    fn test_dispatchers_accept_same_counts() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        let seqs = ["VLSPAD", "VHLTPE", "GLSDGE", "VLSEGE", "VHLSPA", "GLTDAE", "VLSGEW", "AHLTPE"];
+        for n in crate::build_info::supported_n() {
+            Sequences::clear();
+            for seq in &seqs[..n] {
+                Sequences::set_seq(seq.to_string()).unwrap();
+            }
+            HeuristicHPair::init().unwrap();
+            let serial = astar::run_astar_uncached(&AStarOpt::builder().build()).unwrap();
+            let options = PAStarOpt::builder().threads(2).no_affinity(true).build().unwrap();
+            let parallel = run_pastar_uncached(options).unwrap();
+            assert_eq!(parallel.sp_score(), serial.sp_score(), "{} sequences", n);
+        }
+    }
+
+    #[test]
+    fn test_list_capacity() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        Sequences::set_seq("ACGT".to_string()).unwrap();
+        Sequences::set_seq("ACG".to_string()).unwrap();
+
+        // 5 * 4 lattice nodes shared by 2 threads
+        let options = PAStarOpt::builder().threads(2).build().unwrap();
+        assert_eq!(PAStar::<2>::list_capacity(&options), 10);
+        let options = PAStarOpt::builder().threads(2).presize(100).build().unwrap();
+        assert_eq!(PAStar::<2>::list_capacity(&options), 100);
+        // Options built by hand skip validation, so zero threads gets one share
+        let options = PAStarOpt { threads_num: 0, presize: None, ..options };
+        assert_eq!(PAStar::<2>::list_capacity(&options), 20);
+
+        Sequences::set_seq("A".repeat(1000)).unwrap();
+        Sequences::set_seq("A".repeat(1000)).unwrap();
+        let options = PAStarOpt::builder().threads(2).build().unwrap();
+        assert_eq!(PAStar::<4>::list_capacity(&options), PRESIZE_LIMIT);
+    }
+
+    #[test]
+    fn test_output_independent_of_thread_count() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        // Nucleotide costs leave many equal cost paths to choose from
+        for seq in [
+            "ACAGACTACTTCGTATATTGGACAACATACTGG",
+            "ACTCCACTAACTAGAACGCTAGGTGAAAGAATATGA",
+            "TACACTAACTGAACACTAGGGTCGATGACGCTT",
+            "ACCAAACTTGAAGGCATAGTGGTCAAAGAGTACAG",
+        ] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        HeuristicHPair::init().unwrap();
+
+        let run = |threads| {
+            let options = PAStarOpt::builder().threads(threads).build().unwrap();
+            run_pastar_for_sequences(options).unwrap()
+        };
+        let serial = run(1);
+        for threads in [2, 4, 8] {
+            assert_eq!(run(threads).seqs(), serial.seqs());
+        }
+    }
+
+    #[test]
+    fn test_partial_expansion_matches_full() {
+        let _guard = crate::test_lock();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD", "VLSEGEWQLVLHVWAKVEAD"]);
+
+        for threads in [1, 2, 4] {
+            let full = run_pastar_for_sequences(PAStarOpt::builder().threads(threads).build().unwrap()).unwrap();
+            for margin in [0, 10] {
+                let options = PAStarOpt::builder().threads(threads).partial_expansion(margin).build().unwrap();
+                let partial = run_pastar_for_sequences(options).unwrap();
+                assert_eq!(partial.seqs(), full.seqs(), "{} threads, margin {}", threads, margin);
+                assert_eq!(partial.search_scores(), full.search_scores());
+                // Deferred successors never reach the open list
+                assert!(partial.list_stats().unwrap().peak_open < full.list_stats().unwrap().peak_open);
+            }
+        }
+    }
+
+    #[test]
+    fn test_prepared_problem_survives_reloads() {
+        let _guard = crate::test_lock();
+        let seqs: Vec<String> = ["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]
+            .iter().map(|s| s.to_string()).collect();
+        let problem = crate::align::prepare(&seqs, &crate::align::AlignParams::pam250()).unwrap();
+
+        let first = problem.run(&PAStarOpt::builder().threads(1).build().unwrap()).unwrap();
+        // Another problem replacing the globals in between
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        HeuristicHPair::destroy_instance();
+        let options = PAStarOpt::builder().threads(2).hash_type(HashType::PSum).build().unwrap();
+        let second = problem.run(&options).unwrap();
+
+        assert_eq!(second.sp_score(), first.sp_score());
+        assert_eq!(second.seqs(), first.seqs());
+    }
+
+    #[test]
+    fn test_deterministic_output_files() {
+        let _guard = crate::test_lock();
+        let inputs: [(bool, &[&str]); 2] = [
+            (false, &["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]),
+            // Zero cost matches leave wide plateaus of equal cost paths
+            (true, &["ACGTTGCAACGT", "ACGTGCAACGTT", "AGTTGCAAGT", "ACGTTCAACG"]),
+        ];
+
+        for (nucleotide, seqs) in inputs {
+            if nucleotide {
+                Cost::set_cost_nuc();
+            } else {
+                Cost::set_cost_pam250();
+            }
+            Sequences::clear();
+            for (i, seq) in seqs.iter().enumerate() {
+                Sequences::set_name(format!(">seq{}", i));
+                Sequences::set_seq(seq.to_string()).unwrap();
+            }
+            HeuristicHPair::init().unwrap();
+
+            for policy in [TiePolicy::Coord, TiePolicy::Fifo] {
+                let written: Vec<Vec<u8>> = [1, 2, 4]
+                    .into_iter()
+                    .map(|threads| {
+                        let path = std::env::temp_dir()
+                            .join(format!("deterministic_{}_{}.fasta", threads, std::process::id()));
+                        let options = PAStarOpt::builder()
+                            .threads(threads)
+                            .tie_policy(policy)
+                            .deterministic(true)
+                            .output_file(path.to_str().unwrap())
+                            .build()
+                            .unwrap();
+                        run_pastar_for_sequences(options).unwrap();
+                        let bytes = std::fs::read(&path).unwrap();
+                        std::fs::remove_file(&path).unwrap();
+                        bytes
+                    })
+                    .collect();
+
+                assert!(!written[0].is_empty());
+                assert!(written.iter().all(|bytes| *bytes == written[0]), "{:?}", policy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_first_seq_keeps_output_order() {
+        let _guard = crate::test_lock();
+        let inputs = ["VLSPADKTNVKAAWGKV", "GLSDGEWQLVLNVWGKVEAD", "VHLTPEEKSAVTALWGKV"];
+        crate::test_support::pam_fixture(&inputs);
+        let baseline = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+
+        Sequences::move_to_front(1).unwrap();
+        HeuristicHPair::init().unwrap();
+        let options = PAStarOpt::builder()
+            .threads(2)
+            .hash_type(HashType::PZorder)
+            .build()
+            .unwrap();
+        let alignment = run_pastar_for_sequences(options).unwrap();
+
+        for (row, input) in alignment.seqs().iter().zip(inputs) {
+            assert_eq!(row.replace('-', ""), input);
+        }
+        assert_eq!(alignment.sp_score(), baseline.sp_score());
+        assert_eq!(Sequences::get_seq(1), inputs[1].as_bytes());
     }
 }
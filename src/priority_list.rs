@@ -6,34 +6,61 @@
  */
 
 use std::collections::BinaryHeap;
+use std::collections::binary_heap::PeekMut;
 use crate::node::Node;
-use crate::priority_types::PriorityNode;
+use crate::priority_types::{PriorityNode, TiePolicy};
 
 pub struct PriorityList<const N: usize> {
     heap: BinaryHeap<PriorityNode<N>>,
+    policy: TiePolicy,
+    /// Nodes pushed so far, the FIFO tie key
+    pushed: u64,
 }
 
 impl<const N: usize> PriorityList<N> {
     pub fn new() -> Self {
-        PriorityList {
-            heap: BinaryHeap::new(),
-        }
+        Self::with_capacity(0)
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
         PriorityList {
             heap: BinaryHeap::with_capacity(capacity),
+            policy: TiePolicy::default(),
+            pushed: 0,
         }
     }
 
+    /// Break f ties by `policy` instead of by coordinate
+    pub fn with_tie_policy(mut self, policy: TiePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
     pub fn push(&mut self, node: Node<N>) {
-        self.heap.push(PriorityNode::new(node));
+        let tie = match self.policy {
+            TiePolicy::Coord => 0,
+            TiePolicy::HighG => (i32::MAX - node.get_g()) as u64,
+            TiePolicy::Fifo => self.pushed,
+        };
+        self.pushed += 1;
+        self.heap.push(PriorityNode::with_tie(node, tie));
     }
 
     pub fn pop(&mut self) -> Option<Node<N>> {
         self.heap.pop().map(|pn| pn.node)
     }
 
+    /// Pop the lowest-f node only if `pred` accepts its f, leaving the list
+    /// untouched otherwise, for drivers that stop at an f bound
+    pub fn pop_if(&mut self, pred: impl Fn(i32) -> bool) -> Option<Node<N>> {
+        let top = self.heap.peek_mut()?;
+        if pred(top.node.get_f()) {
+            Some(PeekMut::pop(top).node)
+        } else {
+            None
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.heap.is_empty()
     }
@@ -45,6 +72,12 @@ impl<const N: usize> PriorityList<N> {
     pub fn clear(&mut self) {
         self.heap.clear();
     }
+
+    /// Keep only the nodes for which `keep` returns true, e.g. to drop
+    /// entries a closed node with lower `g` already dominates
+    pub fn retain(&mut self, keep: impl Fn(&Node<N>) -> bool) {
+        self.heap.retain(|pn| keep(&pn.node));
+    }
 }
 
 impl<const N: usize> Default for PriorityList<N> {
@@ -77,4 +110,75 @@ mod tests {
         let popped = plist.pop().unwrap();
         assert_eq!(popped.get_f(), 15);
     }
+
+    #[test]
+    fn test_retain() {
+        let mut plist: PriorityList<2> = PriorityList::new();
+        for i in 0..20u16 {
+            let mut node = Node::with_values(i as i32, Coord::from_array([i, 0]), 0);
+            node.set_f((i as i32 * 7) % 20);
+            plist.push(node);
+        }
+
+        plist.retain(|node| node.get_g() % 2 == 0);
+        assert_eq!(plist.len(), 10);
+
+        let popped: Vec<Node<2>> = std::iter::from_fn(|| plist.pop()).collect();
+        assert!(popped.iter().all(|node| node.get_g() % 2 == 0));
+        assert!(popped.windows(2).all(|w| w[0].get_f() <= w[1].get_f()));
+    }
+
+    #[test]
+    fn test_pop_if() {
+        let mut plist: PriorityList<2> = PriorityList::new();
+        for f in [5, 3, 9] {
+            let mut node = Node::with_values(f, Coord::from_array([f as u16, 0]), 0);
+            node.set_f(f);
+            plist.push(node);
+        }
+
+        assert_eq!(plist.pop_if(|f| f <= 4).map(|node| node.get_f()), Some(3));
+        assert!(plist.pop_if(|f| f <= 4).is_none());
+        assert_eq!(plist.len(), 2);
+        assert_eq!(plist.pop().map(|node| node.get_f()), Some(5));
+
+        plist.clear();
+        assert!(plist.pop_if(|_| true).is_none());
+    }
+
+    #[test]
+    fn test_tie_policies() {
+        let nodes = [(3, [0u16, 1]), (5, [2, 0]), (4, [1, 1])];
+        let order = |policy: TiePolicy| {
+            let mut plist: PriorityList<2> = PriorityList::new().with_tie_policy(policy);
+            for (g, pos) in nodes {
+                let mut node = Node::with_values(g, Coord::from_array(pos), 0);
+                node.set_f(10);
+                plist.push(node);
+            }
+            std::iter::from_fn(|| plist.pop()).map(|node| node.get_g()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(TiePolicy::Coord), [3, 4, 5]);
+        assert_eq!(order(TiePolicy::HighG), [5, 4, 3]);
+        assert_eq!(order(TiePolicy::Fifo), [3, 5, 4]);
+    }
+
+    #[test]
+    fn test_same_coord_pops_better_node_first() {
+        let pos = Coord::from_array([2u16, 3]);
+        let entries = [(8, 3), (7, 2), (7, 1)];
+        for pushes in [entries, [entries[2], entries[0], entries[1]]] {
+            let mut plist: PriorityList<2> = PriorityList::new();
+            for (g, parenti) in pushes {
+                let mut node = Node::with_values(g, pos, parenti);
+                node.set_f(12);
+                plist.push(node);
+            }
+            let popped: Vec<(i32, i32)> = std::iter::from_fn(|| plist.pop())
+                .map(|node| (node.get_g(), node.get_parenti()))
+                .collect();
+            assert_eq!(popped, [(7, 1), (7, 2), (8, 3)]);
+        }
+    }
 }
@@ -6,22 +6,55 @@
  */
 
 use std::cmp::Ordering;
+use std::str::FromStr;
 use crate::node::Node;
 
+/// Which of the nodes with the lowest f the open list hands out first
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TiePolicy {
+    /// Lowest coordinate, reproducible across runs
+    #[default]
+    Coord,
+    /// Highest g, the node closest to the goal, then lowest coordinate
+    HighG,
+    /// The node pushed first
+    Fifo,
+}
+
+impl FromStr for TiePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "coord" => Ok(TiePolicy::Coord),
+            "high-g" => Ok(TiePolicy::HighG),
+            "fifo" => Ok(TiePolicy::Fifo),
+            _ => Err(format!("Unknown tie policy: {}", s)),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct PriorityNode<const N: usize> {
     pub node: Node<N>,
+    /// Secondary key among equal f, lower first (see `TiePolicy`)
+    pub tie: u64,
 }
 
 impl<const N: usize> PriorityNode<N> {
     pub fn new(node: Node<N>) -> Self {
-        PriorityNode { node }
+        PriorityNode { node, tie: 0 }
+    }
+
+    pub fn with_tie(node: Node<N>, tie: u64) -> Self {
+        PriorityNode { node, tie }
     }
 }
 
 impl<const N: usize> PartialEq for PriorityNode<N> {
     fn eq(&self, other: &Self) -> bool {
-        self.node.pos == other.node.pos
+        self.cmp(other) == Ordering::Equal
     }
 }
 
@@ -35,9 +68,13 @@ impl<const N: usize> PartialOrd for PriorityNode<N> {
 
 impl<const N: usize> Ord for PriorityNode<N> {
     fn cmp(&self, other: &Self) -> Ordering {
-        // Reverse ordering for min-heap (lower f value = higher priority)
+        // Reverse ordering for min-heap (lower f value = higher priority).
+        // Two entries for one coordinate pop the better node first, so the
+        // order is total and doesn't depend on the order of the pushes.
         other.node.get_f().cmp(&self.node.get_f())
+            .then_with(|| other.tie.cmp(&self.tie))
             .then_with(|| other.node.pos.cmp(&self.node.pos))
+            .then_with(|| (other.node.get_g(), other.node.get_parenti()).cmp(&(self.node.get_g(), self.node.get_parenti())))
     }
 }
 
@@ -0,0 +1,261 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Profile-to-profile alignment: align two existing alignments to
+ * each other while keeping their internal columns fixed
+ */
+
+use std::cmp::min;
+use crate::alignment::Alignment;
+use crate::backtrace;
+use crate::cost::{Cost, ScoringScheme};
+use crate::msa_options::AStarOpt;
+use crate::read_fasta::read_fasta_records;
+use crate::sequences::Sequences;
+use crate::time_counter::TimeCounter;
+
+/// An existing alignment seen as a sequence of residue distributions
+pub struct Profile {
+    names: Vec<String>,
+    rows: Vec<Vec<u8>>,
+    /// Per column `(residue, count)` pairs, gaps included as `b'-'`
+    columns: Vec<Vec<(u8, usize)>>,
+}
+
+impl Profile {
+    /// Build a profile from aligned rows, which must all have the same length
+    pub fn new(names: Vec<String>, rows: Vec<String>) -> Result<Self, String> {
+        if rows.is_empty() {
+            return Err("Profile has no sequences".to_string());
+        }
+        if names.len() != rows.len() {
+            return Err(format!("Got {} names for {} profile rows", names.len(), rows.len()));
+        }
+
+        let rows: Vec<Vec<u8>> = rows.into_iter().map(String::into_bytes).collect();
+        let len = rows[0].len();
+        if let Some(i) = rows.iter().position(|row| row.len() != len) {
+            return Err(format!(
+                "Profile row {} has length {}, expected {}", i, rows[i].len(), len
+            ));
+        }
+
+        let columns = (0..len)
+            .map(|col| {
+                let mut counts: Vec<(u8, usize)> = Vec::new();
+                for row in &rows {
+                    match counts.iter_mut().find(|(c, _)| *c == row[col]) {
+                        Some((_, n)) => *n += 1,
+                        None => counts.push((row[col], 1)),
+                    }
+                }
+                counts
+            })
+            .collect();
+
+        Ok(Profile { names, rows, columns })
+    }
+
+    /// Read an aligned FASTA file as a profile
+    pub fn from_fasta_file(filename: &str) -> Result<Self, String> {
+        let (names, rows) = read_fasta_records(filename)?.into_iter().unzip();
+        Profile::new(names, rows)
+            .map_err(|e| format!("Invalid profile {}: {}", filename, e))
+    }
+
+    pub fn seq_num(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+}
+
+/// Cost of all pairs inside one column
+fn within_cost(costs: &ScoringScheme, column: &[(u8, usize)]) -> i32 {
+    let mut total = 0;
+    for (k, &(x, nx)) in column.iter().enumerate() {
+        total += costs.cost(x, x) * (nx * nx.saturating_sub(1) / 2) as i32;
+        for &(y, ny) in &column[k + 1..] {
+            total += costs.cost(x, y) * (nx * ny) as i32;
+        }
+    }
+    total
+}
+
+/// Cost of all pairs with one row from each column
+fn cross_cost(costs: &ScoringScheme, a: &[(u8, usize)], b: &[(u8, usize)]) -> i32 {
+    let mut total = 0;
+    for &(x, nx) in a {
+        for &(y, ny) in b {
+            total += costs.cost(x, y) * (nx * ny) as i32;
+        }
+    }
+    total
+}
+
+/// Align two profiles, minimizing the sum-of-pairs cost of the merged
+/// alignment. Columns of each profile are never split, only gap columns
+/// are inserted between them.
+pub fn align_profiles(a: &Profile, b: &Profile) -> Alignment {
+    let costs = Cost::matrix_snapshot();
+    let (a_len, b_len) = (a.len(), b.len());
+    let gaps_a = vec![(b'-', a.seq_num())];
+    let gaps_b = vec![(b'-', b.seq_num())];
+
+    // Cost of a column of `a` against a gap column of `b`, and vice versa
+    let within_gaps_a = within_cost(&costs, &gaps_a);
+    let within_gaps_b = within_cost(&costs, &gaps_b);
+    let a_vs_gap: Vec<i32> = a.columns.iter()
+        .map(|col| within_cost(&costs, col) + within_gaps_b + cross_cost(&costs, col, &gaps_b))
+        .collect();
+    let gap_vs_b: Vec<i32> = b.columns.iter()
+        .map(|col| within_cost(&costs, col) + within_gaps_a + cross_cost(&costs, &gaps_a, col))
+        .collect();
+    let within_a: Vec<i32> = a.columns.iter().map(|col| within_cost(&costs, col)).collect();
+    let within_b: Vec<i32> = b.columns.iter().map(|col| within_cost(&costs, col)).collect();
+
+    // Prefix cost matrix
+    let mut matrix = vec![vec![0i32; b_len + 1]; a_len + 1];
+    for i in 1..=a_len {
+        matrix[i][0] = matrix[i - 1][0] + a_vs_gap[i - 1];
+    }
+    for j in 1..=b_len {
+        matrix[0][j] = matrix[0][j - 1] + gap_vs_b[j - 1];
+    }
+    for i in 1..=a_len {
+        for j in 1..=b_len {
+            let diag = matrix[i - 1][j - 1] + within_a[i - 1] + within_b[j - 1]
+                + cross_cost(&costs, &a.columns[i - 1], &b.columns[j - 1]);
+            let up = matrix[i - 1][j] + a_vs_gap[i - 1];
+            let left = matrix[i][j - 1] + gap_vs_b[j - 1];
+            matrix[i][j] = min(diag, min(up, left));
+        }
+    }
+
+    // Traceback from the end, building columns in reverse
+    let mut out: Vec<Vec<u8>> = vec![Vec::new(); a.seq_num() + b.seq_num()];
+    let (mut i, mut j) = (a_len, b_len);
+    while i > 0 || j > 0 {
+        let take_a;
+        let take_b;
+        if i > 0 && j > 0 && matrix[i][j] == matrix[i - 1][j - 1] + within_a[i - 1]
+            + within_b[j - 1] + cross_cost(&costs, &a.columns[i - 1], &b.columns[j - 1]) {
+            (take_a, take_b) = (true, true);
+        } else if i > 0 && matrix[i][j] == matrix[i - 1][j] + a_vs_gap[i - 1] {
+            (take_a, take_b) = (true, false);
+        } else {
+            (take_a, take_b) = (false, true);
+        }
+
+        for (k, row) in a.rows.iter().enumerate() {
+            out[k].push(if take_a { row[i - 1] } else { b'-' });
+        }
+        for (k, row) in b.rows.iter().enumerate() {
+            out[a.seq_num() + k].push(if take_b { row[j - 1] } else { b'-' });
+        }
+        if take_a {
+            i -= 1;
+        }
+        if take_b {
+            j -= 1;
+        }
+    }
+
+    Alignment::new(
+        out.into_iter()
+            .map(|mut row| {
+                row.reverse();
+                String::from_utf8_lossy(&row).to_string()
+            })
+            .collect(),
+    )
+}
+
+/// Read two aligned FASTA files, align them as profiles and report the
+/// result. The global sequences are replaced by the degapped profile rows
+/// so the output keeps their names.
+pub fn run_profile_alignment(
+    file_a: &str,
+    file_b: &str,
+    options: &AStarOpt,
+) -> Result<Alignment, String> {
+    let a = Profile::from_fasta_file(file_a)?;
+    let b = Profile::from_fasta_file(file_b)?;
+    crate::info!("Profile {}: {} sequences, {} columns", file_a, a.seq_num(), a.len());
+    crate::info!("Profile {}: {} sequences, {} columns", file_b, b.seq_num(), b.len());
+
+    Sequences::clear();
+    for (name, row) in a.names.iter().zip(&a.rows).chain(b.names.iter().zip(&b.rows)) {
+        Sequences::set_name(name.clone());
+        Sequences::set_seq(row.iter().filter(|&&c| c != b'-').map(|&c| c as char).collect())?;
+    }
+
+    let _timer = TimeCounter::new("\nProfile alignment running time:");
+    let alignment = align_profiles(&a, &b);
+    backtrace::report_alignment(&alignment, options)?;
+    Ok(alignment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pair_align::PairAlign;
+
+    fn profile(rows: &[&str]) -> Profile {
+        let names = (0..rows.len()).map(|i| format!(">p{}", i)).collect();
+        Profile::new(names, rows.iter().map(|r| r.to_string()).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_single_sequence_profiles_match_pairwise() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+
+        let (s1, s2) = ("VLSPADKTNVKAAWGKV", "VHLTPEEKSAVTALWGKV");
+        let alignment = align_profiles(&profile(&[s1]), &profile(&[s2]));
+        let pair = PairAlign::new((0, 1), s1.as_bytes(), s2.as_bytes());
+
+        assert_eq!(alignment.sp_score(), pair.get_final_score());
+        assert_eq!(alignment.seqs()[0].replace('-', ""), s1);
+        assert_eq!(alignment.seqs()[1].replace('-', ""), s2);
+    }
+
+    #[test]
+    fn test_profile_columns_kept() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+
+        let a = ["AC-GT", "ACTGT"];
+        let b = ["AGT", "A-T", "ACT"];
+        let alignment = align_profiles(&profile(&a), &profile(&b));
+        let rows = alignment.seqs();
+        assert_eq!(rows.len(), 5);
+
+        // Dropping the inserted gap columns gives back each profile
+        for (part, inputs) in [(&rows[..2], &a[..]), (&rows[2..], &b[..])] {
+            let kept: Vec<usize> = (0..rows[0].len())
+                .filter(|&c| part.iter().any(|r| r.as_bytes()[c] != b'-'))
+                .collect();
+            for (row, input) in part.iter().zip(inputs) {
+                let restored: String = kept.iter().map(|&c| row.as_bytes()[c] as char).collect();
+                assert_eq!(&restored, input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_profile_rows_must_match() {
+        let result = Profile::new(
+            vec![">a".to_string(), ">b".to_string()],
+            vec!["ACG".to_string(), "AC".to_string()],
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,64 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Python bindings (`python` feature): `import astar_msa`
+ */
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use crate::align::AlignParams;
+use crate::cost::Matrix;
+
+/// align(sequences, matrix="pam250", gap_open=None, threads=1)
+///
+/// Align the sequences and return the aligned rows in input order.
+/// `matrix` is "pam250", "blosum62" or "nuc"; `gap_open` overrides the
+/// matrix's residue/gap cost.
+#[pyfunction]
+#[pyo3(signature = (sequences, matrix = "pam250", gap_open = None, threads = 1))]
+fn align(sequences: Vec<String>, matrix: &str, gap_open: Option<i32>, threads: usize) -> PyResult<Vec<String>> {
+    let matrix: Matrix = matrix.parse().map_err(PyValueError::new_err)?;
+    let mut params = AlignParams::new(matrix).threads(threads);
+    if let Some(gap_cost) = gap_open {
+        params = params.gap_cost(gap_cost);
+    }
+
+    let alignment = params.align(&sequences).map_err(PyValueError::new_err)?;
+    Ok(alignment.seqs().to_vec())
+}
+
+#[pymodule]
+fn astar_msa(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", crate::VERSION)?;
+    m.add_function(wrap_pyfunction!(align, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_align() {
+        let _guard = crate::test_lock();
+        pyo3::prepare_freethreaded_python();
+
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "astar_msa").unwrap();
+            astar_msa(&module).unwrap();
+
+            let seqs = vec!["ACGT".to_string(), "AGT".to_string()];
+            let rows: Vec<String> = module
+                .getattr("align").unwrap()
+                .call1((seqs, "nuc"))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(rows, vec!["ACGT".to_string(), "A-GT".to_string()]);
+
+            let err = module.getattr("align").unwrap().call1((vec!["AC", "AG"], "blosum"));
+            assert!(err.unwrap_err().is_instance_of::<PyValueError>(py));
+        });
+    }
+}
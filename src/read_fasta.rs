@@ -2,22 +2,65 @@
  * \author Vinícius Manoel
  * \copyright MIT License
  *
- * \brief Read FASTA format files
+ * \brief Read FASTA format files, and CLUSTAL alignments in their place
  */
 
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use crate::sequences::Sequences;
 
-pub fn read_fasta_file<P: AsRef<Path>>(filename: P) -> Result<(), String> {
+/// Whether `c` may appear in a sequence line: a residue, a gap or a stop
+/// codon, the latter two handled by `Sequences::normalize`
+fn is_sequence_char(c: u8) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, b'-' | b'.' | b'*')
+}
+
+/// The sequence characters of line `line_num` (1-based). Anything else, like
+/// a `>` left by a bad concatenation, is dropped with a warning, or with
+/// `strict` rejected.
+fn sequence_line(line: &[u8], line_num: usize, strict: bool) -> Result<Cow<'_, [u8]>, String> {
+    match line.iter().position(|&c| !is_sequence_char(c)) {
+        None => Ok(Cow::Borrowed(line)),
+        Some(pos) if strict => Err(format!(
+            "Line {}, column {}: unexpected character {:?} in sequence",
+            line_num, pos + 1, String::from_utf8_lossy(&line[pos..]).chars().next().unwrap()
+        )),
+        Some(_) => {
+            let kept: Vec<u8> = line.iter().copied().filter(|&c| is_sequence_char(c)).collect();
+            eprintln!("Warning: {}", dropped_warning(line, &kept, line_num));
+            Ok(Cow::Owned(kept))
+        }
+    }
+}
+
+/// Warning for a sequence line that held characters that can't be
+/// residues. Its letters are still kept, so a header that lost its line
+/// break or its `>` ends up in the sequence.
+fn dropped_warning(line: &[u8], kept: &[u8], line_num: usize) -> String {
+    let dropped: String = String::from_utf8_lossy(line).chars()
+        .filter(|&c| !c.is_ascii() || !is_sequence_char(c as u8))
+        .collect();
+    format!(
+        "Line {}: dropped {:?} from a sequence line and kept {:?}; if it was a header, it's now part of the sequence",
+        line_num, dropped, String::from_utf8_lossy(kept)
+    )
+}
+
+/// Load every sequence of a FASTA file. Characters that can't be residues
+/// are stripped from sequence lines, or with `strict` reported as an error.
+pub fn read_fasta_file<P: AsRef<Path>>(filename: P, strict: bool) -> Result<(), String> {
+    if is_clustal(&filename)? {
+        return read_clustal_file(filename, true);
+    }
     let file = File::open(&filename)
         .map_err(|e| format!("Can't open file {:?}: {}", filename.as_ref(), e))?;
     
     let reader = BufReader::new(file);
     let mut current_seq = String::new();
     
-    for line in reader.lines() {
+    for (line_num, line) in reader.lines().enumerate() {
         let line = line.map_err(|e| format!("Error reading line: {}", e))?;
         let line = line.trim();
         
@@ -43,7 +86,8 @@ pub fn read_fasta_file<P: AsRef<Path>>(filename: P) -> Result<(), String> {
             Sequences::set_name(line.to_string());
         } else {
             // Append to current sequence
-            current_seq.push_str(line);
+            let residues = sequence_line(line.as_bytes(), line_num + 1, strict)?;
+            current_seq.extend(residues.iter().map(|&c| c as char));
         }
     }
     
@@ -57,14 +101,540 @@ pub fn read_fasta_file<P: AsRef<Path>>(filename: P) -> Result<(), String> {
     Ok(())
 }
 
+/// Same result as `read_fasta_file`, but builds each sequence directly as
+/// bytes: the buffer is reserved from the remaining file size and
+/// uppercased in place, so no intermediate `String` copies are made.
+/// Meant for genome-scale inputs.
+pub fn read_fasta_file_bytes<P: AsRef<Path>>(filename: P, strict: bool) -> Result<(), String> {
+    if is_clustal(&filename)? {
+        return read_clustal_file(filename, true);
+    }
+    let file = File::open(&filename)
+        .map_err(|e| format!("Can't open file {:?}: {}", filename.as_ref(), e))?;
+    let file_size = file.metadata().map(|m| m.len() as usize).unwrap_or(0);
+
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let mut current_seq: Vec<u8> = Vec::new();
+    let mut consumed = 0;
+    let mut line_num = 0;
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)
+            .map_err(|e| format!("Error reading line: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        consumed += read;
+        line_num += 1;
+        let line = line.trim_ascii();
+
+        if line.is_empty() || line[0] == b'>' {
+            store_seq_bytes(&mut current_seq)?;
+            if !line.is_empty() {
+                Sequences::set_name(String::from_utf8_lossy(line).into_owned());
+            }
+            continue;
+        }
+
+        if current_seq.capacity() == 0 {
+            current_seq.reserve(file_size.saturating_sub(consumed - read));
+        }
+        let start = current_seq.len();
+        current_seq.extend_from_slice(&sequence_line(line, line_num, strict)?);
+        current_seq[start..].make_ascii_uppercase();
+    }
+
+    // Don't forget the last sequence
+    store_seq_bytes(&mut current_seq)
+}
+
+/// Hand a finished sequence over to `Sequences`, releasing unused capacity
+fn store_seq_bytes(current_seq: &mut Vec<u8>) -> Result<(), String> {
+    if current_seq.is_empty() {
+        return Ok(());
+    }
+    let mut seq = std::mem::take(current_seq);
+    seq.shrink_to_fit();
+    Sequences::set_seq_bytes(seq)
+        .map_err(|e| format!("Error setting sequence: {}", e))
+}
+
+/// Load sequences given directly (e.g. on the command line) instead of a
+/// FASTA file. Missing names default to "Sequence <n>".
+pub fn read_inline_sequences(seqs: &[String], names: &[String]) -> Result<(), String> {
+    if seqs.len() < 2 {
+        return Err(format!("Need at least 2 sequences, got {}", seqs.len()));
+    }
+    if names.len() > seqs.len() {
+        return Err(format!("Got {} names for {} sequences", names.len(), seqs.len()));
+    }
+
+    for (i, seq) in seqs.iter().enumerate() {
+        let name = match names.get(i) {
+            Some(name) if name.starts_with('>') => name.clone(),
+            Some(name) => format!(">{}", name),
+            None => format!(">Sequence {}", i + 1),
+        };
+        Sequences::set_name(name);
+        Sequences::set_seq(seq.trim().to_uppercase())
+            .map_err(|e| format!("Error setting sequence: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Read a FASTA file into `(name, sequence)` records without touching the
+/// global sequences. Gaps are kept, so this also reads aligned FASTA, and
+/// CLUSTAL files are read with `read_clustal_records`.
+pub fn read_fasta_records<P: AsRef<Path>>(filename: P) -> Result<Vec<(String, String)>, String> {
+    if is_clustal(&filename)? {
+        return read_clustal_records(filename);
+    }
+    let file = File::open(&filename)
+        .map_err(|e| format!("Can't open file {:?}: {}", filename.as_ref(), e))?;
+
+    let reader = BufReader::new(file);
+    let mut records: Vec<(String, String)> = Vec::new();
+
+    for line in reader.lines() {
+        let line = line.map_err(|e| format!("Error reading line: {}", e))?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('>') {
+            records.push((line.to_string(), String::new()));
+        } else {
+            if records.is_empty() {
+                records.push((">Sequence 1".to_string(), String::new()));
+            }
+            if let Some((_, seq)) = records.last_mut() {
+                seq.push_str(&line.to_uppercase());
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+/// Whether the first non-empty line of the file is a CLUSTAL header
+fn is_clustal<P: AsRef<Path>>(filename: P) -> Result<bool, String> {
+    let file = File::open(&filename)
+        .map_err(|e| format!("Can't open file {:?}: {}", filename.as_ref(), e))?;
+
+    for line in BufReader::new(file).split(b'\n') {
+        let line = line.map_err(|e| format!("Error reading line: {}", e))?;
+        let line = line.trim_ascii();
+        if !line.is_empty() {
+            return Ok(line.starts_with(b"CLUSTAL"));
+        }
+    }
+    Ok(false)
+}
+
+/// Read a CLUSTAL alignment into `(name, row)` records, joining each
+/// sequence's lines across the interleaved blocks. Names get a leading
+/// `>` like FASTA headers; conservation lines and residue counts are
+/// skipped.
+pub fn read_clustal_records<P: AsRef<Path>>(filename: P) -> Result<Vec<(String, String)>, String> {
+    let file = File::open(&filename)
+        .map_err(|e| format!("Can't open file {:?}: {}", filename.as_ref(), e))?;
+
+    let mut records: Vec<(String, String)> = Vec::new();
+    for (line_num, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Error reading line: {}", e))?;
+        // Conservation lines start with spaces
+        if line.trim().is_empty() || line.starts_with(char::is_whitespace) || line.starts_with("CLUSTAL") {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(row)) = (fields.next(), fields.next()) else {
+            return Err(format!("CLUSTAL file {:?}, line {}: expected a name and residues", filename.as_ref(), line_num + 1));
+        };
+        if fields.next().is_some_and(|count| count.parse::<usize>().is_err()) {
+            return Err(format!("CLUSTAL file {:?}, line {}: unexpected text after the residues", filename.as_ref(), line_num + 1));
+        }
+
+        let name = format!(">{}", name);
+        match records.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, seq)) => seq.push_str(&row.to_uppercase()),
+            None => records.push((name, row.to_uppercase())),
+        }
+    }
+
+    Ok(records)
+}
+
+/// Load the sequences of a CLUSTAL alignment, without their gaps if `degap`
+pub fn read_clustal_file<P: AsRef<Path>>(filename: P, degap: bool) -> Result<(), String> {
+    for (name, mut row) in read_clustal_records(filename)? {
+        if degap {
+            row.retain(|c| c != '-' && c != '.');
+        }
+        Sequences::set_name(name);
+        Sequences::set_seq(row).map_err(|e| format!("Error setting sequence: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Read gap profiles for the loaded sequences: FASTA-style records named
+/// like the sequences, each holding one whitespace separated gap cost per
+/// residue. Sequences without a record keep the matrix's gap cost.
+pub fn read_gap_profile<P: AsRef<Path>>(filename: P) -> Result<(), String> {
+    let file = File::open(&filename)
+        .map_err(|e| format!("Can't open file {:?}: {}", filename.as_ref(), e))?;
+
+    let mut profiles: Vec<(String, Vec<i32>)> = Vec::new();
+    for (line_num, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Error reading line: {}", e))?;
+        let line = line.trim();
+        if line.starts_with('>') {
+            profiles.push((line.to_string(), Vec::new()));
+            continue;
+        }
+        for value in line.split_whitespace() {
+            let Some((_, profile)) = profiles.last_mut() else {
+                return Err(format!("Gap profile {:?}: values before the first '>' header", filename.as_ref()));
+            };
+            let cost = value.parse().map_err(|_| format!(
+                "Gap profile {:?}, line {}: invalid cost '{}'", filename.as_ref(), line_num + 1, value
+            ))?;
+            profile.push(cost);
+        }
+    }
+
+    for (name, profile) in profiles {
+        let index = (0..Sequences::get_seq_num())
+            .find(|&i| Sequences::get_seq_name(i) == name)
+            .ok_or_else(|| format!("Gap profile {:?}: no sequence named {}", filename.as_ref(), name))?;
+        Sequences::set_gap_profile(index, profile)?;
+    }
+    Ok(())
+}
+
+/// Read secondary structure labels (H, E or C per residue) and attach
+/// them to the loaded sequences of the same name. Records look like FASTA:
+/// the sequence's `>` header line followed by its labels, whitespace is
+/// ignored. Sequences without a record stay unlabeled.
+pub fn read_ss_file<P: AsRef<Path>>(filename: P) -> Result<(), String> {
+    let file = File::open(&filename)
+        .map_err(|e| format!("Can't open file {:?}: {}", filename.as_ref(), e))?;
+
+    let mut records: Vec<(String, Vec<u8>)> = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Error reading line: {}", e))?;
+        let line = line.trim();
+        if line.starts_with('>') {
+            records.push((line.to_string(), Vec::new()));
+            continue;
+        }
+        let labels = line.bytes().filter(|c| !c.is_ascii_whitespace()).map(|c| c.to_ascii_uppercase());
+        match records.last_mut() {
+            Some((_, record)) => record.extend(labels),
+            None if line.is_empty() => {}
+            None => return Err(format!("Structure file {:?}: labels before the first '>' header", filename.as_ref())),
+        }
+    }
+
+    for (name, labels) in records {
+        let index = (0..Sequences::get_seq_num())
+            .find(|&i| Sequences::get_seq_name(i) == name)
+            .ok_or_else(|| format!("Structure file {:?}: no sequence named {}", filename.as_ref(), name))?;
+        Sequences::set_ss_labels(index, labels)?;
+    }
+    Ok(())
+}
+
+/// Read an aligned FASTA file, checking that every row has the same length
+pub fn read_aligned_fasta<P: AsRef<Path>>(filename: P) -> Result<Vec<(String, String)>, String> {
+    let records = read_fasta_records(&filename)?;
+    if records.len() < 2 {
+        return Err(format!("Alignment {:?} has fewer than 2 sequences", filename.as_ref()));
+    }
+
+    let len = records[0].1.len();
+    if let Some(i) = records.iter().position(|(_, row)| row.len() != len) {
+        return Err(format!(
+            "Alignment {:?}: row {} has length {}, expected {}",
+            filename.as_ref(), i, records[i].1.len(), len
+        ));
+    }
+
+    Ok(records)
+}
+
+/// One line of a samtools-style `.fai` index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaiRecord {
+    /// Header up to the first whitespace, without the `>`
+    pub name: String,
+    /// Number of residues
+    pub length: u64,
+    /// Byte offset of the first residue
+    pub offset: u64,
+    /// Residues per full line
+    pub line_bases: u64,
+    /// Bytes per full line, line ending and trailing whitespace included
+    pub line_width: u64,
+}
+
+/// Index every record of a FASTA file. CRLF endings and whitespace around
+/// the residues are fine as long as all full lines of a record are laid out
+/// alike; only the last line may be shorter, as random access needs.
+pub fn build_fasta_index<P: AsRef<Path>>(filename: P) -> Result<Vec<FaiRecord>, String> {
+    let file = File::open(&filename)
+        .map_err(|e| format!("Can't open file {:?}: {}", filename.as_ref(), e))?;
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let mut records: Vec<FaiRecord> = Vec::new();
+    // Set once a record had a short (last) line
+    let mut ended = false;
+    let (mut offset, mut line_num) = (0u64, 0);
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)
+            .map_err(|e| format!("Error reading line: {}", e))? as u64;
+        if read == 0 {
+            break;
+        }
+        offset += read;
+        line_num += 1;
+        let leading = line.iter().take_while(|c| c.is_ascii_whitespace()).count() as u64;
+        let bases = line.trim_ascii().len() as u64;
+
+        if line.trim_ascii_start().first() == Some(&b'>') {
+            let header = String::from_utf8_lossy(&line.trim_ascii()[1..]).into_owned();
+            let name = header.split_whitespace().next().unwrap_or("").to_string();
+            records.push(FaiRecord { name, length: 0, offset, line_bases: 0, line_width: 0 });
+            ended = false;
+            continue;
+        }
+        let Some(record) = records.last_mut() else {
+            if bases == 0 {
+                continue;
+            }
+            return Err(format!("FASTA index {:?}: sequence before the first '>' header", filename.as_ref()));
+        };
+        if bases == 0 {
+            ended = record.length > 0;
+            continue;
+        }
+        if leading > 0 {
+            return Err(format!("FASTA index {:?}, line {}: sequence line starts with whitespace", filename.as_ref(), line_num));
+        }
+
+        if record.length == 0 {
+            record.offset = offset - read;
+            record.line_bases = bases;
+            record.line_width = read;
+        } else if ended || bases > record.line_bases || (bases == record.line_bases && read != record.line_width) {
+            return Err(format!(
+                "FASTA index {:?}, line {}: record {} has lines of different lengths",
+                filename.as_ref(), line_num, record.name
+            ));
+        }
+        ended = bases < record.line_bases;
+        record.length += bases;
+    }
+
+    Ok(records)
+}
+
+/// Write the index of `filename` next to it as `<filename>.fai`
+pub fn write_fasta_index<P: AsRef<Path>>(filename: P) -> Result<PathBuf, String> {
+    let records = build_fasta_index(&filename)?;
+    let mut path = filename.as_ref().as_os_str().to_owned();
+    path.push(".fai");
+    let path = PathBuf::from(path);
+
+    let text: String = records.iter()
+        .map(|r| format!("{}\t{}\t{}\t{}\t{}\n", r.name, r.length, r.offset, r.line_bases, r.line_width))
+        .collect();
+    std::fs::write(&path, text).map_err(|e| format!("Error writing index {:?}: {}", path, e))?;
+    Ok(path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::astar;
+    use crate::cost::Cost;
+    use crate::heuristic_hpair::HeuristicHPair;
+    use crate::msa_options::AStarOpt;
+
+    #[test]
+    fn test_read_fasta_missing_file() {
+        let result = read_fasta_file("does/not/exist.fasta", false);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_read_fasta_file_bytes_matches() {
+        let _guard = crate::test_lock();
+        let path = std::env::temp_dir().join(format!("read_fasta_bytes_{}.fasta", std::process::id()));
+        std::fs::write(&path, ">first seq\r\nacgt\r\nNNac\n\n  GGT  \n>second\nMKV\nlw\n").unwrap();
+
+        let load = |reader: fn(&std::path::Path) -> Result<(), String>, file: &std::path::Path| {
+            Sequences::clear();
+            reader(file).unwrap();
+            (0..Sequences::get_seq_num())
+                .map(|i| (Sequences::get_seq_name(i), Sequences::get_seq(i)))
+                .collect::<Vec<_>>()
+        };
+
+        for file in [path.as_path(), std::path::Path::new("data/seqs/PAM/3/PF10550.fasta")] {
+            let by_string = load(|p| read_fasta_file(p, false), file);
+            let by_bytes = load(|p| read_fasta_file_bytes(p, false), file);
+            assert_eq!(by_string, by_bytes);
+        }
+        assert_eq!(load(|p| read_fasta_file_bytes(p, false), &path)[0], (">first seq".to_string(), b"ACGTNNAC".to_vec()));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_strict_fasta() {
+        let _guard = crate::test_lock();
+        let path = std::env::temp_dir().join(format!("read_fasta_strict_{}.fasta", std::process::id()));
+        std::fs::write(&path, ">first\nACGT\n>second\nAC>second copy\nGT\n").unwrap();
+
+        for reader in [read_fasta_file::<&std::path::Path>, read_fasta_file_bytes] {
+            Sequences::clear();
+            let err = reader(&path, true).unwrap_err();
+            assert_eq!(err, "Line 4, column 3: unexpected character '>' in sequence");
+
+            Sequences::clear();
+            reader(&path, false).unwrap();
+            assert_eq!(Sequences::get_seq_num(), 2);
+        }
+        // The lenient read keeps the letters of the merged header, and says so
+        let line = b"AC>second copy";
+        let kept = sequence_line(line, 4, false).unwrap();
+        assert_eq!(
+            dropped_warning(line, &kept, 4),
+            "Line 4: dropped \"> \" from a sequence line and kept \"ACsecondcopy\"; if it was a header, it's now part of the sequence"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
 
     #[test]
-    fn test_read_fasta() {
-        // This test would require a test FASTA file
-        // For now, just ensure the function compiles
-        assert!(true);
+    fn test_fasta_index() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("fasta_index_{}.fasta", std::process::id()));
+        std::fs::write(&path, ">s1 first record\nACGT\nAC\n\n>s2\r\nAAAA\r\nA\r\n").unwrap();
+
+        let index = write_fasta_index(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&index).unwrap(),
+            "s1\t6\t17\t4\t5\ns2\t5\t31\t4\t6\n"
+        );
+        let text = std::fs::read(&path).unwrap();
+        assert_eq!(&text[17..21], b"ACGT");
+        assert_eq!(&text[31..35], b"AAAA");
+
+        // A full-length line after a short one can't be seeked to
+        std::fs::write(&path, ">s1\nACGT\nAC\nACGT\n").unwrap();
+        assert!(build_fasta_index(&path).unwrap_err().contains("line 4"));
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&index).unwrap();
+    }
+
+    #[test]
+    fn test_read_inline_sequences() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+
+        let seqs = vec!["acgt".to_string(), "AGCT".to_string(), "ACCT".to_string()];
+        read_inline_sequences(&seqs, &["first".to_string()]).unwrap();
+
+        assert_eq!(Sequences::get_seq_num(), 3);
+        assert_eq!(Sequences::get_seq(0), b"ACGT");
+        assert_eq!(Sequences::get_seq_name(0), ">first");
+        assert_eq!(Sequences::get_seq_name(2), ">Sequence 3");
+
+        HeuristicHPair::init().unwrap();
+        let alignment = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+        assert_eq!(alignment.seq_num(), 3);
+        let len = alignment.seqs()[0].len();
+        assert!(alignment.seqs().iter().all(|s| s.len() == len));
+    }
+
+    #[test]
+    fn test_read_inline_sequences_needs_two() {
+        let result = read_inline_sequences(&["ACGT".to_string()], &[]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_read_clustal() {
+        let _guard = crate::test_lock();
+        let path = std::env::temp_dir().join(format!("read_clustal_{}.aln", std::process::id()));
+        std::fs::write(&path, "CLUSTAL W (1.83) multiple sequence alignment\n\n\
+            seq1      ACGT-A 5\n\
+            seq2      AC-TTA 5\n\
+            \x20         ** * *\n\n\
+            seq1      cc 7\n\
+            seq2      C- 6\n").unwrap();
+
+        let records = read_fasta_records(&path).unwrap();
+        assert_eq!(records, [(">seq1".to_string(), "ACGT-ACC".to_string()), (">seq2".to_string(), "AC-TTAC-".to_string())]);
+
+        Sequences::clear();
+        read_fasta_file_bytes(&path, false).unwrap();
+        assert_eq!(Sequences::get_seq_num(), 2);
+        assert_eq!(Sequences::get_seq_name(1), ">seq2");
+        assert_eq!(Sequences::get_seq(0), b"ACGTACC");
+        assert_eq!(Sequences::get_seq(1), b"ACTTAC");
+
+        std::fs::write(&path, "CLUSTAL\n\nseq1 ACGT x\n").unwrap();
+        assert!(read_clustal_records(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_gap_profile() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        read_inline_sequences(&["ACGT".to_string(), "AGT".to_string()], &["a".to_string(), "b".to_string()]).unwrap();
+
+        let path = std::env::temp_dir().join(format!("gap_profile_{}.txt", std::process::id()));
+        std::fs::write(&path, ">b\n5 5\n  7\n").unwrap();
+        read_gap_profile(&path).unwrap();
+        assert_eq!(Sequences::get_gap_costs(0), [2; 5]);
+        assert_eq!(Sequences::get_gap_costs(1), [5, 5, 5, 7]);
+
+        std::fs::write(&path, ">c\n1 1 1\n").unwrap();
+        assert!(read_gap_profile(&path).unwrap_err().contains("no sequence named >c"));
+        std::fs::write(&path, ">a\n1 x 1 1\n").unwrap();
+        assert!(read_gap_profile(&path).unwrap_err().contains("invalid cost 'x'"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_ss_file() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        read_inline_sequences(&["ACGT".to_string(), "AGT".to_string()], &["a".to_string(), "b".to_string()]).unwrap();
+
+        let path = std::env::temp_dir().join(format!("ss_labels_{}.txt", std::process::id()));
+        std::fs::write(&path, ">a\nHH\nec\n").unwrap();
+        read_ss_file(&path).unwrap();
+        assert_eq!(Sequences::get_ss_labels(0), b"HHEC");
+        assert!(Sequences::get_ss_labels(1).is_empty());
+        assert_eq!(Sequences::get_ss_label(1, 0), 0);
+
+        std::fs::write(&path, ">b\nHXC\n").unwrap();
+        assert!(read_ss_file(&path).unwrap_err().contains("label 'X'"));
+        std::fs::write(&path, ">b\nHH\n").unwrap();
+        assert!(read_ss_file(&path).unwrap_err().contains("has 2 labels, expected 3"));
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,211 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief On-disk cache of finished alignments, keyed on the loaded
+ * sequences and everything that changes how they are scored
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::alignment::{AlignMode, Alignment, SearchScores};
+use crate::backtrace;
+use crate::msa_options::AStarOpt;
+use crate::sequences::Sequences;
+
+/// Bumped whenever the key inputs or the file layout change
+const CACHE_VERSION: u64 = 4;
+
+/// FNV-1a, stable across runs and builds unlike the std and ahash hashers
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_i64(&mut self, value: i64) {
+        self.write(&value.to_le_bytes());
+    }
+}
+
+/// Hash of the loaded sequences in search order, the cost matrix, the gap
+/// costs at every position, the structure labels and the options that pick
+/// the algorithm or constrain its result
+pub fn cache_key(options: &AStarOpt) -> String {
+    let mut hash = Fnv::new();
+    hash.write_i64(CACHE_VERSION as i64);
+
+    let seq_num = Sequences::get_seq_num();
+    hash.write_i64(seq_num as i64);
+    for i in 0..seq_num {
+        let seq = Sequences::get_seq(i);
+        hash.write_i64(seq.len() as i64);
+        hash.write(&seq);
+        for cost in Sequences::get_gap_costs(i) {
+            hash.write_i64(cost as i64);
+        }
+        let labels = Sequences::get_ss_labels(i);
+        hash.write_i64(labels.len() as i64);
+        hash.write(&labels);
+    }
+
+    let costs = options.costs();
+    for r in 0..=u8::MAX {
+        for l in 0..=u8::MAX {
+            hash.write_i64(costs.cost(r, l) as i64);
+        }
+    }
+    let structure = costs.structure();
+    hash.write_i64(structure.bonus as i64);
+    hash.write_i64(structure.penalty as i64);
+
+    hash.write(&[options.approx as u8, options.to_reference as u8]);
+    hash.write_i64(options.auto_approx.map_or(-1, |size| size.min(i64::MAX as u128) as i64));
+    hash.write_i64(options.max_gap.map_or(-1, |max_gap| max_gap.min(i64::MAX as usize) as i64));
+    // A run over the cap fails instead of being cached, but a cap it passes
+    // says nothing about a lower one
+    hash.write_i64(options.max_cost.map_or(i64::MIN, |max_cost| max_cost as i64));
+
+    format!("{:016x}", hash.0)
+}
+
+fn cache_file(dir: &str, key: &str) -> PathBuf {
+    Path::new(dir).join(format!("{}.aln", key))
+}
+
+/// Cached alignment for `key`, rows in input order. A missing or
+/// unreadable entry is a miss.
+pub fn load(dir: &str, key: &str) -> Option<Alignment> {
+    let text = fs::read_to_string(cache_file(dir, key)).ok()?;
+    let mut lines = text.lines();
+    let mut scores = None;
+    let mut rows = Vec::new();
+    if let Some(first) = lines.next() {
+        match first.strip_prefix("scores ") {
+            Some(values) => {
+                let values: Vec<i32> = values.split_whitespace().map(str::parse).collect::<Result<_, _>>().ok()?;
+                let &[f, g, h, initial_h] = values.as_slice() else {
+                    return None;
+                };
+                scores = Some(SearchScores { f, g, h, initial_h });
+            }
+            None => rows.push(first.to_string()),
+        }
+    }
+    rows.extend(lines.map(str::to_string));
+    if rows.len() != Sequences::get_seq_num() {
+        return None;
+    }
+
+    let alignment = Alignment::new(rows);
+    Some(match scores {
+        Some(scores) => alignment.with_search_scores(scores),
+        None => alignment,
+    })
+}
+
+/// Save `alignment` under `key`, replacing the file in one rename so a
+/// concurrent reader never sees it half written
+pub fn store(dir: &str, key: &str, alignment: &Alignment) -> Result<(), String> {
+    let mut text = String::new();
+    if let Some(s) = alignment.search_scores() {
+        text.push_str(&format!("scores {} {} {} {}\n", s.f, s.g, s.h, s.initial_h));
+    }
+    for row in alignment.seqs() {
+        text.push_str(row);
+        text.push('\n');
+    }
+
+    let path = cache_file(dir, key);
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&tmp, text))
+        .and_then(|_| fs::rename(&tmp, &path))
+        .map_err(|e| format!("Error writing cache entry {:?}: {}", path, e))
+}
+
+/// Return the cached alignment for the loaded sequences if `cache_dir` has
+/// one, reporting it like a finished search. Otherwise run `search` and
+/// cache its result. Local alignments aren't cached, they trim the loaded
+/// sequences to the aligned regions, and neither are streamed ones.
+pub fn with_cache(
+    options: &AStarOpt,
+    search: impl FnOnce() -> Result<Alignment, String>,
+) -> Result<Alignment, String> {
+    let Some(dir) = options.cache_dir.as_ref().filter(|_| options.mode == AlignMode::Global) else {
+        return search();
+    };
+    let key = cache_key(options);
+
+    if let Some(cached) = load(dir, &key) {
+        crate::info!("\nCache hit ({}), skipping the search", key);
+        // The cached rows are in input order; permute them to the current
+        // order so the sequences are put back in input order with them
+        let mut current = vec![String::new(); cached.seq_num()];
+        for (i, row) in current.iter_mut().enumerate() {
+            *row = cached.seqs()[Sequences::input_index(i)].clone();
+        }
+        Sequences::restore_input_order(current);
+        backtrace::report_alignment(&cached, options)?;
+        return Ok(cached);
+    }
+
+    let alignment = search()?;
+    // A streamed alignment has no rows to keep
+    if alignment.column_stats().is_none()
+        && let Err(e) = store(dir, &key, &alignment) {
+        eprintln!("Warning: {}", e);
+    }
+    Ok(alignment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar;
+    use crate::cost::Cost;
+
+    #[test]
+    fn test_second_run_hits_cache() {
+        let _guard = crate::test_lock();
+        let dir = std::env::temp_dir().join(format!("result_cache_{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]);
+        let options = AStarOpt::builder().cache_dir(dir).build();
+
+        let key = cache_key(&options);
+        let first = astar::run_astar_for_sequences(&options).unwrap();
+        assert!(load(dir, &key).is_some());
+
+        let mut searched = false;
+        let second = with_cache(&options, || {
+            searched = true;
+            Err("searched".to_string())
+        })
+        .unwrap();
+        assert!(!searched);
+        assert_eq!(second.seqs(), first.seqs());
+        assert_eq!(second.search_scores(), first.search_scores());
+
+        // Any scoring change is a different entry
+        Cost::set_gap_cost(31);
+        assert_ne!(cache_key(&options), key);
+        Cost::set_cost_pam250();
+        assert_ne!(cache_key(&AStarOpt::builder().cache_dir(dir).approx(true).build()), key);
+
+        // The cached optimum isn't returned for a cap below it
+        let g = first.search_scores().unwrap().g;
+        let capped = AStarOpt::builder().cache_dir(dir).max_cost(g - 1).build();
+        assert_ne!(cache_key(&capped), key);
+        assert_eq!(astar::run_astar_for_sequences(&capped).unwrap_err(), format!("No alignment costs at most {}", g - 1));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -5,19 +5,34 @@
  * \brief Singleton that holds all sequences being aligned
  */
 
+use std::cmp::min;
+use std::str::FromStr;
 use once_cell::sync::Lazy;
-use parking_lot::RwLock;
+use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard};
 use crate::coord::Coord;
+use crate::cost::Cost;
+use crate::error::MsaError;
 use crate::node::Node;
+use crate::MAX_SEARCH_SEQUENCES;
 
 static SEQUENCES: Lazy<RwLock<SequencesData>> = Lazy::new(|| {
     RwLock::new(SequencesData::new())
 });
 
+#[derive(Clone)]
 struct SequencesData {
     seqs: Vec<Vec<u8>>,
     seqs_name: Vec<String>,
     final_coord: Vec<usize>,
+    /// Input position of each loaded sequence
+    input_order: Vec<usize>,
+    /// Gap cost at each residue of a sequence, empty for the matrix's
+    gap_profiles: Vec<Vec<i32>>,
+    /// Secondary structure label of each residue of a sequence, empty if
+    /// it has none
+    ss_labels: Vec<Vec<u8>>,
+    /// Sequences changed since the heuristic last took them, sorted
+    edited: Vec<usize>,
 }
 
 impl SequencesData {
@@ -26,20 +41,95 @@ impl SequencesData {
             seqs: Vec::new(),
             seqs_name: Vec::new(),
             final_coord: Vec::new(),
+            input_order: Vec::new(),
+            gap_profiles: Vec::new(),
+            ss_labels: Vec::new(),
+            edited: Vec::new(),
+        }
+    }
+
+    fn mark_edited(&mut self, index: usize) {
+        if let Err(pos) = self.edited.binary_search(&index) {
+            self.edited.insert(pos, index);
         }
     }
 }
 
 pub struct Sequences;
 
+/// Copy of the loaded sequences, taken by `Sequences::snapshot`
+#[derive(Clone)]
+pub struct SequenceSet(SequencesData);
+
+/// What to do with `*` (stop codons) in the input
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum StopHandling {
+    /// Drop trailing `*`, reject any other
+    #[default]
+    Strip,
+    /// Keep `*` and score it (see `Cost::score_stop_codons`)
+    Score,
+    /// Reject any `*`
+    Error,
+}
+
+impl FromStr for StopHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "strip" => Ok(StopHandling::Strip),
+            "score" => Ok(StopHandling::Score),
+            "error" => Ok(StopHandling::Error),
+            _ => Err(format!("Unknown stop handling: {}", s)),
+        }
+    }
+}
+
+/// Slice `start..end` (0-based, end exclusive) of sequence `index`, written
+/// `index:start-end` on the command line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid region {}, expected INDEX:START-END", s);
+        let (index, range) = s.split_once(':').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let region = Region {
+            index: index.trim().parse().map_err(|_| invalid())?,
+            start: start.trim().parse().map_err(|_| invalid())?,
+            end: end.trim().parse().map_err(|_| invalid())?,
+        };
+        if region.start >= region.end {
+            return Err(format!("Empty region {}", s));
+        }
+        Ok(region)
+    }
+}
+
 impl Sequences {
     pub fn set_seq(seq: String) -> Result<(), String> {
+        Self::set_seq_bytes(seq.into_bytes())
+    }
+
+    /// Take ownership of an already uppercased sequence buffer
+    pub fn set_seq_bytes(seq_bytes: Vec<u8>) -> Result<(), String> {
         let mut data = SEQUENCES.write();
-        let seq_bytes: Vec<u8> = seq.into_bytes();
         let seq_len = seq_bytes.len();
         
+        let input_index = data.seqs.len();
         data.seqs.push(seq_bytes);
         data.final_coord.push(seq_len);
+        data.input_order.push(input_index);
+        data.gap_profiles.push(Vec::new());
+        data.ss_labels.push(Vec::new());
         
         Ok(())
     }
@@ -49,6 +139,44 @@ impl Sequences {
         data.seqs_name.push(name);
     }
 
+    /// Replace sequence `index` in place, e.g. after a user edit. Its gap
+    /// profile and structure labels belonged to the old residues and are
+    /// dropped. The sequence is marked edited, so `HeuristicHPair::refresh`
+    /// re-aligns only its pairs.
+    pub fn replace_seq(index: usize, seq: String) -> Result<(), String> {
+        let mut data = SEQUENCES.write();
+        let Some(old) = data.seqs.get_mut(index) else {
+            return Err(format!("No sequence {} (have {})", index, data.seqs.len()));
+        };
+        *old = seq.into_bytes();
+        data.final_coord[index] = data.seqs[index].len();
+        data.gap_profiles[index].clear();
+        data.ss_labels[index].clear();
+        data.mark_edited(index);
+        Ok(())
+    }
+
+    /// Rename sequence `index`
+    pub fn set_name_at(index: usize, name: String) -> Result<(), String> {
+        let mut data = SEQUENCES.write();
+        let have = data.seqs_name.len();
+        let old = data.seqs_name.get_mut(index)
+            .ok_or_else(|| format!("No sequence name {} (have {})", index, have))?;
+        *old = name;
+        Ok(())
+    }
+
+    /// Sequences whose residues or costs changed since the last
+    /// `take_edited`, the ones the heuristic has to re-align
+    pub fn edited() -> Vec<usize> {
+        SEQUENCES.read().edited.clone()
+    }
+
+    /// `edited`, clearing the list
+    pub fn take_edited() -> Vec<usize> {
+        std::mem::take(&mut SEQUENCES.write().edited)
+    }
+
     pub fn get_seq_num() -> usize {
         let data = SEQUENCES.read();
         data.seqs.len()
@@ -59,6 +187,14 @@ impl Sequences {
         data.seqs.get(index).cloned().unwrap_or_default()
     }
 
+    /// Borrowed view of sequence `index`, without cloning it. The read lock
+    /// is held until the view is dropped, so don't load sequences meanwhile.
+    pub fn get_seq_slice(index: usize) -> MappedRwLockReadGuard<'static, [u8]> {
+        RwLockReadGuard::map(SEQUENCES.read_recursive(), |data| {
+            data.seqs.get(index).map(Vec::as_slice).unwrap_or_default()
+        })
+    }
+
     pub fn get_seq_len(index: usize) -> usize {
         let data = SEQUENCES.read();
         data.seqs.get(index).map(|s| s.len()).unwrap_or(0)
@@ -76,11 +212,118 @@ impl Sequences {
         data.seqs_name.get(index).cloned().unwrap_or_default()
     }
 
+    /// Set the gap cost at each residue of sequence `index`, in place of
+    /// the cost matrix's. An empty profile restores the matrix's gap cost.
+    pub fn set_gap_profile(index: usize, profile: Vec<i32>) -> Result<(), String> {
+        let mut data = SEQUENCES.write();
+        let Some(seq) = data.seqs.get(index) else {
+            return Err(format!("No sequence {} (have {})", index, data.seqs.len()));
+        };
+        if !profile.is_empty() && profile.len() != seq.len() {
+            return Err(format!(
+                "Gap profile of sequence {} has {} values, expected {}", index, profile.len(), seq.len()
+            ));
+        }
+        if let Some(cost) = profile.iter().find(|&&cost| cost < 0) {
+            return Err(format!("Gap profile of sequence {} has negative cost {}", index, cost));
+        }
+        data.gap_profiles[index] = profile;
+        data.mark_edited(index);
+        Ok(())
+    }
+
+    /// Cost of a gap in sequence `index` after its first `pos` residues.
+    /// With a gap profile it's the lower cost of the two residues around
+    /// the gap, so only gaps inside a costly region pay its cost.
+    pub fn get_gap_cost(index: usize, pos: usize) -> i32 {
+        Self::get_gap_profile_cost(index, pos).unwrap_or_else(Cost::get_gap_cost)
+    }
+
+    /// `get_gap_cost` if sequence `index` has a gap profile
+    pub fn get_gap_profile_cost(index: usize, pos: usize) -> Option<i32> {
+        let data = SEQUENCES.read();
+        let profile = data.gap_profiles.get(index).filter(|profile| !profile.is_empty())?;
+        let after = profile[pos.min(profile.len() - 1)];
+        let before = profile[pos.saturating_sub(1).min(profile.len() - 1)];
+        Some(min(before, after))
+    }
+
+    /// `get_gap_cost` of sequence `index` at every position `0..=len`
+    pub fn get_gap_costs(index: usize) -> Vec<i32> {
+        (0..=Self::get_seq_len(index)).map(|pos| Self::get_gap_cost(index, pos)).collect()
+    }
+
+    /// Number of nodes of the search lattice, the product of every
+    /// sequence length plus one, or `None` if it overflows `u128`
+    pub fn lattice_size() -> Option<u128> {
+        let data = SEQUENCES.read();
+        data.seqs.iter().try_fold(1u128, |acc, seq| acc.checked_mul(seq.len() as u128 + 1))
+    }
+
+    /// Set the secondary structure label (H, E or C) of each residue of
+    /// sequence `index`. Residue pairs of two labeled sequences are scored
+    /// with `Cost::structure_scoring`. An empty list drops the labels.
+    pub fn set_ss_labels(index: usize, labels: Vec<u8>) -> Result<(), String> {
+        let mut data = SEQUENCES.write();
+        let Some(seq) = data.seqs.get(index) else {
+            return Err(format!("No sequence {} (have {})", index, data.seqs.len()));
+        };
+        if !labels.is_empty() && labels.len() != seq.len() {
+            return Err(format!(
+                "Structure of sequence {} has {} labels, expected {}", index, labels.len(), seq.len()
+            ));
+        }
+        if let Some(&label) = labels.iter().find(|&&label| !matches!(label, b'H' | b'E' | b'C')) {
+            return Err(format!(
+                "Structure of sequence {} has label '{}', expected H, E or C", index, label as char
+            ));
+        }
+        data.ss_labels[index] = labels;
+        data.mark_edited(index);
+        Ok(())
+    }
+
+    /// Structure label of residue `pos` of sequence `index`, 0 if it has none
+    pub fn get_ss_label(index: usize, pos: usize) -> u8 {
+        let data = SEQUENCES.read();
+        data.ss_labels.get(index).and_then(|labels| labels.get(pos)).copied().unwrap_or(0)
+    }
+
+    /// Structure labels of sequence `index`, empty if it has none
+    pub fn get_ss_labels(index: usize) -> Vec<u8> {
+        SEQUENCES.read().ss_labels.get(index).cloned().unwrap_or_default()
+    }
+
+    /// Whether any loaded sequence has a gap profile
+    pub fn has_gap_profiles() -> bool {
+        SEQUENCES.read().gap_profiles.iter().any(|profile| !profile.is_empty())
+    }
+
+    /// Distinct residues of the loaded sequences, sorted
+    pub fn residues() -> Vec<u8> {
+        let data = SEQUENCES.read();
+        let mut present = [false; 256];
+        for &c in data.seqs.iter().flatten() {
+            present[c as usize] = true;
+        }
+        (0..=255u8).filter(|&c| present[c as usize]).collect()
+    }
+
+    /// Check that the exact search supports the number of loaded sequences,
+    /// before spending time on the heuristic
+    pub fn check_search_count() -> Result<(), MsaError> {
+        match Self::get_seq_num() {
+            n if n < 2 => Err(MsaError::TooFewSequences(n)),
+            n if n > MAX_SEARCH_SEQUENCES => Err(MsaError::TooManySequences(n)),
+            _ => Ok(()),
+        }
+    }
+
     pub fn get_final_coord<const N: usize>() -> Coord<N> {
         let data = SEQUENCES.read();
         let mut coords = [0u16; N];
-        for i in 0..N {
-            coords[i] = data.final_coord.get(i).copied().unwrap_or(0) as u16;
+        for (i, c) in coords.iter_mut().enumerate() {
+            *c = data.final_coord.get(i).copied().unwrap_or(0) as u16;
         }
         Coord::from_array(coords)
     }
@@ -98,11 +341,171 @@ impl Sequences {
         c == &final_coord
     }
 
+    /// Copy of the loaded sequences and their names and order
+    pub fn snapshot() -> SequenceSet {
+        SequenceSet(SEQUENCES.read().clone())
+    }
+
+    /// Load the sequences of a `snapshot` again, replacing the current ones
+    pub fn restore(set: &SequenceSet) {
+        *SEQUENCES.write() = set.0.clone();
+    }
+
     pub fn clear() {
         let mut data = SEQUENCES.write();
         data.seqs.clear();
         data.seqs_name.clear();
         data.final_coord.clear();
+        data.input_order.clear();
+        data.gap_profiles.clear();
+        data.ss_labels.clear();
+        data.edited.clear();
+    }
+
+    /// Clean the loaded sequences before aligning: drop gap characters
+    /// (`-` and `.`) and handle stop codons as `stop` says. Call after the
+    /// cost matrix is selected.
+    pub fn normalize(stop: StopHandling) -> Result<(), String> {
+        let mut data = SEQUENCES.write();
+        let data = &mut *data;
+        for (i, seq) in data.seqs.iter_mut().enumerate() {
+            seq.retain(|&c| c != b'-' && c != b'.');
+            if stop == StopHandling::Strip {
+                while seq.last() == Some(&b'*') {
+                    seq.pop();
+                }
+            }
+            if stop != StopHandling::Score
+                && let Some(pos) = seq.iter().position(|&c| c == b'*') {
+                return Err(format!(
+                    "Sequence {} has a stop codon '*' at position {} (see --stop-handling)", i, pos
+                ));
+            }
+            data.final_coord[i] = seq.len();
+        }
+        if stop == StopHandling::Score {
+            Cost::score_stop_codons();
+        }
+        Ok(())
+    }
+
+    /// Truncate a loaded sequence to `region` and note the kept residues in
+    /// its name as `/first-last` (1-based, inclusive)
+    pub fn restrict_to_region(region: &Region) -> Result<(), String> {
+        let mut data = SEQUENCES.write();
+        let Some(seq) = data.seqs.get_mut(region.index) else {
+            return Err(format!("No sequence {} (have {})", region.index, data.seqs.len()));
+        };
+        if region.end > seq.len() {
+            return Err(format!(
+                "Region {}-{} is past the end of sequence {} (length {})",
+                region.start, region.end, region.index, seq.len()
+            ));
+        }
+        seq.truncate(region.end);
+        seq.drain(..region.start);
+        let profile = &mut data.gap_profiles[region.index];
+        if !profile.is_empty() {
+            profile.truncate(region.end);
+            profile.drain(..region.start);
+        }
+        let labels = &mut data.ss_labels[region.index];
+        if !labels.is_empty() {
+            labels.truncate(region.end);
+            labels.drain(..region.start);
+        }
+        data.final_coord[region.index] = region.end - region.start;
+        data.mark_edited(region.index);
+        if let Some(name) = data.seqs_name.get_mut(region.index) {
+            name.push_str(&format!("/{}-{}", region.start + 1, region.end));
+        }
+        Ok(())
+    }
+
+    /// Reverse sequence `index` in place (not complemented), with its gap
+    /// profile and structure labels, and note it in its name as `/rev`
+    pub fn reverse(index: usize) -> Result<(), String> {
+        let mut data = SEQUENCES.write();
+        let Some(seq) = data.seqs.get_mut(index) else {
+            return Err(format!("No sequence {} (have {})", index, data.seqs.len()));
+        };
+        seq.reverse();
+        data.gap_profiles[index].reverse();
+        data.ss_labels[index].reverse();
+        data.mark_edited(index);
+        if let Some(name) = data.seqs_name.get_mut(index) {
+            name.push_str("/rev");
+        }
+        Ok(())
+    }
+
+    /// Input position of the sequence currently loaded at `index`
+    pub fn input_index(index: usize) -> usize {
+        SEQUENCES.read().input_order.get(index).copied().unwrap_or(index)
+    }
+
+    /// Move sequence `index` to dimension 0, keeping the others in order.
+    /// The partial hashes skip dimension 0, so this sequence decides how
+    /// PA-Star spreads nodes over threads.
+    pub fn move_to_front(index: usize) -> Result<(), String> {
+        let mut data = SEQUENCES.write();
+        if index >= data.seqs.len() {
+            return Err(format!("No sequence {} (have {})", index, data.seqs.len()));
+        }
+        data.seqs[..=index].rotate_right(1);
+        data.final_coord[..=index].rotate_right(1);
+        data.input_order[..=index].rotate_right(1);
+        data.gap_profiles[..=index].rotate_right(1);
+        data.ss_labels[..=index].rotate_right(1);
+        if index < data.seqs_name.len() {
+            data.seqs_name[..=index].rotate_right(1);
+        }
+        // Every pair now sits at other dimensions
+        if index > 0 {
+            data.edited = (0..data.seqs.len()).collect();
+        }
+        Ok(())
+    }
+
+    /// Put the sequences back in input order and return `rows`, given in
+    /// the current order, permuted the same way
+    pub fn restore_input_order(rows: Vec<String>) -> Vec<String> {
+        let mut data = SEQUENCES.write();
+        if data.input_order.iter().enumerate().all(|(i, &k)| i == k) {
+            return rows;
+        }
+
+        let order = std::mem::take(&mut data.input_order);
+        let mut restored = vec![String::new(); rows.len()];
+        for (row, &k) in rows.into_iter().zip(&order) {
+            restored[k] = row;
+        }
+
+        let mut seqs = vec![Vec::new(); order.len()];
+        let mut final_coord = vec![0; order.len()];
+        let mut gap_profiles = vec![Vec::new(); order.len()];
+        let mut ss_labels = vec![Vec::new(); order.len()];
+        for (i, &k) in order.iter().enumerate() {
+            seqs[k] = std::mem::take(&mut data.seqs[i]);
+            final_coord[k] = data.final_coord[i];
+            gap_profiles[k] = std::mem::take(&mut data.gap_profiles[i]);
+            ss_labels[k] = std::mem::take(&mut data.ss_labels[i]);
+        }
+        if data.seqs_name.len() == order.len() {
+            let mut names = vec![String::new(); order.len()];
+            for (i, &k) in order.iter().enumerate() {
+                names[k] = std::mem::take(&mut data.seqs_name[i]);
+            }
+            data.seqs_name = names;
+        }
+        data.seqs = seqs;
+        data.final_coord = final_coord;
+        data.gap_profiles = gap_profiles;
+        data.ss_labels = ss_labels;
+        data.input_order = (0..order.len()).collect();
+        data.edited = (0..order.len()).collect();
+
+        restored
     }
 
     pub fn destroy_instance() {
@@ -116,6 +519,7 @@ mod tests {
 
     #[test]
     fn test_sequences() {
+        let _guard = crate::test_lock();
         Sequences::clear();
         Sequences::set_seq("ACGT".to_string()).unwrap();
         Sequences::set_seq("AGCT".to_string()).unwrap();
@@ -127,6 +531,7 @@ mod tests {
 
     #[test]
     fn test_final_coord() {
+        let _guard = crate::test_lock();
         Sequences::clear();
         Sequences::set_seq("ACGT".to_string()).unwrap();
         Sequences::set_seq("AG".to_string()).unwrap();
@@ -135,4 +540,199 @@ mod tests {
         assert_eq!(final_coord.get(0), 4);
         assert_eq!(final_coord.get(1), 2);
     }
+
+    #[test]
+    fn test_replace_seq() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        for (name, seq) in [(">a", "ACGT"), (">b", "AG"), (">c", "CGT")] {
+            Sequences::set_name(name.to_string());
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        Sequences::set_gap_profile(1, vec![3, 4]).unwrap();
+        Sequences::take_edited();
+
+        Sequences::replace_seq(1, "AGGTCA".to_string()).unwrap();
+        Sequences::set_name_at(1, ">b edited".to_string()).unwrap();
+        assert_eq!(Sequences::get_seq(1), b"AGGTCA");
+        assert_eq!(Sequences::get_seq_len(1), 6);
+        assert_eq!(Sequences::get_seq_name(1), ">b edited");
+        assert!(!Sequences::has_gap_profiles());
+        let final_coord: Coord<3> = Sequences::get_final_coord();
+        assert_eq!((final_coord.get(0), final_coord.get(1), final_coord.get(2)), (4, 6, 3));
+
+        assert_eq!(Sequences::take_edited(), [1]);
+        assert!(Sequences::edited().is_empty());
+        assert!(Sequences::replace_seq(3, "A".to_string()).is_err());
+        assert!(Sequences::set_name_at(3, ">d".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_check_search_count() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        Sequences::set_seq("AC".to_string()).unwrap();
+        assert_eq!(Sequences::check_search_count(), Err(MsaError::TooFewSequences(1)));
+
+        for _ in 1..MAX_SEARCH_SEQUENCES {
+            Sequences::set_seq("AC".to_string()).unwrap();
+        }
+        assert!(Sequences::check_search_count().is_ok());
+
+        Sequences::set_seq("AC".to_string()).unwrap();
+        let err = Sequences::check_search_count().unwrap_err();
+        assert_eq!(err, MsaError::TooManySequences(MAX_SEARCH_SEQUENCES + 1));
+        assert!(err.to_string().contains("at most 8"));
+    }
+
+    #[test]
+    fn test_move_to_front_and_restore() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        for (name, seq) in [(">a", "AC"), (">b", "ACG"), (">c", "ACGT")] {
+            Sequences::set_name(name.to_string());
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+
+        Sequences::move_to_front(2).unwrap();
+        assert_eq!(Sequences::get_seq(0), b"ACGT");
+        assert_eq!(Sequences::get_seq_name(1), ">a");
+        assert_eq!(Sequences::get_seq_len(2), 3);
+        assert_eq!(&*Sequences::get_seq_slice(2), b"ACG");
+        assert!(Sequences::get_seq_slice(3).is_empty());
+        assert!(Sequences::move_to_front(3).is_err());
+
+        let rows = vec!["c".to_string(), "a".to_string(), "b".to_string()];
+        assert_eq!(Sequences::restore_input_order(rows), vec!["a", "b", "c"]);
+        assert_eq!(Sequences::get_seq(0), b"AC");
+        assert_eq!(Sequences::get_seq_name(2), ">c");
+    }
+
+    #[test]
+    fn test_region_alignment() {
+        use crate::astar;
+        use crate::cost::Cost;
+        use crate::heuristic_hpair::HeuristicHPair;
+        use crate::msa_options::AStarOpt;
+
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        for (name, seq) in [(">long", "TTTACGTTT"), (">short", "ACG")] {
+            Sequences::set_name(name.to_string());
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+
+        let region: Region = "0:3-6".parse().unwrap();
+        assert_eq!(region, Region { index: 0, start: 3, end: 6 });
+        assert!("0:5-5".parse::<Region>().is_err());
+        assert!("0-3".parse::<Region>().is_err());
+        assert!(Sequences::restrict_to_region(&Region { index: 1, start: 0, end: 4 }).is_err());
+
+        Sequences::restrict_to_region(&region).unwrap();
+        assert_eq!(Sequences::get_seq(0), b"ACG");
+        assert_eq!(Sequences::get_seq_name(0), ">long/4-6");
+        assert_eq!(Sequences::residues(), b"ACG");
+        assert_eq!(Sequences::get_final_coord::<2>(), Coord::from_array([3, 3]));
+
+        HeuristicHPair::init().unwrap();
+        let alignment = astar::run_astar_for_sequences(&AStarOpt::builder().build()).unwrap();
+        assert_eq!(alignment.seqs(), ["ACG", "ACG"]);
+    }
+
+    #[test]
+    fn test_lattice_size() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        assert_eq!(Sequences::lattice_size(), Some(1));
+        for seq in ["ACGT", "ACG", ""] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        assert_eq!(Sequences::lattice_size(), Some(5 * 4));
+
+        // 65536^8 = 2^128, one past u128::MAX
+        Sequences::clear();
+        for _ in 0..8 {
+            Sequences::set_seq_bytes(vec![b'A'; u16::MAX as usize]).unwrap();
+        }
+        assert_eq!(Sequences::lattice_size(), None);
+    }
+
+    #[test]
+    fn test_gap_profile_costs() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_nuc();
+        Sequences::clear();
+        Sequences::set_seq("ACGTAC".to_string()).unwrap();
+        Sequences::set_seq("ACG".to_string()).unwrap();
+        assert!(!Sequences::has_gap_profiles());
+        assert!(Sequences::set_gap_profile(0, vec![1; 5]).is_err());
+        assert!(Sequences::set_gap_profile(0, vec![1, 1, -1, 1, 1, 1]).is_err());
+
+        Sequences::set_gap_profile(0, vec![1, 9, 9, 9, 3, 3]).unwrap();
+        assert!(Sequences::has_gap_profiles());
+        assert_eq!(Sequences::get_gap_costs(0), [1, 1, 9, 9, 3, 3, 3]);
+        assert_eq!(Sequences::get_gap_costs(1), [2; 4]);
+
+        // The profile follows its sequence when reordered or cut
+        Sequences::move_to_front(1).unwrap();
+        assert_eq!(Sequences::get_gap_cost(1, 2), 9);
+        Sequences::restrict_to_region(&Region { index: 1, start: 2, end: 5 }).unwrap();
+        assert_eq!(Sequences::get_gap_costs(1), [9, 9, 3, 3]);
+        Sequences::restore_input_order(Vec::new());
+        assert_eq!(Sequences::get_gap_costs(0), [9, 9, 3, 3]);
+    }
+
+    #[test]
+    fn test_normalize_stop_codons() {
+        let _guard = crate::test_lock();
+        let load = |seqs: &[&str]| {
+            Sequences::clear();
+            for seq in seqs {
+                Sequences::set_seq(seq.to_string()).unwrap();
+            }
+        };
+        Cost::set_cost_pam250();
+
+        load(&["MK-V.L*", "MKV"]);
+        Sequences::normalize(StopHandling::Strip).unwrap();
+        assert_eq!(Sequences::get_seq(0), b"MKVL");
+        assert_eq!(Sequences::get_final_coord::<2>(), Coord::from_array([4, 3]));
+
+        load(&["MK*VL", "MKV"]);
+        assert!(Sequences::normalize(StopHandling::Strip).is_err());
+
+        load(&["MKVL*", "MKV"]);
+        let err = Sequences::normalize(StopHandling::Error).unwrap_err();
+        assert!(err.contains("position 4"));
+
+        load(&["MKVL*", "MKV*"]);
+        Sequences::normalize(StopHandling::Score).unwrap();
+        assert_eq!(Sequences::get_seq(0), b"MKVL*");
+        assert_eq!(Cost::cost(b'*', b'*'), 0);
+        assert_eq!(Cost::cost(b'*', b'A'), Cost::cost(b'W', b'C'));
+        assert_eq!("ERROR".parse::<StopHandling>(), Ok(StopHandling::Error));
+    }
+
+    #[test]
+    fn test_reverse() {
+        let _guard = crate::test_lock();
+        Sequences::clear();
+        for (name, seq) in [(">a", "MKVLW"), (">b", "MKV")] {
+            Sequences::set_name(name.to_string());
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        Sequences::set_gap_profile(0, vec![1, 2, 3, 4, 5]).unwrap();
+        Sequences::set_ss_labels(0, b"HHECC".to_vec()).unwrap();
+        Sequences::take_edited();
+
+        Sequences::reverse(0).unwrap();
+        assert_eq!(Sequences::get_seq(0), b"WLVKM");
+        assert_eq!(Sequences::get_ss_labels(0), b"CCEHH");
+        assert_eq!(Sequences::get_gap_cost(0, 1), 4);
+        assert_eq!(Sequences::get_seq_name(0), ">a/rev");
+        assert_eq!(Sequences::get_seq(1), b"MKV");
+        assert_eq!(Sequences::edited(), [0]);
+        assert!(Sequences::reverse(2).is_err());
+    }
 }
@@ -0,0 +1,123 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Search statistics appended as CSV rows, one per run, to compare
+ * parameter sweeps in a spreadsheet
+ */
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use crate::alignment::{Alignment, IdentityMode};
+use crate::cost::Cost;
+use crate::msa_options::PAStarOpt;
+use crate::time_counter;
+
+/// Columns of a stats file, written as its first line
+pub const HEADER: [&str; 17] = [
+    "input", "n", "matrix", "gap_cost", "gap_gap", "threads", "hash_type", "hash_shift",
+    "nodes_expanded", "nodes_reopened", "closed_size", "peak_open", "sp_score", "similarity",
+    "heuristic_s", "search_s", "backtrace_s",
+];
+
+/// `field` quoted if it holds a comma, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Seconds of the last recorded phase named `Phase <num>...`, empty if none
+fn phase_secs(num: usize) -> String {
+    let prefix = format!("Phase {}", num);
+    time_counter::timings().iter()
+        .rfind(|t| t.name.starts_with(&prefix))
+        .map_or(String::new(), |t| format!("{:.6}", t.duration.as_secs_f64()))
+}
+
+/// Row of `HEADER` for `alignment`, found from `input` with the active
+/// costs by A-Star, or by PA-Star with `parallel`. Statistics the search
+/// didn't record, like those of a cached result, are left empty.
+pub fn stats_row(input: &str, alignment: &Alignment, identity_mode: IdentityMode,
+                 parallel: Option<&PAStarOpt>) -> Vec<String> {
+    let scheme = Cost::matrix_snapshot();
+    let list_stats = alignment.list_stats();
+    let optional = |value: Option<usize>| value.map_or(String::new(), |v| v.to_string());
+    vec![
+        csv_field(input),
+        alignment.seq_num().to_string(),
+        scheme.name().to_string(),
+        scheme.gap_cost().to_string(),
+        scheme.gap_gap().to_string(),
+        parallel.map_or(1, |options| options.threads_num).to_string(),
+        parallel.map_or(String::new(), |options| format!("{:?}", options.hash_type).to_lowercase()),
+        parallel.map_or(String::new(), |options| options.hash_shift.to_string()),
+        optional(alignment.nodes_expanded()),
+        optional(list_stats.map(|stats| stats.reopened)),
+        optional(list_stats.map(|stats| stats.closed)),
+        optional(list_stats.map(|stats| stats.peak_open)),
+        alignment.sp_score().to_string(),
+        format!("{:.2}", alignment.similarity(identity_mode)),
+        phase_secs(1),
+        phase_secs(2),
+        phase_secs(3),
+    ]
+}
+
+/// Append `row` to `filename`, creating it with the `HEADER` line first
+pub fn append_row(filename: &str, row: &[String]) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Error writing stats file {}: {}", filename, e);
+    let mut file = OpenOptions::new().create(true).append(true).open(filename).map_err(error)?;
+    let mut text = String::new();
+    if file.metadata().map_err(error)?.len() == 0 {
+        text.push_str(&HEADER.join(","));
+        text.push('\n');
+    }
+    text.push_str(&row.join(","));
+    text.push('\n');
+    // One write, so runs appending at once don't interleave their rows
+    file.write_all(text.as_bytes()).map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar;
+    use crate::msa_options::AStarOpt;
+    use crate::pastar;
+
+    #[test]
+    fn test_stats_rows_appended() {
+        let _guard = crate::test_lock();
+        let path = std::env::temp_dir().join(format!("stats_{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        crate::test_support::pam_fixture(&["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"]);
+
+        let serial = astar::run_astar_uncached(&AStarOpt::builder().build()).unwrap();
+        append_row(path, &stats_row("globins, 3", &serial, IdentityMode::Strict, None)).unwrap();
+        let options = PAStarOpt::builder().threads(2).hash_shift(1).build().unwrap();
+        let parallel = pastar::run_pastar_uncached(options.clone()).unwrap();
+        append_row(path, &stats_row("globins.fasta", &parallel, IdentityMode::Strict, Some(&options))).unwrap();
+
+        let text = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], HEADER.join(","));
+        let serial_row: Vec<&str> = lines[1].strip_prefix("\"globins, 3\",").unwrap().split(',').collect();
+        let parallel_row: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!((serial_row.len(), parallel_row.len()), (HEADER.len() - 1, HEADER.len()));
+
+        let stats = serial.list_stats().unwrap();
+        assert_eq!(&serial_row[..11], ["3", "pam250", "30", "30", "1", "", "", &serial.nodes_expanded().unwrap().to_string(),
+                   &stats.reopened.to_string(), &stats.closed.to_string(), &stats.peak_open.to_string()]);
+        assert!(stats.closed > 0 && stats.peak_open > 0);
+        assert_eq!(serial_row[11], serial.sp_score().to_string());
+        assert_eq!(&parallel_row[..8], ["globins.fasta", "3", "pam250", "30", "30", "2", "fzorder", "1"]);
+        assert_eq!(parallel_row[12], serial_row[11]);
+        assert!(parallel_row[14..17].iter().all(|secs| secs.parse::<f64>().is_ok()));
+        std::fs::remove_file(path).unwrap();
+    }
+}
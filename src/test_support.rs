@@ -0,0 +1,21 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Setup shared by the unit tests
+ */
+
+use crate::cost::Cost;
+use crate::heuristic_hpair::HeuristicHPair;
+use crate::sequences::Sequences;
+
+/// Load `seqs` with PAM250 costs and build their heuristic. Callers hold
+/// `crate::test_lock()`.
+pub fn pam_fixture(seqs: &[&str]) {
+    Cost::set_cost_pam250();
+    Sequences::clear();
+    for seq in seqs {
+        Sequences::set_seq(seq.to_string()).unwrap();
+    }
+    HeuristicHPair::init().unwrap();
+}
@@ -5,7 +5,59 @@
  * \brief Time counter utility for performance measurement
  */
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+/// Reference point of the recorded phase start times
+static EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
+static TIMINGS: Lazy<Mutex<Vec<PhaseTiming>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// One finished phase. `start` is measured from the first timer of the process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// Start time of a phase, after the epoch its offset is measured from
+pub fn phase_start() -> Instant {
+    Lazy::force(&EPOCH);
+    Instant::now()
+}
+
+/// Record a phase that began at `start` and ends now, returning its length
+pub fn record_phase(name: &str, start: Instant) -> Duration {
+    let duration = start.elapsed();
+    TIMINGS.lock().push(PhaseTiming {
+        name: name.trim().trim_end_matches(':').to_string(),
+        start: start.saturating_duration_since(*EPOCH),
+        duration,
+    });
+    duration
+}
+
+/// Phases recorded so far, in start order
+pub fn timings() -> Vec<PhaseTiming> {
+    let mut timings = TIMINGS.lock().clone();
+    timings.sort_by_key(|t| t.start);
+    timings
+}
+
+pub fn reset_timings() {
+    TIMINGS.lock().clear();
+}
+
+/// `timings()` as a JSON object with nanosecond start and duration per phase
+pub fn timings_json() -> String {
+    let phases: Vec<String> = timings().iter()
+        .map(|t| format!("{{\"name\":\"{}\",\"start_ns\":{},\"duration_ns\":{}}}",
+                         t.name.replace('\\', "\\\\").replace('"', "\\\""),
+                         t.start.as_nanos(), t.duration.as_nanos()))
+        .collect();
+    format!("{{\"phases\":[{}]}}", phases.join(","))
+}
 
 pub struct TimeCounter {
     name: String,
@@ -14,10 +66,10 @@ pub struct TimeCounter {
 
 impl TimeCounter {
     pub fn new(name: &str) -> Self {
-        println!("{}", name);
+        crate::info!("{}", name);
         TimeCounter {
             name: name.to_string(),
-            start: Instant::now(),
+            start: phase_start(),
         }
     }
 
@@ -32,8 +84,8 @@ impl TimeCounter {
 
 impl Drop for TimeCounter {
     fn drop(&mut self) {
-        let duration = self.elapsed();
-        println!("{} completed in {:.3}s", self.name, duration.as_secs_f64());
+        let duration = record_phase(&self.name, self.start);
+        crate::info!("{} completed in {:.3}s", self.name, duration.as_secs_f64());
     }
 }
 
@@ -43,8 +95,29 @@ mod tests {
 
     #[test]
     fn test_time_counter() {
+        // Finishing the timer records a phase in the shared list
+        let _guard = crate::test_lock();
         let tc = TimeCounter::new("Test timer");
         std::thread::sleep(std::time::Duration::from_millis(10));
         assert!(tc.elapsed_secs() >= 0.01);
     }
+
+    #[test]
+    fn test_phase_timings() {
+        let _guard = crate::test_lock();
+        reset_timings();
+        for name in ["\nFirst phase:", "Second phase:"] {
+            let _timer = TimeCounter::new(name);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let phases: Vec<PhaseTiming> = timings().into_iter().filter(|t| t.name.ends_with(" phase")).collect();
+        assert_eq!(phases.len(), 2);
+        assert_eq!((phases[0].name.as_str(), phases[1].name.as_str()), ("First phase", "Second phase"));
+        assert!(phases.iter().all(|t| t.duration >= Duration::from_millis(1)));
+        assert!(phases[1].start >= phases[0].start + phases[0].duration);
+
+        let json = timings_json();
+        assert!(json.starts_with("{\"phases\":[") && json.contains("{\"name\":\"First phase\",\"start_ns\":"));
+    }
 }
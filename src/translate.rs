@@ -0,0 +1,273 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief Translation of coding sequences to protein before alignment, and
+ * back-translation of the protein alignment onto the original codons
+ */
+
+use std::io::Write;
+use std::str::FromStr;
+use crate::alignment::Alignment;
+use crate::backtrace;
+use crate::cost::GAP;
+use crate::msa_options::AStarOpt;
+use crate::sequences::Sequences;
+
+/// NCBI genetic code tables, by their number
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GeneticCode {
+    /// Table 1
+    #[default]
+    Standard,
+    /// Table 2: AGA and AGG stop, ATA codes M, TGA codes W
+    VertebrateMitochondrial,
+    /// Table 4: TGA codes W
+    MoldMitochondrial,
+    /// Table 11, the standard amino acids with other start codons
+    Bacterial,
+}
+
+impl FromStr for GeneticCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "1" | "standard" => Ok(GeneticCode::Standard),
+            "2" | "vertebrate-mito" => Ok(GeneticCode::VertebrateMitochondrial),
+            "4" | "mold-mito" => Ok(GeneticCode::MoldMitochondrial),
+            "11" | "bacterial" => Ok(GeneticCode::Bacterial),
+            _ => Err(format!("Unknown genetic code: {} (expected 1, 2, 4 or 11)", s)),
+        }
+    }
+}
+
+impl GeneticCode {
+    /// Amino acid of each codon, bases ordered T, C, A, G as in the NCBI
+    /// tables
+    fn amino_acids(self) -> &'static [u8; 64] {
+        match self {
+            GeneticCode::Standard | GeneticCode::Bacterial =>
+                b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+            GeneticCode::VertebrateMitochondrial =>
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+            GeneticCode::MoldMitochondrial =>
+                b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        }
+    }
+
+    /// Amino acid `codon` codes for, `*` for a stop and `X` if a base isn't
+    /// A, C, G, T or U
+    pub fn translate_codon(self, codon: &[u8]) -> u8 {
+        let mut index = 0;
+        for &base in codon {
+            let digit = match base.to_ascii_uppercase() {
+                b'T' | b'U' => 0,
+                b'C' => 1,
+                b'A' => 2,
+                b'G' => 3,
+                _ => return b'X',
+            };
+            index = index * 4 + digit;
+        }
+        self.amino_acids()[index]
+    }
+}
+
+/// Codons of `seq` read in `frame` (1 to 3, the base the first codon
+/// starts at). Bases before the frame and a trailing partial codon are
+/// left out.
+fn codons(seq: &[u8], frame: usize) -> std::slice::ChunksExact<'_, u8> {
+    seq[(frame - 1).min(seq.len())..].chunks_exact(3)
+}
+
+/// Protein `seq` codes for in `frame` under `code`
+pub fn translate(seq: &[u8], frame: usize, code: GeneticCode) -> Vec<u8> {
+    codons(seq, frame).map(|codon| code.translate_codon(codon)).collect()
+}
+
+/// Replace every loaded sequence by its translation, returning the
+/// sequences as loaded without gaps, for `back_translate`
+pub fn translate_loaded(frame: usize, code: GeneticCode) -> Result<Vec<Vec<u8>>, String> {
+    let mut nucleotides = Vec::with_capacity(Sequences::get_seq_num());
+    for i in 0..Sequences::get_seq_num() {
+        let mut seq = Sequences::get_seq(i);
+        seq.retain(|&c| c != b'-' && c != b'.');
+        let protein = translate(&seq, frame, code);
+        if protein.is_empty() {
+            return Err(format!("Sequence {} has no complete codon in frame {}", i, frame));
+        }
+        Sequences::replace_seq(i, String::from_utf8_lossy(&protein).into_owned())?;
+        nucleotides.push(seq);
+    }
+    Ok(nucleotides)
+}
+
+/// Codon alignment of the protein alignment `row` of `nucleotides`: each
+/// residue becomes its codon and each gap three gaps. Fails if a residue
+/// isn't the translation of its codon.
+pub fn back_translate(row: &str, nucleotides: &[u8], frame: usize, code: GeneticCode) -> Result<String, String> {
+    let mut codons = codons(nucleotides, frame);
+    let mut out = String::with_capacity(row.len() * 3);
+    for (column, residue) in row.bytes().enumerate() {
+        if residue == GAP {
+            out.push_str("---");
+            continue;
+        }
+        let codon = codons.next().ok_or_else(|| format!("Column {} is past the last codon", column))?;
+        if code.translate_codon(codon) != residue.to_ascii_uppercase() {
+            return Err(format!(
+                "Column {}: '{}' isn't the translation of {}", column, residue as char, String::from_utf8_lossy(codon)
+            ));
+        }
+        out.push_str(&String::from_utf8_lossy(codon));
+    }
+    Ok(out)
+}
+
+/// Write the codon alignment of `alignment`, the alignment of the
+/// translations of `nucleotides` (from `translate_loaded`), as FASTA
+pub fn write_back_translation(
+    filename: &str,
+    alignment: &Alignment,
+    nucleotides: &[Vec<u8>],
+    frame: usize,
+    code: GeneticCode,
+    wrap: usize,
+) -> Result<(), String> {
+    if alignment.column_stats().is_some() {
+        return Err("needs the aligned rows, which --stream-output doesn't build".to_string());
+    }
+    let rows = alignment.seqs().iter().zip(nucleotides)
+        .enumerate()
+        .map(|(i, (row, seq))| back_translate(row, seq, frame, code).map_err(|e| format!("Sequence {}: {}", i, e)))
+        .collect::<Result<Vec<String>, String>>()?;
+    let written = std::fs::File::create(filename).and_then(|file| {
+        let mut out = std::io::BufWriter::new(file);
+        backtrace::write_fasta(&mut out, &rows, wrap)?;
+        out.flush()
+    });
+    written.map_err(|e| format!("Error writing codon alignment {}: {}", filename, e))
+}
+
+/// `--translate` of the loaded sequences, keeping what `--back-translate`
+/// needs to put the codons back after the search
+pub struct Translation {
+    nucleotides: Vec<Vec<u8>>,
+    frame: usize,
+    code: GeneticCode,
+}
+
+impl Translation {
+    /// Replace the loaded sequences by their translations in `frame`
+    pub fn apply(frame: usize, code: GeneticCode) -> Result<Self, String> {
+        let nucleotides = translate_loaded(frame, code)?;
+        crate::info!("Translated to protein in frame {} ({:?} genetic code)", frame, code);
+        Ok(Translation { nucleotides, frame, code })
+    }
+
+    /// Write the codon alignment of `alignment` to `filename`, unless
+    /// `summary_only` asks for no output files
+    pub fn write_codons(&self, filename: &str, alignment: &Alignment, options: &AStarOpt) -> Result<(), String> {
+        if options.summary_only {
+            return Ok(());
+        }
+        write_back_translation(filename, alignment, &self.nucleotides, self.frame, self.code, options.wrap)?;
+        crate::info!("Wrote codon alignment {}", filename);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar;
+    use crate::cost::Cost;
+    use crate::heuristic_hpair::HeuristicHPair;
+    use crate::msa_options::AStarOpt;
+    use crate::sequences::StopHandling;
+
+    #[test]
+    fn test_translate_cds() {
+        // Start of human beta-globin (HBB) and its protein, with the stop
+        let cds = b"ATGGTGCATCTGACTCCTGAGGAGAAGTCTGCCGTTACTGCCCTGTGGGGCAAGGTGAACGTGGATTGA";
+        assert_eq!(translate(cds, 1, GeneticCode::Standard), b"MVHLTPEEKSAVTALWGKVNVD*");
+        assert_eq!(translate(&cds[..7], 2, GeneticCode::Standard), b"WC");
+        assert_eq!(translate(b"augnnn", 1, GeneticCode::Standard), b"MX");
+        assert_eq!(translate(b"TGAAGAATA", 1, GeneticCode::VertebrateMitochondrial), b"W*M");
+        assert_eq!("11".parse(), Ok(GeneticCode::Bacterial));
+        assert!("3".parse::<GeneticCode>().is_err());
+    }
+
+    #[test]
+    fn test_back_translation_keeps_codons() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        Sequences::clear();
+        // Two codons dropped from the second, one base before frame 2 of the third
+        let cds = ["ATGGTGCATCTGACTCCTGAGGAGAAGTCTTGA", "ATGGTGCTGACTGAGGAGAAGTCTTGA", "CATGGTGCATCTGACCCTGAGGAGAAGTAA"];
+        for seq in cds {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        let frames = [1, 1, 2];
+        let mut nucleotides = Vec::new();
+        for (i, &frame) in frames.iter().enumerate() {
+            let protein = translate(cds[i].as_bytes(), frame, GeneticCode::Standard);
+            Sequences::replace_seq(i, String::from_utf8(protein).unwrap()).unwrap();
+            nucleotides.push(cds[i].as_bytes().to_vec());
+        }
+        Sequences::normalize(StopHandling::Strip).unwrap();
+        assert_eq!(Sequences::get_seq(1), b"MVLTEEKS");
+        HeuristicHPair::init().unwrap();
+        let alignment = astar::run_astar_uncached(&AStarOpt::builder().build()).unwrap();
+
+        for (i, row) in alignment.seqs().iter().enumerate() {
+            let codon_row = back_translate(row, &nucleotides[i], frames[i], GeneticCode::Standard).unwrap();
+            assert_eq!(codon_row.len(), 3 * row.len());
+            // Codons stay whole, in order, and still code for their column
+            for (column, codon) in codon_row.as_bytes().chunks(3).enumerate() {
+                match row.as_bytes()[column] {
+                    GAP => assert_eq!(codon, b"---"),
+                    residue => assert_eq!(GeneticCode::Standard.translate_codon(codon), residue),
+                }
+            }
+            let degapped: String = codon_row.chars().filter(|&c| c != '-').collect();
+            let start = frames[i] - 1;
+            assert_eq!(degapped, cds[i][start..start + 3 * (degapped.len() / 3)]);
+        }
+
+        assert_eq!(back_translate("MW", b"ATGGTG", 1, GeneticCode::Standard),
+                   Err("Column 1: 'W' isn't the translation of GTG".to_string()));
+        assert_eq!(back_translate("M-V", b"ATG", 1, GeneticCode::Standard),
+                   Err("Column 2 is past the last codon".to_string()));
+    }
+
+    #[test]
+    fn test_codon_alignment_skipped_with_summary_only() {
+        let _guard = crate::test_lock();
+        Cost::set_cost_pam250();
+        Sequences::clear();
+        for seq in ["ATGGTGCATCTGACTCCTGAGGAG", "ATGGTGCTGACTGAGGAG"] {
+            Sequences::set_seq(seq.to_string()).unwrap();
+        }
+        let translation = Translation::apply(1, GeneticCode::Standard).unwrap();
+        assert_eq!(Sequences::get_seq(1), b"MVLTEE");
+        HeuristicHPair::init().unwrap();
+        let alignment = astar::run_astar_uncached(&AStarOpt::builder().summary_only(true).build()).unwrap();
+
+        let file = std::env::temp_dir().join(format!("translate_codons_{}.fasta", std::process::id()));
+        let file = file.to_str().unwrap();
+        translation.write_codons(file, &alignment, &AStarOpt::builder().summary_only(true).build()).unwrap();
+        assert!(!std::path::Path::new(file).exists());
+
+        translation.write_codons(file, &alignment, &AStarOpt::builder().wrap(0).build()).unwrap();
+        let text = std::fs::read_to_string(file).unwrap();
+        let rows: Vec<&str> = text.lines().skip(1).step_by(2).collect();
+        assert_eq!(rows.len(), 2);
+        for (row, cds) in rows.iter().zip(["ATGGTGCATCTGACTCCTGAGGAG", "ATGGTGCTGACTGAGGAG"]) {
+            assert_eq!(row.len(), 3 * alignment.length());
+            assert_eq!(row.replace('-', ""), cds);
+        }
+        std::fs::remove_file(file).unwrap();
+    }
+}
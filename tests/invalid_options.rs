@@ -0,0 +1,32 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief `msa_pastar` rejects a bad thread or hybrid CPU setup with an
+ * error instead of panicking
+ */
+
+use std::process::Command;
+
+/// Run `msa_pastar` on two short sequences with `extra` arguments
+fn run(extra: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_msa_pastar"))
+        .args(["--seq", "ACGT", "--seq", "AGT"])
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_invalid_threads_exit_with_error() {
+    for extra in [
+        &["-t", "0"][..],
+        &["-t", "2", "--p-cores-num", "4", "--p-cores-size", "1"],
+        &["-t", "4", "--p-cores-num", "2", "--p-cores-size", "0"],
+    ] {
+        let output = run(extra);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{:?}: {}", extra, stderr);
+        assert!(stderr.starts_with("Error: "), "{:?}: {}", extra, stderr);
+    }
+}
@@ -0,0 +1,43 @@
+/*!
+ * \author Vinícius Manoel
+ * \copyright MIT License
+ *
+ * \brief `msa_pastar --threads 1` runs the serial search and aligns like
+ * `msa_astar`
+ */
+
+use std::process::Command;
+
+const SEQS: [&str; 3] = ["VLSPADKTNVKAAWGKVGAH", "VHLTPEEKSAVTALWGKV", "GLSDGEWQLVLNVWGKVEAD"];
+
+/// Run `binary` on `SEQS` with `extra` arguments, returning its output and
+/// the FASTA file it wrote
+fn align(binary: &str, extra: &[&str]) -> (String, String) {
+    let file = std::env::temp_dir().join(format!(
+        "threads_one_{}_{}.fasta",
+        std::path::Path::new(binary).file_name().unwrap().to_str().unwrap(),
+        std::process::id()
+    ));
+    let mut command = Command::new(binary);
+    for seq in SEQS {
+        command.args(["--seq", seq]);
+    }
+    let output = command.args(["-f", file.to_str().unwrap()]).args(extra).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let fasta = std::fs::read_to_string(&file).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    (String::from_utf8(output.stdout).unwrap(), fasta)
+}
+
+#[test]
+fn test_threads_one_matches_serial_binary() {
+    let (serial_log, serial) = align(env!("CARGO_BIN_EXE_msa_astar"), &[]);
+    let (parallel_log, parallel) = align(env!("CARGO_BIN_EXE_msa_pastar"), &["--threads", "1"]);
+
+    assert!(parallel_log.contains("Single thread requested, running Serial A-Star"), "{}", parallel_log);
+    assert_eq!(parallel, serial);
+    let score = |log: &str| log.lines().find(|line| line.starts_with("Sum-of-pairs score:")).map(str::to_string);
+    assert!(score(&serial_log).is_some());
+    assert_eq!(score(&parallel_log), score(&serial_log));
+}